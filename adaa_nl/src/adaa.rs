@@ -1,11 +1,22 @@
-use envelope::{Env, LinearEnvelope};
+use envelope::{Env, GenericLinearEnvelope};
 use polylog::Li2;
+use sample::{MemoryFootprint, Sample};
+
+use dc_filter::dc_filter::DCFilter64;
+
+use crate::polynomial::{polynomial_nl, Polynomial};
 
 use nih_plug::prelude::*;
 
 const ERR_TOL: f64 = 1e-5;
 
+/// Largest input magnitude `NonlinearProcessor` passes on to the antiderivatives, anything
+/// hotter (including inf) is clamped to it and NaN is taken as silence. Up to here the second
+/// order differences still resolve to well under 1e-3 of error.
+pub const MAX_INPUT_LEVEL: f64 = 1e4;
+
 #[derive(Enum, Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum ProcessorStyle {
     #[id = "hard clip"]
     #[name = "Hard Clip"]
@@ -16,6 +27,27 @@ pub enum ProcessorStyle {
     #[id = "soft clip x2"]
     #[name = "Soft Clip X2"]
     SoftClipX2 = 2,
+    #[id = "cubic soft clip"]
+    #[name = "Cubic Soft Clip"]
+    CubicSoftClip = 3,
+    #[id = "arctan"]
+    #[name = "Arctan"]
+    Arctan = 4,
+    #[id = "exp soft clip"]
+    #[name = "Exp Soft Clip"]
+    ExpSoftClip = 5,
+    #[id = "diode"]
+    #[name = "Diode"]
+    Diode = 6,
+    #[id = "triangle fold"]
+    #[name = "Triangle Fold"]
+    TriangleFold = 7,
+    #[id = "sine fold"]
+    #[name = "Sine Fold"]
+    SineFold = 8,
+    #[id = "quintic soft clip"]
+    #[name = "Quintic Soft Clip"]
+    QuinticSoftClip = 9,
 }
 use ProcessorStyle::*;
 
@@ -43,8 +75,10 @@ type H2 = fn(f64) -> f64;
 
 const ONE_SIXTH: f64 = 1.0 / 6.0;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+// compared by style and state, function pointer equality isn't reliable
+#[derive(Debug, Copy, Clone)]
 struct ProcState {
+    style: ProcessorStyle,
     x1: f64,
     x2: f64,
     d2: f64,
@@ -56,6 +90,18 @@ struct ProcState {
     nl_func_ad2: H2,
 }
 
+impl PartialEq for ProcState {
+    fn eq(&self, other: &Self) -> bool {
+        self.style == other.style
+            && self.x1 == other.x1
+            && self.x2 == other.x2
+            && self.d2 == other.d2
+            && self.ad1_x1 == other.ad1_x1
+            && self.ad2_x0 == other.ad2_x0
+            && self.ad2_x1 == other.ad2_x1
+    }
+}
+
 impl ProcState {
     const SOFT_CLIP_X2: H = |x| {
         if x >= 0.0 {
@@ -81,14 +127,43 @@ impl ProcState {
         }
     };
 
+    // 1.5 x - 0.5 x^3, flat at +-1 past the knee
+    polynomial_nl!(
+        CUBIC_SOFT_CLIP,
+        CUBIC_SOFT_CLIP_AD1,
+        CUBIC_SOFT_CLIP_AD2,
+        [0.0, 1.5, 0.0, -0.5]
+    );
+
+    // (15 x - 10 x^3 + 3 x^5) / 8, slope (15 / 8)(1 - x^2)^2 so it meets +-1 with zero
+    // slope and zero curvature
+    polynomial_nl!(
+        QUINTIC_SOFT_CLIP,
+        QUINTIC_SOFT_CLIP_AD1,
+        QUINTIC_SOFT_CLIP_AD2,
+        [0.0, 15.0 / 8.0, 0.0, -10.0 / 8.0, 0.0, 3.0 / 8.0]
+    );
+
     const TANH: H = |x| x.tanh();
 
-    const TANH_AD1: H1 = |x| x.cosh().ln();
+    // ln(cosh(x)) = |x| + ln(1 + e^(-2|x|)) - ln(2), cosh itself overflows past ~710
+    #[inline]
+    fn ln_cosh(x: f64) -> f64 {
+        let abs_x = x.abs();
+        abs_x + (-2.0 * abs_x).exp().ln_1p() - core::f64::consts::LN_2
+    }
+
+    const TANH_AD1: H1 = ProcState::ln_cosh;
 
+    // odd, so evaluate at |x| where e^(-2x) can't overflow and Li2 stays in [-1, 0]
     const TANH_AD2: H2 = |x| {
-        let expval = (-2.0 * x).exp();
-        0.5 * (Li2::li2(&(-expval)) - x * (x + 2.0 * (expval + 1.).ln() - 2.0 * x.cosh().ln()))
-            + (core::f64::consts::PI.powi(2) / 24.0)
+        let abs_x = x.abs();
+        let expval = (-2.0 * abs_x).exp();
+        let ad2 = 0.5
+            * (Li2::li2(&(-expval))
+                - abs_x * (abs_x + 2.0 * expval.ln_1p() - 2.0 * ProcState::ln_cosh(abs_x)))
+            + (core::f64::consts::PI.powi(2) / 24.0);
+        ad2.copysign(x)
     };
 
     const HARD_CLIP: H = |x| x.clamp(-1.0, 1.0);
@@ -111,8 +186,118 @@ impl ProcState {
         is_within_range * within_range + is_outside_range * outside_range
     };
 
+    // atan(kx) / k with k = pi / 2, unity slope at 0 and flat at +-1 like tanh but softer.
+    // The antiderivatives are in u = kx.
+    const ARCTAN_K: f64 = core::f64::consts::FRAC_PI_2;
+
+    const ARCTAN: H = |x| (ProcState::ARCTAN_K * x).atan() / ProcState::ARCTAN_K;
+
+    const ARCTAN_AD1: H1 = |x| {
+        let k = ProcState::ARCTAN_K;
+        let u = k * x;
+        (u * u.atan() - 0.5 * (u * u).ln_1p()) / (k * k)
+    };
+
+    const ARCTAN_AD2: H2 = |x| {
+        let k = ProcState::ARCTAN_K;
+        let u = k * x;
+        (0.5 * (u * u - 1.0) * u.atan() + 0.5 * u - 0.5 * u * (u * u).ln_1p()) / (k * k * k)
+    };
+
+    // sign(x) (1 - e^-|x|), unity slope at 0 with a sharper knee than tanh. expm1 keeps the
+    // small inputs from cancelling.
+    const EXP_SOFT_CLIP: H = |x| (-(-x.abs()).exp_m1()).copysign(x);
+
+    const EXP_SOFT_CLIP_AD1: H1 = |x| x.abs() + (-x.abs()).exp_m1();
+
+    const EXP_SOFT_CLIP_AD2: H2 = |x| {
+        let abs_x = x.abs();
+        (0.5 * abs_x * abs_x - abs_x - (-abs_x).exp_m1()).copysign(x)
+    };
+
+    // (1 - e^-kx) / k for x >= 0 and its antiderivatives, both zero at the origin
+    #[inline]
+    fn exp_knee(x: f64, k: f64) -> f64 {
+        -(-k * x).exp_m1() / k
+    }
+
+    #[inline]
+    fn exp_knee_ad1(x: f64, k: f64) -> f64 {
+        x / k + (-k * x).exp_m1() / (k * k)
+    }
+
+    #[inline]
+    fn exp_knee_ad2(x: f64, k: f64) -> f64 {
+        0.5 * x * x / k - x / (k * k) - (-k * x).exp_m1() / (k * k * k)
+    }
+
+    // Unity slope at 0 on both sides, but the negative half clips twice as early and settles
+    // at -0.5 instead of 1, so a sine comes out with even harmonics and a DC offset.
+    const DIODE_KNEE_POS: f64 = 1.0;
+    const DIODE_KNEE_NEG: f64 = 2.0;
+
+    const DIODE: H = |x| {
+        if x >= 0.0 {
+            ProcState::exp_knee(x, ProcState::DIODE_KNEE_POS)
+        } else {
+            -ProcState::exp_knee(-x, ProcState::DIODE_KNEE_NEG)
+        }
+    };
+
+    const DIODE_AD1: H1 = |x| {
+        if x >= 0.0 {
+            ProcState::exp_knee_ad1(x, ProcState::DIODE_KNEE_POS)
+        } else {
+            ProcState::exp_knee_ad1(-x, ProcState::DIODE_KNEE_NEG)
+        }
+    };
+
+    const DIODE_AD2: H2 = |x| {
+        if x >= 0.0 {
+            ProcState::exp_knee_ad2(x, ProcState::DIODE_KNEE_POS)
+        } else {
+            -ProcState::exp_knee_ad2(-x, ProcState::DIODE_KNEE_NEG)
+        }
+    };
+
+    // Unity slope through [-1, 1], then folds back down every 2 past it. u is the offset
+    // from the middle of the period, so all three stay bounded apart from the x / 2 trend
+    // the antiderivative of the nonzero mean AD1 leaves in AD2.
+    #[inline]
+    fn triangle_phase(x: f64) -> f64 {
+        (x + 1.0).rem_euclid(4.0) - 2.0
+    }
+
+    const TRIANGLE_FOLD: H = |x| 1.0 - ProcState::triangle_phase(x).abs();
+
+    const TRIANGLE_FOLD_AD1: H1 = |x| {
+        let u = ProcState::triangle_phase(x);
+        u - 0.5 * u * u.abs() + 0.5
+    };
+
+    const TRIANGLE_FOLD_AD2: H2 = |x| {
+        let u = ProcState::triangle_phase(x);
+        0.5 * x + 0.5 * u * u - ONE_SIXTH * u.abs() * u * u - 1.0 / 3.0
+    };
+
+    // sin(kx) with k = pi / 2, peaks at +-1 and folds back at the same inputs as the triangle
+    const SINE_FOLD_K: f64 = core::f64::consts::FRAC_PI_2;
+
+    const SINE_FOLD: H = |x| (ProcState::SINE_FOLD_K * x).sin();
+
+    const SINE_FOLD_AD1: H1 = |x| {
+        let k = ProcState::SINE_FOLD_K;
+        (1.0 - (k * x).cos()) / k
+    };
+
+    const SINE_FOLD_AD2: H2 = |x| {
+        let k = ProcState::SINE_FOLD_K;
+        (x - (k * x).sin() / k) / k
+    };
+
     pub fn tanh_proc_state() -> ProcState {
         ProcState {
+            style: Tanh,
             x1: 0.0,
             x2: 0.0,
             d2: 0.0,
@@ -127,6 +312,7 @@ impl ProcState {
 
     pub fn hard_clip_proc_state() -> ProcState {
         ProcState {
+            style: HardClip,
             x1: 0.0,
             x2: 0.0,
             d2: 0.0,
@@ -141,6 +327,7 @@ impl ProcState {
 
     pub fn soft_clip_x2_proc_state() -> ProcState {
         ProcState {
+            style: SoftClipX2,
             x1: 0.0,
             x2: 0.0,
             d2: -1.0,
@@ -152,16 +339,128 @@ impl ProcState {
             nl_func_ad2: ProcState::SOFT_CLIP_X2_AD2,
         }
     }
+
+    pub fn cubic_soft_clip_proc_state() -> ProcState {
+        ProcState {
+            style: CubicSoftClip,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::CUBIC_SOFT_CLIP,
+            nl_func_ad1: ProcState::CUBIC_SOFT_CLIP_AD1,
+            nl_func_ad2: ProcState::CUBIC_SOFT_CLIP_AD2,
+        }
+    }
+
+    pub fn arctan_proc_state() -> ProcState {
+        ProcState {
+            style: Arctan,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::ARCTAN,
+            nl_func_ad1: ProcState::ARCTAN_AD1,
+            nl_func_ad2: ProcState::ARCTAN_AD2,
+        }
+    }
+
+    pub fn exp_soft_clip_proc_state() -> ProcState {
+        ProcState {
+            style: ExpSoftClip,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::EXP_SOFT_CLIP,
+            nl_func_ad1: ProcState::EXP_SOFT_CLIP_AD1,
+            nl_func_ad2: ProcState::EXP_SOFT_CLIP_AD2,
+        }
+    }
+
+    pub fn diode_proc_state() -> ProcState {
+        ProcState {
+            style: Diode,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::DIODE,
+            nl_func_ad1: ProcState::DIODE_AD1,
+            nl_func_ad2: ProcState::DIODE_AD2,
+        }
+    }
+
+    pub fn triangle_fold_proc_state() -> ProcState {
+        ProcState {
+            style: TriangleFold,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::TRIANGLE_FOLD,
+            nl_func_ad1: ProcState::TRIANGLE_FOLD_AD1,
+            nl_func_ad2: ProcState::TRIANGLE_FOLD_AD2,
+        }
+    }
+
+    pub fn sine_fold_proc_state() -> ProcState {
+        ProcState {
+            style: SineFold,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::SINE_FOLD,
+            nl_func_ad1: ProcState::SINE_FOLD_AD1,
+            nl_func_ad2: ProcState::SINE_FOLD_AD2,
+        }
+    }
+
+    pub fn quintic_soft_clip_proc_state() -> ProcState {
+        ProcState {
+            style: QuinticSoftClip,
+            x1: 0.0,
+            x2: 0.0,
+            d2: 0.0,
+            ad1_x1: 0.0,
+            ad2_x0: 0.0,
+            ad2_x1: 0.0,
+            nl_func: ProcState::QUINTIC_SOFT_CLIP,
+            nl_func_ad1: ProcState::QUINTIC_SOFT_CLIP_AD1,
+            nl_func_ad2: ProcState::QUINTIC_SOFT_CLIP_AD2,
+        }
+    }
 }
 
-type ProcAlg = fn(f64, &mut ProcState) -> f32;
+type ProcAlg = fn(f64, &mut ProcState) -> f64;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone)]
 struct ADAA {
     current_proc_state: ProcState,
+    order: AntiderivativeOrder,
     proc_alg: ProcAlg,
 }
 
+impl PartialEq for ADAA {
+    fn eq(&self, other: &Self) -> bool {
+        self.order == other.order && self.current_proc_state == other.current_proc_state
+    }
+}
+
 impl ADAA {
     const PROCESS_FIRST_ORDER: ProcAlg =
         |x: f64, y: &mut ProcState| ADAA::process_first_order(y, x);
@@ -175,6 +474,16 @@ impl ADAA {
                 State(Tanh, _) => ProcState::tanh_proc_state(),
                 State(HardClip, _) => ProcState::hard_clip_proc_state(),
                 State(SoftClipX2, _) => ProcState::soft_clip_x2_proc_state(),
+                State(CubicSoftClip, _) => ProcState::cubic_soft_clip_proc_state(),
+                State(Arctan, _) => ProcState::arctan_proc_state(),
+                State(ExpSoftClip, _) => ProcState::exp_soft_clip_proc_state(),
+                State(Diode, _) => ProcState::diode_proc_state(),
+                State(TriangleFold, _) => ProcState::triangle_fold_proc_state(),
+                State(SineFold, _) => ProcState::sine_fold_proc_state(),
+                State(QuinticSoftClip, _) => ProcState::quintic_soft_clip_proc_state(),
+            },
+            order: match nl_state {
+                State(_, order) => order,
             },
             proc_alg: match nl_state {
                 State(_, FirstOrder) => ADAA::PROCESS_FIRST_ORDER,
@@ -188,12 +497,12 @@ impl ADAA {
     }
 
     #[inline]
-    fn process(&mut self, val: f64) -> f32 {
+    fn process(&mut self, val: f64) -> f64 {
         (self.proc_alg)(val, &mut self.current_proc_state)
     }
 
     #[inline]
-    fn process_first_order(state: &mut ProcState, val: f64) -> f32 {
+    fn process_first_order(state: &mut ProcState, val: f64) -> f64 {
         let diff = val - state.x1;
         let ad1_x0 = (state.nl_func_ad1)(val);
 
@@ -206,11 +515,11 @@ impl ADAA {
         state.x1 = val;
         state.ad1_x1 = ad1_x0;
 
-        result as f32
+        result
     }
 
     #[inline]
-    fn process_second_order(state: &mut ProcState, val: f64) -> f32 {
+    fn process_second_order(state: &mut ProcState, val: f64) -> f64 {
         state.ad2_x0 = (state.nl_func_ad2)(val);
         let d1 = if (val - state.x1).abs() < ERR_TOL {
             (state.nl_func_ad1)(0.5 * (val + state.x1))
@@ -237,33 +546,64 @@ impl ADAA {
         state.x1 = val;
         state.ad2_x1 = state.ad2_x0;
 
-        result as f32
+        result
     }
 }
 
+pub type NonlinearProcessor = GenericNonlinearProcessor<f32>;
+pub type NonlinearProcessor64 = GenericNonlinearProcessor<f64>;
+
+/// `T` is the sample type of the input, the output and the fades. The antiderivatives are
+/// evaluated in f64 whatever `T` is, their differences cancel badly in f32.
 #[derive(Debug, Clone)]
-pub struct NonlinearProcessor {
+pub struct GenericNonlinearProcessor<T: Sample> {
     state: ProcessorState,
     proc: ADAA,
-    fade_out: Option<LinearEnvelope>,
-    fade_in: Option<LinearEnvelope>,
+    fade_out: Option<GenericLinearEnvelope<T>>,
+    fade_in: Option<GenericLinearEnvelope<T>>,
+    bias: f64,
+    dc_filter: DCFilter64,
 }
 
 const FADE_LEN: i32 = 5000;
 
-impl NonlinearProcessor {
+impl<T: Sample> GenericNonlinearProcessor<T> {
     pub fn new() -> Self {
-        NonlinearProcessor {
+        GenericNonlinearProcessor {
             state: State(HardClip, FirstOrder),
             proc: ADAA::from_nl_state(State(HardClip, FirstOrder)),
             fade_out: None,
-            fade_in: Some(LinearEnvelope::fade_in(FADE_LEN)),
+            fade_in: Some(GenericLinearEnvelope::fade_in(FADE_LEN)),
+            bias: 0.0,
+            dc_filter: DCFilter64::new(),
         }
     }
 
     fn change_state(&mut self) {
         // nih_dbg!("Changing state -- fade out is complete");
         self.proc = ADAA::from_nl_state(self.state);
+        self.dc_filter.reset();
+    }
+
+    /// Offset added to the input ahead of the nonlinearity, clamped to +-`MAX_INPUT_LEVEL`.
+    /// Any bias tilts the curve off center and brings in even harmonics, the DC it adds to
+    /// the output is filtered back out.
+    pub fn set_bias(&mut self, bias: T) {
+        let bias = bias.to_f64().clamp(-MAX_INPUT_LEVEL, MAX_INPUT_LEVEL);
+        if self.bias == 0.0 && bias != 0.0 {
+            self.dc_filter.reset();
+        }
+        self.bias = bias;
+    }
+
+    pub fn bias(&self) -> T {
+        T::from_f64(self.bias)
+    }
+
+    // symmetric curves with no bias keep a zero mean and skip the DC filter
+    #[inline]
+    fn removes_dc(&self) -> bool {
+        self.bias != 0.0 || self.proc.current_proc_state.style == Diode
     }
 
     pub fn compare_and_change_state(&mut self, other_state: ProcessorState) {
@@ -274,22 +614,63 @@ impl NonlinearProcessor {
                     // nih_dbg!(&self.state);
                     // nih_dbg!(&other_state);
                     self.state = other_state;
-                    self.fade_out = Some(LinearEnvelope::fade_out(FADE_LEN));
+                    self.fade_out = Some(GenericLinearEnvelope::fade_out(FADE_LEN));
                 }
             }
         }
     }
 
+    /// Clears the antiderivative history, the style and any fade in flight carry on
+    pub fn reset(&mut self) {
+        self.proc = ADAA::from_nl_state(State(self.proc.current_proc_state.style, self.proc.order));
+        self.dc_filter.reset();
+    }
+
+    /// For transport jumps, cancels any fade in flight and switches straight to the last
+    /// requested state with no history, then fades in like a new processor
+    pub fn hard_reset(&mut self) {
+        self.change_state();
+        self.fade_out = None;
+        self.fade_in = Some(GenericLinearEnvelope::fade_in(FADE_LEN));
+    }
+
     #[inline]
-    pub fn process(&mut self, val: f32) -> f32 {
-        let mut nl_process = self.proc.process(val as f64) as f32;
+    fn process_unfaded(&mut self, val: T) -> T {
+        // clamp passes NaN through, and one NaN would stay in the ADAA history for good
+        let val = val.to_f64();
+        let val = if val.is_nan() { 0.0 } else { val };
+        let val = (val + self.bias).clamp(-MAX_INPUT_LEVEL, MAX_INPUT_LEVEL);
+        let out = self.proc.process(val);
+        if self.removes_dc() {
+            T::from_f64(self.dc_filter.process(out))
+        } else {
+            T::from_f64(out)
+        }
+    }
+
+    /// Same as calling `process` on every sample, the envelope checks only run while a fade
+    /// is active
+    pub fn process_block(&mut self, block: &mut [T]) {
+        let mut faded = 0;
+        while faded < block.len() && (self.fade_out.is_some() || self.fade_in.is_some()) {
+            block[faded] = self.process(block[faded]);
+            faded += 1;
+        }
+        block[faded..]
+            .iter_mut()
+            .for_each(|v| *v = self.process_unfaded(*v));
+    }
+
+    #[inline]
+    pub fn process(&mut self, val: T) -> T {
+        let mut nl_process = self.process_unfaded(val);
 
         if let Some(env) = &mut self.fade_out {
             nl_process *= env.consume();
             if env.target_reached() {
                 self.change_state();
                 // nih_dbg!("Setting fade in to SOME --- setting fade_out to NONE");
-                self.fade_in = Some(LinearEnvelope::fade_in(FADE_LEN));
+                self.fade_in = Some(GenericLinearEnvelope::fade_in(FADE_LEN));
                 self.fade_out = None;
             }
         }
@@ -306,6 +687,8 @@ impl NonlinearProcessor {
     }
 }
 
+impl<T: Sample> MemoryFootprint for GenericNonlinearProcessor<T> {}
+
 #[cfg(test)]
 
 mod test {
@@ -335,21 +718,10 @@ mod test {
         let expected_adaa = ADAA::from_nl_state(State(HardClip, FirstOrder));
         let expected_adaa_after = ADAA::from_nl_state(State(HardClip, SecondOrder));
 
-        let _first_order_hc_adaa = ADAA {
-            current_proc_state: ProcState::hard_clip_proc_state(),
-            proc_alg: ADAA::PROCESS_FIRST_ORDER,
-        };
-
-        let _second_order_hc_adaa = ADAA {
-            current_proc_state: ProcState::hard_clip_proc_state(),
-            proc_alg: ADAA::PROCESS_SECOND_ORDER,
-        };
-
         assert_eq!(proc.state, State(HardClip, FirstOrder));
         assert_eq!(
-            proc.proc.proc_alg,
-            ADAA::PROCESS_FIRST_ORDER,
-            "proc state alg not ADAA:PROCESS_FIRST_ORDER before change of state"
+            proc.proc.order, FirstOrder,
+            "proc state alg not first order before change of state"
         );
 
         assert_eq!(proc.proc.current_proc_state, expected_proc_state);
@@ -367,9 +739,8 @@ mod test {
         for _ in 0..FADE_LEN - 1 {
             proc.process(0.0);
             assert_eq!(
-                proc.proc.proc_alg,
-                ADAA::PROCESS_FIRST_ORDER,
-                "proc state alg not ADAA:PROCESS_FIRST_ORDER during fade out"
+                proc.proc.order, FirstOrder,
+                "proc state alg not first order during fade out"
             );
         }
 
@@ -381,23 +752,14 @@ mod test {
         for _ in 0..FADE_LEN {
             proc.process(0.0);
             assert_eq!(
-                proc.proc.proc_alg,
-                ADAA::PROCESS_SECOND_ORDER,
+                proc.proc.order, SecondOrder,
                 "proc state alg not processes second order during fade in "
             );
         }
 
         assert!(proc.fade_in.is_none());
         assert_eq!(proc.state, State(HardClip, SecondOrder));
-        assert_eq!(proc.proc.current_proc_state.nl_func, ProcState::HARD_CLIP);
-        assert_eq!(
-            proc.proc.current_proc_state.nl_func_ad1,
-            ProcState::HARD_CLIP_AD1
-        );
-        assert_eq!(
-            proc.proc.current_proc_state.nl_func_ad2,
-            ProcState::HARD_CLIP_AD2
-        );
+        assert_eq!(proc.proc.current_proc_state.style, HardClip);
 
         assert_eq!(proc.proc.current_proc_state, expected_proc_state);
         assert_eq!(proc.proc, expected_adaa_after);
@@ -405,23 +767,43 @@ mod test {
 
     #[test]
     fn test_proc_state_internals() {
-        let proc_tanh_ad1 = ProcState::tanh_proc_state();
+        // the stored functions are the ones for the style, checked by value
+        let check = |ps: ProcState, f: H, f_ad1: H1, f_ad2: H2| {
+            INPUT_LINSPACE.iter().for_each(|v| {
+                assert_eq!((ps.nl_func)(*v), f(*v));
+                assert_eq!((ps.nl_func_ad1)(*v), f_ad1(*v));
+                assert_eq!((ps.nl_func_ad2)(*v), f_ad2(*v));
+            })
+        };
 
-        assert_eq!(proc_tanh_ad1.nl_func, ProcState::TANH);
-        assert_eq!(proc_tanh_ad1.nl_func_ad1, ProcState::TANH_AD1);
-        assert_ne!(proc_tanh_ad1.nl_func_ad2, ProcState::TANH_AD2);
+        let proc_tanh_ad1 = ProcState::tanh_proc_state();
+        assert_eq!(proc_tanh_ad1.style, Tanh);
+        check(
+            proc_tanh_ad1,
+            ProcState::TANH,
+            ProcState::TANH_AD1,
+            ProcState::TANH_AD2,
+        );
 
         let proc_hard_clip_ad1 = ProcState::hard_clip_proc_state();
-
-        assert_eq!(proc_hard_clip_ad1.nl_func, ProcState::HARD_CLIP);
-        assert_eq!(proc_hard_clip_ad1.nl_func_ad1, ProcState::HARD_CLIP_AD1);
-        assert_ne!(proc_hard_clip_ad1.nl_func_ad2, ProcState::HARD_CLIP_AD2);
+        assert_eq!(proc_hard_clip_ad1.style, HardClip);
+        check(
+            proc_hard_clip_ad1,
+            ProcState::HARD_CLIP,
+            ProcState::HARD_CLIP_AD1,
+            ProcState::HARD_CLIP_AD2,
+        );
 
         let proc_soft_clip_ad1 = ProcState::soft_clip_x2_proc_state();
+        assert_eq!(proc_soft_clip_ad1.style, SoftClipX2);
+        check(
+            proc_soft_clip_ad1,
+            ProcState::SOFT_CLIP_X2,
+            ProcState::SOFT_CLIP_X2_AD1,
+            ProcState::SOFT_CLIP_X2_AD2,
+        );
 
-        assert_eq!(proc_soft_clip_ad1.nl_func, ProcState::SOFT_CLIP_X2);
-        assert_eq!(proc_soft_clip_ad1.nl_func_ad1, ProcState::SOFT_CLIP_X2_AD1);
-        assert_ne!(proc_soft_clip_ad1.nl_func_ad2, ProcState::SOFT_CLIP_X2_AD2);
+        assert_ne!(proc_tanh_ad1, proc_hard_clip_ad1);
     }
 
     #[test]
@@ -461,6 +843,451 @@ mod test {
             adaa_sc_ad2.current_proc_state,
             ProcState::soft_clip_x2_proc_state()
         );
+
+        let adaa_csc_ad2 = ADAA::from_nl_state(State(CubicSoftClip, SecondOrder));
+        assert_eq!(
+            adaa_csc_ad2.current_proc_state,
+            ProcState::cubic_soft_clip_proc_state()
+        );
+
+        let adaa_qsc_ad2 = ADAA::from_nl_state(State(QuinticSoftClip, SecondOrder));
+        assert_eq!(
+            adaa_qsc_ad2.current_proc_state,
+            ProcState::quintic_soft_clip_proc_state()
+        );
+    }
+
+    #[test]
+    fn tanh_antiderivatives_extreme_inputs() {
+        for x in [1e3, 800.0, 1e4] {
+            let ad1 = (ProcState::TANH_AD1)(x);
+            let ad2 = (ProcState::TANH_AD2)(x);
+            assert!((ad1 - (x - core::f64::consts::LN_2)).abs() < 1e-9);
+            assert_eq!((ProcState::TANH_AD1)(-x), ad1);
+            assert_eq!((ProcState::TANH_AD2)(-x), -ad2);
+
+            let expected_ad2 =
+                0.5 * x * x - x * core::f64::consts::LN_2 + core::f64::consts::PI.powi(2) / 24.0;
+            assert!((ad2 - expected_ad2).abs() / expected_ad2 < 1e-12);
+        }
+
+        // unchanged in the range the old closed form handled
+        for x in INPUT_LINSPACE {
+            let expval = (-2.0 * x).exp();
+            let old_ad2 = 0.5
+                * (Li2::li2(&(-expval)) - x * (x + 2.0 * (expval + 1.).ln() - 2.0 * x.cosh().ln()))
+                + (core::f64::consts::PI.powi(2) / 24.0);
+            assert!(((ProcState::TANH_AD1)(x) - x.cosh().ln()).abs() < 1e-12);
+            assert!(
+                ((ProcState::TANH_AD2)(x) - old_ad2).abs() < 1e-12,
+                "x: {}",
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn process_extreme_drive_is_finite() {
+        let input = [
+            1e3,
+            -1e3,
+            1e3,
+            999.0,
+            -1e3,
+            -1e3,
+            1e6,
+            f32::INFINITY,
+            -1e3,
+            0.5,
+        ]
+        .into_iter()
+        // held long enough for the DC filter to settle before the step down
+        .chain(std::iter::repeat_n(1e3, 2000))
+        .chain(std::iter::repeat_n(-1e3, 10))
+        .collect::<Vec<f32>>();
+        for style in [
+            Tanh,
+            HardClip,
+            SoftClipX2,
+            CubicSoftClip,
+            Arctan,
+            ExpSoftClip,
+            Diode,
+            TriangleFold,
+            SineFold,
+            QuinticSoftClip,
+        ] {
+            for order in [FirstOrder, SecondOrder] {
+                let mut proc = NonlinearProcessor::new();
+                proc.proc = ADAA::from_nl_state(State(style, order));
+                proc.fade_in = None;
+                // the DC filter is a highpass whose impulse response sums to 2 in absolute
+                // value, so a curve spanning [-0.5, 1] can come out anywhere in 2 * +-0.75
+                let bound = if proc.removes_dc() { 1.5 } else { 1.0 };
+
+                let mut peak: f32 = 0.0;
+                for v in input.iter().copied() {
+                    let out = proc.process(v);
+                    peak = peak.max(out.abs());
+                    assert!(
+                        out.is_finite() && out.abs() <= bound + 1e-2,
+                        "{:?} {:?}: {} -> {}",
+                        style,
+                        order,
+                        v,
+                        out
+                    );
+                }
+                if style == Diode {
+                    // the step from the settled top of the curve to its bottom
+                    assert!(peak > 1.4, "{:?}: {}", order, peak);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn process_nan_is_silence() {
+        let input = (0..64).map(|i| (i as f32 * 0.2).sin() * 3.0);
+        for style in [Tanh, HardClip, Diode] {
+            for order in [FirstOrder, SecondOrder] {
+                let mut proc = NonlinearProcessor::new();
+                proc.compare_and_change_state(State(style, order));
+                proc.set_bias(0.3);
+                let mut reference = proc.clone();
+
+                for (i, v) in input.clone().enumerate() {
+                    let (with_nan, with_zero) = if i == 20 { (f32::NAN, 0.0) } else { (v, v) };
+                    let out = proc.process(with_nan);
+                    assert!(out.is_finite(), "{:?} {:?}: {}", style, order, i);
+                    assert_eq!(out, reference.process(with_zero));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn process_cubic_soft_clip() {
+        // slow enough that both orders should land close to the static curve
+        let input: Vec<f64> = (0..400).map(|i| -2.0 + i as f64 * 0.01).collect();
+
+        for order in [FirstOrder, SecondOrder] {
+            let mut adaa = ADAA::from_nl_state(State(CubicSoftClip, order));
+            let result: Vec<_> = input.iter().map(|v| adaa.process(*v)).collect();
+            let expected: Vec<_> = input
+                .iter()
+                .map(|v| {
+                    let x = v.clamp(-1.0, 1.0);
+                    1.5 * x - 0.5 * x.powi(3)
+                })
+                .collect();
+
+            check_results_64(&result[2..], &expected[1..expected.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn cubic_soft_clip_antiderivatives() {
+        // hand derived inside the knee, then continued linearly and quadratically past it
+        for x in [
+            -3.0_f64, -1.5, -1.0, -0.6, -0.1, 0.0, 0.2, 0.75, 1.0, 2.0, 1e3,
+        ] {
+            let (ad1, ad2) = if x.abs() <= 1.0 {
+                (
+                    0.75 * x * x - 0.125 * x.powi(4),
+                    0.25 * x.powi(3) - 0.025 * x.powi(5),
+                )
+            } else {
+                let past_knee = x.abs() - 1.0;
+                (
+                    0.625 + past_knee,
+                    (0.225 + 0.625 * past_knee + 0.5 * past_knee * past_knee).copysign(x),
+                )
+            };
+            let tol = 1e-12 * (1.0 + x * x);
+            assert!(
+                ((ProcState::CUBIC_SOFT_CLIP_AD1)(x) - ad1).abs() < tol,
+                "x: {}",
+                x
+            );
+            assert!(
+                ((ProcState::CUBIC_SOFT_CLIP_AD2)(x) - ad2).abs() < tol,
+                "x: {}",
+                x
+            );
+        }
+        assert_eq!((ProcState::CUBIC_SOFT_CLIP)(5.0), 1.0);
+        assert_eq!((ProcState::CUBIC_SOFT_CLIP)(-5.0), -1.0);
+
+        let adaa_csc_ad1 = ADAA::from_nl_state(State(CubicSoftClip, FirstOrder));
+        assert_eq!(adaa_csc_ad1.order, FirstOrder);
+        assert_eq!(adaa_csc_ad1.current_proc_state.style, CubicSoftClip);
+    }
+
+    #[test]
+    fn process_quintic_soft_clip() {
+        // slow enough that both orders should land close to the static curve
+        let input: Vec<f64> = (0..400).map(|i| -2.0 + i as f64 * 0.01).collect();
+
+        for order in [FirstOrder, SecondOrder] {
+            let mut adaa = ADAA::from_nl_state(State(QuinticSoftClip, order));
+            let result: Vec<_> = input.iter().map(|v| adaa.process(*v)).collect();
+            let expected: Vec<_> = input
+                .iter()
+                .map(|v| {
+                    let x = v.clamp(-1.0, 1.0);
+                    (15.0 * x - 10.0 * x.powi(3) + 3.0 * x.powi(5)) / 8.0
+                })
+                .collect();
+
+            check_results_64(&result[2..], &expected[1..expected.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn arctan_antiderivatives() {
+        // central differences of each antiderivative give the one below it
+        let h = 1e-5;
+        for x in [-30.0, -2.5, -0.4, 0.0, 0.3, 1.0, 7.0, 1e3] {
+            let d1 = ((ProcState::ARCTAN_AD1)(x + h) - (ProcState::ARCTAN_AD1)(x - h)) / (2.0 * h);
+            let d2 = ((ProcState::ARCTAN_AD2)(x + h) - (ProcState::ARCTAN_AD2)(x - h)) / (2.0 * h);
+            let tol = 1e-6 * (1.0 + x * x);
+            assert!((d1 - (ProcState::ARCTAN)(x)).abs() < tol, "x: {}", x);
+            assert!((d2 - (ProcState::ARCTAN_AD1)(x)).abs() < tol, "x: {}", x);
+        }
+        assert_eq!((ProcState::ARCTAN_AD1)(0.0), 0.0);
+        assert_eq!((ProcState::ARCTAN_AD2)(0.0), 0.0);
+        assert!(((ProcState::ARCTAN)(1e9) - 1.0).abs() < 1e-8);
+
+        let input: Vec<f64> = (0..400).map(|i| -2.0 + i as f64 * 0.01).collect();
+        for order in [FirstOrder, SecondOrder] {
+            let mut adaa = ADAA::from_nl_state(State(Arctan, order));
+            let result: Vec<_> = input.iter().map(|v| adaa.process(*v)).collect();
+            let expected: Vec<_> = input.iter().map(|v| (ProcState::ARCTAN)(*v)).collect();
+            check_results_64(&result[2..], &expected[1..expected.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn exp_soft_clip_antiderivatives() {
+        let h = 1e-5;
+        for x in [-30.0, -2.5, -0.4, 0.0, 0.3, 1.0, 7.0, 1e3] {
+            let d1 = ((ProcState::EXP_SOFT_CLIP_AD1)(x + h)
+                - (ProcState::EXP_SOFT_CLIP_AD1)(x - h))
+                / (2.0 * h);
+            let d2 = ((ProcState::EXP_SOFT_CLIP_AD2)(x + h)
+                - (ProcState::EXP_SOFT_CLIP_AD2)(x - h))
+                / (2.0 * h);
+            let tol = 1e-6 * (1.0 + x * x);
+            assert!((d1 - (ProcState::EXP_SOFT_CLIP)(x)).abs() < tol, "x: {}", x);
+            assert!(
+                (d2 - (ProcState::EXP_SOFT_CLIP_AD1)(x)).abs() < tol,
+                "x: {}",
+                x
+            );
+        }
+        assert_eq!((ProcState::EXP_SOFT_CLIP)(1e-300), 1e-300);
+        assert_eq!((ProcState::EXP_SOFT_CLIP)(-800.0), -1.0);
+
+        let input: Vec<f64> = (0..400).map(|i| -2.0 + i as f64 * 0.01).collect();
+        for order in [FirstOrder, SecondOrder] {
+            let mut adaa = ADAA::from_nl_state(State(ExpSoftClip, order));
+            let result: Vec<_> = input.iter().map(|v| adaa.process(*v)).collect();
+            let expected: Vec<_> = input
+                .iter()
+                .map(|v| (ProcState::EXP_SOFT_CLIP)(*v))
+                .collect();
+            check_results_64(&result[2..], &expected[1..expected.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn diode_antiderivatives() {
+        let h = 1e-5;
+        for x in [-30.0, -2.5, -0.4, 0.0, 0.3, 1.0, 7.0, 1e3] {
+            let d1 = ((ProcState::DIODE_AD1)(x + h) - (ProcState::DIODE_AD1)(x - h)) / (2.0 * h);
+            let d2 = ((ProcState::DIODE_AD2)(x + h) - (ProcState::DIODE_AD2)(x - h)) / (2.0 * h);
+            let tol = 1e-6 * (1.0 + x * x);
+            assert!((d1 - (ProcState::DIODE)(x)).abs() < tol, "x: {}", x);
+            assert!((d2 - (ProcState::DIODE_AD1)(x)).abs() < tol, "x: {}", x);
+        }
+        assert_eq!((ProcState::DIODE)(800.0), 1.0);
+        assert_eq!((ProcState::DIODE)(-800.0), -0.5);
+
+        // the ADAA core on its own, before any DC removal
+        let input: Vec<f64> = (0..400).map(|i| -2.0 + i as f64 * 0.01).collect();
+        for order in [FirstOrder, SecondOrder] {
+            let mut adaa = ADAA::from_nl_state(State(Diode, order));
+            let result: Vec<_> = input.iter().map(|v| adaa.process(*v)).collect();
+            let expected: Vec<_> = input.iter().map(|v| (ProcState::DIODE)(*v)).collect();
+            check_results_64(&result[2..], &expected[1..expected.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn wavefolder_antiderivatives() {
+        let folders: [(ProcessorStyle, H, H1, H2); 2] = [
+            (
+                TriangleFold,
+                ProcState::TRIANGLE_FOLD,
+                ProcState::TRIANGLE_FOLD_AD1,
+                ProcState::TRIANGLE_FOLD_AD2,
+            ),
+            (
+                SineFold,
+                ProcState::SINE_FOLD,
+                ProcState::SINE_FOLD_AD1,
+                ProcState::SINE_FOLD_AD2,
+            ),
+        ];
+
+        let h = 1e-5;
+        // clear of the triangle corners, where the central difference of AD1 is only O(h)
+        for (_, f, f_ad1, f_ad2) in folders {
+            for x in [-30.3, -2.5, -0.4, 0.0, 0.3, 1.7, 7.2, 1e3 + 0.5] {
+                let d1 = (f_ad1(x + h) - f_ad1(x - h)) / (2.0 * h);
+                let d2 = (f_ad2(x + h) - f_ad2(x - h)) / (2.0 * h);
+                let tol = 1e-6 * (1.0 + x * x);
+                assert!((d1 - f(x)).abs() < tol, "x: {}", x);
+                assert!((d2 - f_ad1(x)).abs() < tol, "x: {}", x);
+            }
+            assert_eq!(f_ad1(0.0), 0.0);
+            assert!(f_ad2(0.0).abs() < 1e-15);
+            assert!((f(1.0) - 1.0).abs() < 1e-15);
+            assert!((f(3.0) + 1.0).abs() < 1e-15);
+        }
+        // folds back through zero instead of clipping
+        assert_eq!((ProcState::TRIANGLE_FOLD)(1.5), 0.5);
+        assert_eq!((ProcState::TRIANGLE_FOLD)(-2.0), 0.0);
+
+        let input: Vec<f64> = (0..800).map(|i| -2.0 + i as f64 * 0.005).collect();
+        for (style, f, _, _) in folders {
+            for order in [FirstOrder, SecondOrder] {
+                let mut adaa = ADAA::from_nl_state(State(style, order));
+                let result: Vec<_> = input.iter().map(|v| adaa.process(*v)).collect();
+                let expected: Vec<_> = input.iter().map(|v| f(*v)).collect();
+                check_results_64(&result[2..], &expected[1..expected.len() - 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn asymmetric_output_has_no_dc() {
+        let input: Vec<f32> = (0..20000)
+            .map(|i| (core::f32::consts::TAU * i as f32 / 100.0).sin() * 2.0)
+            .collect();
+
+        let mut diode = NonlinearProcessor::new();
+        diode.compare_and_change_state(State(Diode, SecondOrder));
+        diode.hard_reset();
+
+        let mut biased = NonlinearProcessor::new();
+        biased.compare_and_change_state(State(Tanh, FirstOrder));
+        biased.hard_reset();
+        biased.set_bias(0.5);
+        assert_eq!(biased.bias(), 0.5);
+
+        for proc in [&mut diode, &mut biased] {
+            let result: Vec<f32> = input.iter().map(|v| proc.process(*v)).collect();
+            // whole periods once the fade in and the DC filter have settled
+            let tail = &result[10000..];
+            let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+            let max = tail.iter().cloned().fold(f32::MIN, f32::max);
+            let min = tail.iter().cloned().fold(f32::MAX, f32::min);
+            assert!(mean.abs() < 1e-2, "mean: {}", mean);
+            assert!((max + min).abs() > 0.05, "max: {}, min: {}", max, min);
+            assert!(result.iter().all(|v| v.is_finite()));
+        }
+
+        // no bias on a symmetric curve leaves the output untouched
+        let mut plain = NonlinearProcessor::new();
+        let mut unbiased = NonlinearProcessor::new();
+        unbiased.set_bias(0.3);
+        unbiased.set_bias(0.0);
+        let result: Vec<f32> = input.iter().map(|v| unbiased.process(*v)).collect();
+        let expected: Vec<f32> = input.iter().map(|v| plain.process(*v)).collect();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn process_block_matches_process() {
+        let input: Vec<f32> = (0..3 * FADE_LEN)
+            .map(|i| (i as f32 * 0.01).sin() * 3.0)
+            .collect();
+        let mut per_sample = NonlinearProcessor::new();
+        let mut block_proc = per_sample.clone();
+
+        let mut expected = Vec::new();
+        let mut result = Vec::new();
+        input.chunks(480).enumerate().for_each(|(i, chunk)| {
+            if i == 5 {
+                // lands the end of the fade out inside a block
+                per_sample.compare_and_change_state(State(Tanh, SecondOrder));
+                block_proc.compare_and_change_state(State(Tanh, SecondOrder));
+            }
+            expected.extend(chunk.iter().map(|v| per_sample.process(*v)));
+            let mut block = chunk.to_vec();
+            block_proc.process_block(&mut block);
+            result.extend(block);
+        });
+
+        assert_eq!(result, expected);
+        assert!(block_proc.fade_in.is_none() && block_proc.fade_out.is_none());
+    }
+
+    #[test]
+    fn hard_reset_matches_new() {
+        let input: Vec<f32> = (0..2 * FADE_LEN)
+            .map(|i| (i as f32 * 0.03).sin() * 2.0)
+            .collect();
+        let mut proc = NonlinearProcessor::new();
+        proc.compare_and_change_state(State(Tanh, SecondOrder));
+        input.iter().take(FADE_LEN as usize / 2).for_each(|v| {
+            proc.process(*v);
+        });
+
+        // the soft reset leaves the fade out running
+        proc.reset();
+        assert!(proc.fade_out.is_some());
+        assert_eq!(proc.proc, ADAA::from_nl_state(State(HardClip, FirstOrder)));
+
+        proc.hard_reset();
+        let mut fresh = NonlinearProcessor::new();
+        fresh.state = State(Tanh, SecondOrder);
+        fresh.change_state();
+
+        let result: Vec<f32> = input.iter().map(|v| proc.process(*v)).collect();
+        let expected: Vec<f32> = input.iter().map(|v| fresh.process(*v)).collect();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn f64_processor_matches_f32() {
+        let input: Vec<f64> = (0..3 * FADE_LEN)
+            .map(|i| (i as f64 * 0.01).sin() * 3.0)
+            .collect();
+
+        for (state, bias) in [
+            (State(Tanh, FirstOrder), 0.0),
+            (State(Diode, SecondOrder), 0.0),
+            (State(CubicSoftClip, SecondOrder), 0.4),
+        ] {
+            let mut single = NonlinearProcessor::new();
+            let mut double = NonlinearProcessor64::new();
+            single.compare_and_change_state(state);
+            double.compare_and_change_state(state);
+            single.set_bias(bias as f32);
+            double.set_bias(bias);
+
+            let mut block = input.clone();
+            double.process_block(&mut block);
+            input.iter().zip(block).for_each(|(v, out)| {
+                let expected = single.process(*v as f32) as f64;
+                // the f32 fade envelope drifts by about 1e-5 over its length
+                assert!((out - expected).abs() < 1e-4, "{:?}: {}", state, v);
+            });
+            assert!(double.fade_in.is_none() && double.fade_out.is_none());
+        }
     }
 
     /*
@@ -1 +1,2 @@
 pub mod adaa;
+mod polynomial;
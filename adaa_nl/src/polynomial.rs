@@ -0,0 +1,252 @@
+const MAX_TERMS: usize = 16;
+
+/// Value, first and second antiderivative at one end of [-1, 1]
+#[derive(Debug, Clone, Copy)]
+struct Knee {
+    f: f64,
+    ad1: f64,
+    ad2: f64,
+}
+
+/// Polynomial `f(x) = c[0] + c[1] x + c[2] x^2 + ...` on [-1, 1], held at `f(1)` above it
+/// and at `f(-1)` below it.
+///
+/// The first and second antiderivative coefficients and the values at both knees are all
+/// worked out in `new`, which is a `const fn`, so every style built from a polynomial gets
+/// exact closed forms without any hand derivation. Both antiderivatives are zero at the
+/// origin, like the hand written ones in `adaa`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Polynomial {
+    f: [f64; MAX_TERMS + 2],
+    ad1: [f64; MAX_TERMS + 2],
+    ad2: [f64; MAX_TERMS + 2],
+    len: usize,
+    upper: Knee,
+    lower: Knee,
+}
+
+impl Polynomial {
+    pub(crate) const fn new(coefs: &[f64]) -> Self {
+        assert!(
+            !coefs.is_empty() && coefs.len() <= MAX_TERMS,
+            "unsupported number of polynomial terms"
+        );
+
+        let mut poly = Polynomial {
+            f: [0.0; MAX_TERMS + 2],
+            ad1: [0.0; MAX_TERMS + 2],
+            ad2: [0.0; MAX_TERMS + 2],
+            len: coefs.len(),
+            upper: Knee {
+                f: 0.0,
+                ad1: 0.0,
+                ad2: 0.0,
+            },
+            lower: Knee {
+                f: 0.0,
+                ad1: 0.0,
+                ad2: 0.0,
+            },
+        };
+
+        let mut k = 0;
+        while k < coefs.len() {
+            // the c[k] x^k term integrates to c[k] x^(k + 1) / (k + 1), and then to
+            // c[k] x^(k + 2) / ((k + 1)(k + 2))
+            let power = k as f64;
+            poly.f[k] = coefs[k];
+            poly.ad1[k + 1] = coefs[k] / (power + 1.0);
+            poly.ad2[k + 2] = coefs[k] / ((power + 1.0) * (power + 2.0));
+
+            // x^k is -1 at x = -1 for odd k, the antiderivative terms one and two powers up
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            poly.upper.f += poly.f[k];
+            poly.upper.ad1 += poly.ad1[k + 1];
+            poly.upper.ad2 += poly.ad2[k + 2];
+            poly.lower.f += sign * poly.f[k];
+            poly.lower.ad1 -= sign * poly.ad1[k + 1];
+            poly.lower.ad2 += sign * poly.ad2[k + 2];
+            k += 1;
+        }
+
+        poly
+    }
+
+    #[inline]
+    fn horner(coefs: &[f64], x: f64) -> f64 {
+        coefs.iter().rev().fold(0.0, |acc, c| acc * x + c)
+    }
+
+    /// The knee `x` is past and how far past it, `None` inside [-1, 1]
+    #[inline]
+    fn past_knee(&self, x: f64) -> Option<(Knee, f64)> {
+        if x > 1.0 {
+            Some((self.upper, x - 1.0))
+        } else if x < -1.0 {
+            Some((self.lower, x + 1.0))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn f(&self, x: f64) -> f64 {
+        match self.past_knee(x) {
+            None => Polynomial::horner(&self.f[..self.len], x),
+            Some((knee, _)) => knee.f,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn ad1(&self, x: f64) -> f64 {
+        match self.past_knee(x) {
+            None => Polynomial::horner(&self.ad1[..self.len + 1], x),
+            Some((knee, d)) => knee.ad1 + knee.f * d,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn ad2(&self, x: f64) -> f64 {
+        match self.past_knee(x) {
+            None => Polynomial::horner(&self.ad2[..self.len + 2], x),
+            Some((knee, d)) => knee.ad2 + knee.ad1 * d + 0.5 * knee.f * d * d,
+        }
+    }
+}
+
+/// Defines `f`, AD1 and AD2 consts on `ProcState` for the polynomial with the given
+/// coefficients, lowest power first, see [`Polynomial`].
+macro_rules! polynomial_nl {
+    ($f:ident, $ad1:ident, $ad2:ident, [$($coef:expr),+ $(,)?]) => {
+        const $f: H = |x| {
+            const POLY: Polynomial = Polynomial::new(&[$($coef),+]);
+            POLY.f(x)
+        };
+
+        const $ad1: H1 = |x| {
+            const POLY: Polynomial = Polynomial::new(&[$($coef),+]);
+            POLY.ad1(x)
+        };
+
+        const $ad2: H2 = |x| {
+            const POLY: Polynomial = Polynomial::new(&[$($coef),+]);
+            POLY.ad2(x)
+        };
+    };
+}
+
+pub(crate) use polynomial_nl;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_INPUTS: [f64; 13] = [
+        -3.0, -1.5, -1.0, -0.9, -0.5, -0.1, 0.0, 0.1, 0.5, 0.9, 1.0, 1.5, 3.0,
+    ];
+
+    #[test]
+    fn antiderivatives_match_numerical_derivative() {
+        let poly = Polynomial::new(&[0.0, 1.5, 0.0, -0.5]);
+        let h = 1e-5;
+        for x in TEST_INPUTS {
+            let d_ad1 = (poly.ad1(x + h) - poly.ad1(x - h)) / (2.0 * h);
+            let d_ad2 = (poly.ad2(x + h) - poly.ad2(x - h)) / (2.0 * h);
+            assert!((d_ad1 - poly.f(x)).abs() < 1e-6, "x: {}", x);
+            assert!((d_ad2 - poly.ad1(x)).abs() < 1e-6, "x: {}", x);
+        }
+        assert_eq!(poly.ad1(0.0), 0.0);
+        assert_eq!(poly.ad2(0.0), 0.0);
+    }
+
+    #[test]
+    fn negative_knee_is_continuous() {
+        // f(1) = -1, so past the knee the output has the opposite sign of x
+        let poly = Polynomial::new(&[0.0, 1.0, 0.0, -2.0]);
+        let eps = 1e-9;
+        for knee in [-1.0, 1.0] {
+            let inside = knee * (1.0 - eps);
+            let outside = knee * (1.0 + eps);
+            assert!(
+                (poly.f(inside) - poly.f(outside)).abs() < 1e-6,
+                "knee: {}",
+                knee
+            );
+            assert!(
+                (poly.ad1(inside) - poly.ad1(outside)).abs() < 1e-6,
+                "knee: {}",
+                knee
+            );
+            assert!(
+                (poly.ad2(inside) - poly.ad2(outside)).abs() < 1e-6,
+                "knee: {}",
+                knee
+            );
+        }
+        assert_eq!(poly.f(3.0), -1.0);
+        assert_eq!(poly.f(-3.0), 1.0);
+
+        // f has a corner at the knee here, so the central differences skip it
+        let h = 1e-5;
+        for x in TEST_INPUTS.into_iter().filter(|x| x.abs() != 1.0) {
+            let d_ad1 = (poly.ad1(x + h) - poly.ad1(x - h)) / (2.0 * h);
+            let d_ad2 = (poly.ad2(x + h) - poly.ad2(x - h)) / (2.0 * h);
+            assert!((d_ad1 - poly.f(x)).abs() < 1e-6, "x: {}", x);
+            assert!((d_ad2 - poly.ad1(x)).abs() < 1e-6, "x: {}", x);
+        }
+    }
+
+    #[test]
+    fn quadratic_matches_numerical_integration() {
+        // f(-1) = -1.2 and f(1) = 0.8, so the two knees hold different values
+        let poly = Polynomial::new(&[0.1, 1.0, -0.3]);
+        let f = |x: f64| poly.f(x);
+
+        // Simpson's rule from 0 to x, the second antiderivative as the single integral
+        // of (x - t) f(t)
+        let integrate = |x: f64, g: &dyn Fn(f64) -> f64| {
+            let n = 20000;
+            let h = x / n as f64;
+            let sum: f64 = (0..=n)
+                .map(|i| {
+                    let weight = match i {
+                        0 => 1.0,
+                        i if i == n => 1.0,
+                        i if i % 2 == 1 => 4.0,
+                        _ => 2.0,
+                    };
+                    weight * g(i as f64 * h)
+                })
+                .sum();
+            sum * h / 3.0
+        };
+
+        for x in TEST_INPUTS {
+            let ad1 = integrate(x, &f);
+            let ad2 = integrate(x, &|t| (x - t) * f(t));
+            assert!((poly.ad1(x) - ad1).abs() < 1e-6, "x: {}", x);
+            assert!((poly.ad2(x) - ad2).abs() < 1e-6, "x: {}", x);
+        }
+        assert!((poly.f(3.0) - 0.8).abs() < 1e-12);
+        assert!((poly.f(-3.0) + 1.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn linear_polynomial_is_hard_clip() {
+        let poly = Polynomial::new(&[0.0, 1.0]);
+        for x in TEST_INPUTS {
+            let abs_x = x.abs();
+            let clip = (abs_x - 1.0).max(0.0);
+            let hc_ad1 = 0.5 * (x * x - clip * clip);
+            let hc_ad2 = if abs_x <= 1.0 {
+                x.powi(3) / 6.0
+            } else {
+                (x * x * 0.5 + 1.0 / 6.0) * x.signum() - x * 0.5
+            };
+
+            assert!((poly.f(x) - x.clamp(-1.0, 1.0)).abs() < 1e-12);
+            assert!((poly.ad1(x) - hc_ad1).abs() < 1e-12);
+            assert!((poly.ad2(x) - hc_ad2).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod processor;
+pub mod spec;
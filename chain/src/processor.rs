@@ -0,0 +1,283 @@
+use adaa_nl::adaa::NonlinearProcessor;
+use circular_buffer::circular_buffer::CircularDelayBuffer;
+use dc_filter::dc_filter::DCFilter;
+use iir_biquad_filter::iir_biquad_filter::{IIRBiquadFilter, IIRBiquadFilter64};
+use oversampler::oversample::{Oversample, OversampleFactor};
+use sample::MemoryFootprint;
+
+/// A block processor that can be put in a `Chain`
+pub trait Processor: MemoryFootprint + Send {
+    fn process_block(&mut self, block: &mut [f32]);
+
+    /// Clears the signal history, the configuration is kept
+    fn reset(&mut self);
+
+    /// For transport jumps, also drops any state `reset` carries on such as a fade in
+    /// flight, the same as `reset` unless the processor says otherwise
+    fn hard_reset(&mut self) {
+        self.reset();
+    }
+
+    /// Delay the processor adds to the signal path, in samples at its input rate
+    fn latency_samples(&self) -> usize {
+        0
+    }
+}
+
+/// Runs its processors one after the other
+#[derive(Default)]
+pub struct Chain {
+    nodes: Vec<Box<dyn Processor>>,
+}
+
+impl Chain {
+    pub fn new() -> Self {
+        Chain { nodes: Vec::new() }
+    }
+
+    pub fn push<P: Processor + 'static>(&mut self, node: P) {
+        self.nodes.push(Box::new(node));
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl Processor for Chain {
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.nodes
+            .iter_mut()
+            .for_each(|node| node.process_block(block));
+    }
+
+    fn reset(&mut self) {
+        self.nodes.iter_mut().for_each(|node| node.reset());
+    }
+
+    fn hard_reset(&mut self) {
+        self.nodes.iter_mut().for_each(|node| node.hard_reset());
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.nodes.iter().map(|node| node.latency_samples()).sum()
+    }
+}
+
+impl MemoryFootprint for Chain {
+    fn memory_footprint(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| std::mem::size_of_val(node.as_ref()) + node.memory_footprint())
+            .sum()
+    }
+}
+
+/// Constant gain
+#[derive(Debug, Clone)]
+pub struct Gain {
+    pub gain: f32,
+}
+
+impl Processor for Gain {
+    fn process_block(&mut self, block: &mut [f32]) {
+        let gain = self.gain;
+        block.iter_mut().for_each(|v| *v *= gain);
+    }
+
+    fn reset(&mut self) {}
+}
+
+impl MemoryFootprint for Gain {}
+
+/// Runs `inner` at `factor` times the rate, every block has to be `block_size` long
+pub struct Oversampled<P: Processor> {
+    os: Oversample,
+    inner: P,
+    up: Vec<f32>,
+    block_size: usize,
+}
+
+impl<P: Processor> Oversampled<P> {
+    pub fn new(inner: P, factor: OversampleFactor, block_size: usize) -> Self {
+        Oversampled {
+            os: Oversample::new(factor, block_size),
+            inner,
+            up: vec![0.0_f32; block_size << factor as usize],
+            block_size,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+}
+
+impl<P: Processor> Processor for Oversampled<P> {
+    fn process_block(&mut self, block: &mut [f32]) {
+        assert_eq!(block.len(), self.block_size);
+        self.os.process_up(block, &mut self.up);
+        self.inner.process_block(&mut self.up);
+        self.os.process_down(&self.up, block);
+    }
+
+    fn reset(&mut self) {
+        self.os.reset();
+        self.inner.reset();
+    }
+
+    fn hard_reset(&mut self) {
+        self.os.reset();
+        self.inner.hard_reset();
+    }
+
+    fn latency_samples(&self) -> usize {
+        let ratio = 1 << self.os.get_oversample_factor() as usize;
+        self.os.get_latency_samples() + self.inner.latency_samples().div_ceil(ratio)
+    }
+}
+
+impl<P: Processor> MemoryFootprint for Oversampled<P> {
+    fn memory_footprint(&self) -> usize {
+        self.os.memory_footprint() + self.inner.memory_footprint() + self.up.memory_footprint()
+    }
+}
+
+impl Processor for NonlinearProcessor {
+    fn process_block(&mut self, block: &mut [f32]) {
+        NonlinearProcessor::process_block(self, block);
+    }
+
+    fn reset(&mut self) {
+        NonlinearProcessor::reset(self);
+    }
+
+    fn hard_reset(&mut self) {
+        NonlinearProcessor::hard_reset(self);
+    }
+}
+
+impl Processor for DCFilter {
+    fn process_block(&mut self, block: &mut [f32]) {
+        block.iter_mut().for_each(|v| *v = self.process(*v));
+    }
+
+    fn reset(&mut self) {
+        DCFilter::reset(self);
+    }
+}
+
+impl Processor for IIRBiquadFilter {
+    fn process_block(&mut self, block: &mut [f32]) {
+        IIRBiquadFilter::process_block(self, block);
+    }
+
+    fn reset(&mut self) {
+        IIRBiquadFilter::reset(self);
+    }
+}
+
+/// Runs the f32 block with f64 state, for low cutoffs at high sample rates
+impl Processor for IIRBiquadFilter64 {
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.process_block_f32(block);
+    }
+
+    fn reset(&mut self) {
+        IIRBiquadFilter64::reset(self);
+    }
+}
+
+impl Processor for CircularDelayBuffer {
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.delay(block);
+    }
+
+    fn reset(&mut self) {
+        CircularDelayBuffer::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adaa_nl::adaa::{AntiderivativeOrder, ProcessorState, ProcessorStyle};
+
+    #[test]
+    fn chain_runs_in_order() {
+        let mut chain = Chain::new();
+        chain.push(Gain { gain: 2.0 });
+        chain.push(CircularDelayBuffer::new(3));
+        chain.push(Gain { gain: -1.0 });
+        assert_eq!(chain.len(), 3);
+
+        let mut block = [1.0_f32, 2.0, 3.0, 4.0];
+        chain.process_block(&mut block);
+        assert_eq!(block, [0.0, 0.0, -2.0, -4.0]);
+
+        chain.reset();
+        let mut block = [0.0_f32; 4];
+        chain.process_block(&mut block);
+        assert_eq!(block, [0.0; 4]);
+    }
+
+    #[test]
+    fn hard_reset_reaches_nested_processors() {
+        let mut state_change = NonlinearProcessor::new();
+        state_change.compare_and_change_state(ProcessorState::State(
+            ProcessorStyle::Tanh,
+            AntiderivativeOrder::FirstOrder,
+        ));
+
+        let mut chain = Chain::new();
+        chain.push(Gain { gain: 0.5 });
+        chain.push(Oversampled::new(
+            state_change.clone(),
+            OversampleFactor::TwoTimes,
+            16,
+        ));
+
+        // the fresh chain gets the same hard reset, so both start from the requested
+        // style with the fade in from the top
+        let mut fresh = Chain::new();
+        fresh.push(Gain { gain: 0.5 });
+        fresh.push(Oversampled::new(
+            state_change,
+            OversampleFactor::TwoTimes,
+            16,
+        ));
+        fresh.hard_reset();
+
+        let mut block = [0.8_f32; 16];
+        chain.process_block(&mut block);
+        chain.hard_reset();
+
+        let mut a: [f32; 16] = std::array::from_fn(|i| (i as f32 * 0.3).sin());
+        let mut b = a;
+        chain.process_block(&mut a);
+        fresh.process_block(&mut b);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn oversampled_reports_latency_and_memory() {
+        let os = Oversampled::new(Gain { gain: 1.0 }, OversampleFactor::FourTimes, 32);
+        assert_eq!(
+            os.latency_samples(),
+            Oversample::new(OversampleFactor::FourTimes, 32).get_latency_samples()
+        );
+
+        let mut chain = Chain::new();
+        chain.push(os);
+        chain.push(DCFilter::new());
+        let expected = Oversample::new(OversampleFactor::FourTimes, 32).memory_footprint()
+            + (32 << 2) * 4
+            + std::mem::size_of::<Oversampled<Gain>>()
+            + std::mem::size_of::<DCFilter>();
+        assert_eq!(chain.memory_footprint(), expected);
+    }
+}
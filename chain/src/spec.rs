@@ -0,0 +1,337 @@
+use std::fmt;
+
+use adaa_nl::adaa::{AntiderivativeOrder, NonlinearProcessor, ProcessorState, ProcessorStyle};
+use circular_buffer::circular_buffer::CircularDelayBuffer;
+use dc_filter::dc_filter::DCFilter;
+use iir_biquad_filter::iir_biquad_filter::{FilterOrder, FilterType, IIRBiquadFilter};
+use oversampler::oversample::OversampleFactor;
+use param_range::param_range::{db_to_gain, ParamRange, Taper, CUTOFF_HZ, DRIVE_DB, OUTPUT_DB};
+
+use crate::processor::{Chain, Gain, Oversampled};
+
+/// A gain node stands in for both drive and output level, so it takes either range
+const GAIN_DB: ParamRange = ParamRange::new(OUTPUT_DB.min, DRIVE_DB.max, Taper::Linear);
+
+/// Declarative description of a `Chain`, nodes run in the order they are listed
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChainSpec {
+    pub sample_rate: f32,
+    /// Every block passed to the built chain has to be this long
+    pub block_size: usize,
+    pub nodes: Vec<NodeSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeSpec {
+    Gain {
+        db: f32,
+    },
+    DcBlock,
+    Lowpass {
+        cutoff_hz: f32,
+        second_order: bool,
+    },
+    Delay {
+        samples: usize,
+    },
+    /// `oversample` is the factor, 2, 4, 8 or 16, or none to run at the base rate
+    Saturator {
+        style: SaturatorStyle,
+        second_order: bool,
+        oversample: Option<u32>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SaturatorStyle {
+    HardClip,
+    Tanh,
+    SoftClipX2,
+    CubicSoftClip,
+    Arctan,
+    ExpSoftClip,
+    Diode,
+    TriangleFold,
+    SineFold,
+    QuinticSoftClip,
+}
+
+/// Why a `ChainSpec` could not be built, `node` is the index in `ChainSpec::nodes`
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ChainSpecError {
+    InvalidSampleRate(f32),
+    InvalidBlockSize(usize),
+    CutoffOutOfRange { node: usize, cutoff_hz: f32 },
+    GainOutOfRange { node: usize, db: f32 },
+    InvalidOversampleFactor { node: usize, factor: u32 },
+}
+
+impl fmt::Display for ChainSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainSpecError::InvalidSampleRate(sr) => write!(f, "invalid sample rate {}", sr),
+            ChainSpecError::InvalidBlockSize(size) => write!(f, "invalid block size {}", size),
+            ChainSpecError::CutoffOutOfRange { node, cutoff_hz } => {
+                write!(f, "node {}: cutoff {} Hz is out of range", node, cutoff_hz)
+            }
+            ChainSpecError::GainOutOfRange { node, db } => {
+                write!(f, "node {}: gain {} dB is out of range", node, db)
+            }
+            ChainSpecError::InvalidOversampleFactor { node, factor } => {
+                write!(f, "node {}: no {}x oversampling", node, factor)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainSpecError {}
+
+impl ChainSpec {
+    /// Instantiates the processors, this allocates so don't call it on the audio thread
+    pub fn build(&self) -> Result<Chain, ChainSpecError> {
+        if !(self.sample_rate.is_finite() && self.sample_rate > 0.0) {
+            return Err(ChainSpecError::InvalidSampleRate(self.sample_rate));
+        }
+        // the oversampler stages halve the block down to 16x
+        if self.block_size == 0 || !self.block_size.is_multiple_of(16) {
+            return Err(ChainSpecError::InvalidBlockSize(self.block_size));
+        }
+
+        let mut chain = Chain::new();
+        for (node, spec) in self.nodes.iter().enumerate() {
+            match spec {
+                NodeSpec::Gain { db } => {
+                    if !GAIN_DB.contains(*db) {
+                        return Err(ChainSpecError::GainOutOfRange { node, db: *db });
+                    }
+                    chain.push(Gain {
+                        gain: db_to_gain(*db),
+                    })
+                }
+                NodeSpec::DcBlock => chain.push(DCFilter::new()),
+                NodeSpec::Lowpass {
+                    cutoff_hz,
+                    second_order,
+                } => {
+                    if !(CUTOFF_HZ.contains(*cutoff_hz) && *cutoff_hz < self.sample_rate / 2.0) {
+                        return Err(ChainSpecError::CutoffOutOfRange {
+                            node,
+                            cutoff_hz: *cutoff_hz,
+                        });
+                    }
+                    let order = if *second_order {
+                        FilterOrder::Second
+                    } else {
+                        FilterOrder::First
+                    };
+                    let mut filter = IIRBiquadFilter::new(FilterType::Lowpass);
+                    filter.init(&self.sample_rate, cutoff_hz, order);
+                    chain.push(filter);
+                }
+                // the delay buffer holds one more sample than it delays by
+                NodeSpec::Delay { samples } => chain.push(CircularDelayBuffer::new(samples + 1)),
+                NodeSpec::Saturator {
+                    style,
+                    second_order,
+                    oversample,
+                } => {
+                    let nl = saturator(*style, *second_order);
+                    match oversample {
+                        None => chain.push(nl),
+                        Some(factor) => {
+                            let factor = oversample_factor(*factor).ok_or(
+                                ChainSpecError::InvalidOversampleFactor {
+                                    node,
+                                    factor: *factor,
+                                },
+                            )?;
+                            chain.push(Oversampled::new(nl, factor, self.block_size));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(chain)
+    }
+}
+
+fn saturator(style: SaturatorStyle, second_order: bool) -> NonlinearProcessor {
+    let style = match style {
+        SaturatorStyle::HardClip => ProcessorStyle::HardClip,
+        SaturatorStyle::Tanh => ProcessorStyle::Tanh,
+        SaturatorStyle::SoftClipX2 => ProcessorStyle::SoftClipX2,
+        SaturatorStyle::CubicSoftClip => ProcessorStyle::CubicSoftClip,
+        SaturatorStyle::Arctan => ProcessorStyle::Arctan,
+        SaturatorStyle::ExpSoftClip => ProcessorStyle::ExpSoftClip,
+        SaturatorStyle::Diode => ProcessorStyle::Diode,
+        SaturatorStyle::TriangleFold => ProcessorStyle::TriangleFold,
+        SaturatorStyle::SineFold => ProcessorStyle::SineFold,
+        SaturatorStyle::QuinticSoftClip => ProcessorStyle::QuinticSoftClip,
+    };
+    let order = if second_order {
+        AntiderivativeOrder::SecondOrder
+    } else {
+        AntiderivativeOrder::FirstOrder
+    };
+    let mut nl = NonlinearProcessor::new();
+    nl.compare_and_change_state(ProcessorState::State(style, order));
+    // start on the requested style instead of fading out of the default one
+    nl.hard_reset();
+    nl
+}
+
+fn oversample_factor(factor: u32) -> Option<OversampleFactor> {
+    match factor {
+        2 => Some(OversampleFactor::TwoTimes),
+        4 => Some(OversampleFactor::FourTimes),
+        8 => Some(OversampleFactor::EightTimes),
+        16 => Some(OversampleFactor::SixteenTimes),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::Processor;
+
+    fn saturator_spec() -> ChainSpec {
+        ChainSpec {
+            sample_rate: 48000.0,
+            block_size: 64,
+            nodes: vec![
+                NodeSpec::Gain { db: 12.0 },
+                NodeSpec::Saturator {
+                    style: SaturatorStyle::Tanh,
+                    second_order: false,
+                    oversample: Some(4),
+                },
+                NodeSpec::DcBlock,
+                NodeSpec::Lowpass {
+                    cutoff_hz: 8000.0,
+                    second_order: true,
+                },
+                NodeSpec::Delay { samples: 10 },
+            ],
+        }
+    }
+
+    #[test]
+    fn built_chain_matches_hand_built() {
+        let mut built = saturator_spec().build().unwrap();
+        assert_eq!(built.len(), 5);
+
+        let mut by_hand = Chain::new();
+        by_hand.push(Gain {
+            gain: db_to_gain(12.0),
+        });
+        by_hand.push(Oversampled::new(
+            saturator(SaturatorStyle::Tanh, false),
+            OversampleFactor::FourTimes,
+            64,
+        ));
+        by_hand.push(DCFilter::new());
+        let mut lowpass = IIRBiquadFilter::new(FilterType::Lowpass);
+        lowpass.init(&48000.0, &8000.0, FilterOrder::Second);
+        by_hand.push(lowpass);
+        by_hand.push(CircularDelayBuffer::new(11));
+
+        assert_eq!(built.latency_samples(), by_hand.latency_samples());
+        (0..8).for_each(|b| {
+            let mut a: Vec<f32> = (b * 64..(b + 1) * 64)
+                .map(|n| (n as f32 * 0.05).sin())
+                .collect();
+            let mut c = a.clone();
+            built.process_block(&mut a);
+            by_hand.process_block(&mut c);
+            assert_eq!(a, c);
+        });
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        let mut spec = saturator_spec();
+        spec.block_size = 40;
+        assert_eq!(
+            spec.build().err(),
+            Some(ChainSpecError::InvalidBlockSize(40))
+        );
+
+        let mut spec = saturator_spec();
+        spec.nodes[3] = NodeSpec::Lowpass {
+            cutoff_hz: 30000.0,
+            second_order: false,
+        };
+        assert_eq!(
+            spec.build().err(),
+            Some(ChainSpecError::CutoffOutOfRange {
+                node: 3,
+                cutoff_hz: 30000.0
+            })
+        );
+
+        let mut spec = saturator_spec();
+        spec.nodes[3] = NodeSpec::Lowpass {
+            cutoff_hz: 10.0,
+            second_order: false,
+        };
+        assert_eq!(
+            spec.build().err(),
+            Some(ChainSpecError::CutoffOutOfRange {
+                node: 3,
+                cutoff_hz: 10.0
+            })
+        );
+
+        let mut spec = saturator_spec();
+        spec.nodes[0] = NodeSpec::Gain { db: 60.0 };
+        assert_eq!(
+            spec.build().err(),
+            Some(ChainSpecError::GainOutOfRange { node: 0, db: 60.0 })
+        );
+
+        spec.nodes[0] = NodeSpec::Gain { db: f32::NAN };
+        let err = spec.build().err().unwrap();
+        assert!(
+            matches!(err, ChainSpecError::GainOutOfRange { node: 0, db } if db.is_nan()),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.to_string(), "node 0: gain NaN dB is out of range");
+
+        let mut spec = saturator_spec();
+        spec.nodes[1] = NodeSpec::Saturator {
+            style: SaturatorStyle::HardClip,
+            second_order: true,
+            oversample: Some(3),
+        };
+        let err = spec.build().err().unwrap();
+        assert_eq!(err.to_string(), "node 1: no 3x oversampling");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn builds_from_fixture() {
+        let fixture = r#"{
+            "sample_rate": 48000.0,
+            "block_size": 64,
+            "nodes": [
+                { "Gain": { "db": 12.0 } },
+                { "Saturator": { "style": "Tanh", "second_order": false, "oversample": 4 } },
+                "DcBlock",
+                { "Lowpass": { "cutoff_hz": 8000.0, "second_order": true } },
+                { "Delay": { "samples": 10 } }
+            ]
+        }"#;
+        let spec: ChainSpec = serde_json::from_str(fixture).unwrap();
+        assert_eq!(spec, saturator_spec());
+        assert_eq!(
+            serde_json::from_str::<ChainSpec>(&serde_json::to_string(&spec).unwrap()).unwrap(),
+            spec
+        );
+    }
+}
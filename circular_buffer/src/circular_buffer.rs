@@ -1,5 +1,7 @@
 use std::ptr;
 
+use sample::{MemoryFootprint, Sample};
+
 use std::simd::{prelude::*, LaneCount, SimdElement, SupportedLaneCount};
 
 #[derive(Debug)]
@@ -33,6 +35,11 @@ impl TiledConv {
         }
     }
 
+    /// Clears the tail of previous input carried into the next block
+    pub fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|x| *x = 0.0);
+    }
+
     #[inline]
     fn dot_product_simd_generic<T, const N: usize>(a: &[f32], b: &[f32]) -> f32
     where
@@ -41,6 +48,15 @@ impl TiledConv {
     {
         assert!(a.len() == b.len());
 
+        // summed strictly in order, the result doesn't depend on `N` or on how the
+        // platform reduces the lanes
+        if cfg!(feature = "deterministic") {
+            return a
+                .iter()
+                .zip(b.iter())
+                .fold(0.0, |acc, (aa, bb)| acc + aa * bb);
+        }
+
         let mut sum = Simd::<f32, N>::splat(0.0);
         let mut result = 0.0;
 
@@ -72,24 +88,35 @@ impl TiledConv {
     }
 }
 
+pub type CircularDelayBuffer = GenericCircularDelayBuffer<f32>;
+
+impl MemoryFootprint for TiledConv {
+    fn memory_footprint(&self) -> usize {
+        self.buffer.memory_footprint()
+    }
+}
+
 #[derive(Debug)]
-pub struct CircularDelayBuffer {
-    data: Vec<f32>,
+pub struct GenericCircularDelayBuffer<T: Sample> {
+    data: Vec<T>,
     pos: usize,
     size: usize,
+    // gain applied each time a frozen sample goes around the loop
+    freeze_feedback: Option<T>,
 }
 
-impl CircularDelayBuffer {
+impl<T: Sample> GenericCircularDelayBuffer<T> {
     pub fn new(initial_size: usize) -> Self {
-        CircularDelayBuffer {
-            data: vec![0.0_f32; initial_size],
+        GenericCircularDelayBuffer {
+            data: vec![T::ZERO; initial_size],
             pos: 0,
             size: initial_size,
+            freeze_feedback: None,
         }
     }
 
     #[inline]
-    fn push(&mut self, val: f32) {
+    fn push(&mut self, val: T) {
         self.data[self.pos] = val;
     }
 
@@ -103,19 +130,50 @@ impl CircularDelayBuffer {
     }
 
     /// Resets the buffer's data to all zeros and resets the buffers position value to zero
-    fn reset(&mut self) {
-        self.data.iter_mut().for_each(|x| *x = 0.0_f32.into());
+    pub fn reset(&mut self) {
+        self.data.iter_mut().for_each(|x| *x = T::ZERO);
         self.pos = 0;
     }
 
     /// delays the input by self.size number of samples
     #[inline]
-    pub fn delay(&mut self, input: &mut [f32]) {
-        input.iter_mut().for_each(|v| {
-            self.push(*v);
-            self.decrement_pos();
-            *v = self.data[self.pos];
-        })
+    pub fn delay(&mut self, input: &mut [T]) {
+        self.delay_with_loop_filter(input, |v| v)
+    }
+
+    /// `delay`, while frozen every recirculated sample also goes through `filter`
+    #[inline]
+    pub fn delay_with_loop_filter<F: FnMut(T) -> T>(&mut self, input: &mut [T], mut filter: F) {
+        match self.freeze_feedback {
+            None => input.iter_mut().for_each(|v| {
+                self.push(*v);
+                self.decrement_pos();
+                *v = self.data[self.pos];
+            }),
+            Some(feedback) => input.iter_mut().for_each(|v| {
+                // the output goes back in where the input would have, one delay length later
+                let write_pos = self.pos;
+                self.decrement_pos();
+                *v = self.data[self.pos];
+                self.data[write_pos] = filter(*v * feedback);
+            }),
+        }
+    }
+
+    /// Stops taking input and loops what is currently in the buffer, the output carries on
+    /// without a jump. `feedback` scales the loop on every pass, 1.0 holds it indefinitely
+    /// and anything lower fades it out.
+    pub fn freeze(&mut self, feedback: T) {
+        self.freeze_feedback = Some(feedback);
+    }
+
+    /// Takes input again, the frozen loop plays out as the new input replaces it
+    pub fn unfreeze(&mut self) {
+        self.freeze_feedback = None;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.freeze_feedback.is_some()
     }
 
     pub fn set_delay_len(&mut self, new_len: usize) {
@@ -124,6 +182,12 @@ impl CircularDelayBuffer {
     }
 }
 
+impl<T: Sample> MemoryFootprint for GenericCircularDelayBuffer<T> {
+    fn memory_footprint(&self) -> usize {
+        self.data.memory_footprint()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -233,6 +297,82 @@ mod tests {
             .for_each(|(a, b)| assert!((a - b).abs() < ERR_TOL, "result: {}, expected: {}", a, b));
     }
 
+    #[test]
+    #[cfg(feature = "deterministic")]
+    fn deterministic_conv_is_bit_exact() {
+        let kernel: Vec<f32> = (0..37).map(|x| ((x as f32) * 0.37).sin() / 7.0).collect();
+        let sig: Vec<f32> = (0..64).map(|x| ((x as f32) * 0.11).cos()).collect();
+
+        let mut padded = vec![0.0_f32; kernel.len() - 1];
+        padded.extend_from_slice(&sig);
+        let expected: Vec<f32> = (0..sig.len())
+            .map(|i| {
+                let mut acc = 0.0_f32;
+                for (a, b) in padded[i..i + kernel.len()].iter().zip(kernel.iter()) {
+                    acc += a * b;
+                }
+                acc
+            })
+            .collect();
+
+        let mut lanes_4 = sig.clone();
+        TiledConv::new(kernel.len(), sig.len()).convolve::<f32, 4>(&mut lanes_4, &kernel);
+        let mut lanes_16 = sig.clone();
+        TiledConv::new(kernel.len(), sig.len()).convolve::<f32, 16>(&mut lanes_16, &kernel);
+
+        assert_eq!(lanes_4, expected);
+        assert_eq!(lanes_16, expected);
+    }
+
+    #[test]
+    fn reset_clears_history() {
+        let mut buf = TiledConv::new(3, 3);
+        let kernel = [2., 1., 0.];
+        buf.convolve::<f32, 8>(&mut [1., 2., 3.], &kernel);
+        buf.reset();
+        let mut sig = [0., 0., 0.];
+        buf.convolve::<f32, 8>(&mut sig, &kernel);
+        assert_eq!(sig, [0., 0., 0.]);
+
+        let mut delay_buf = CircularDelayBuffer::new(3);
+        delay_buf.delay(&mut [1., 2., 3.]);
+        delay_buf.reset();
+        let mut sig = [0., 0., 0.];
+        delay_buf.delay(&mut sig);
+        assert_eq!(sig, [0., 0., 0.]);
+    }
+
+    #[test]
+    fn footprint_counts_buffers() {
+        // kernel + input - 1 samples of history
+        assert_eq!(TiledConv::new(8, 32).memory_footprint(), 39 * 4);
+        assert_eq!(CircularDelayBuffer::new(64).memory_footprint(), 64 * 4);
+    }
+
+    #[test]
+    fn freeze_loops_buffer() {
+        let mut delay_buf = CircularDelayBuffer::new(4);
+        let mut sig: Vec<f32> = (1..7).map(|x| x as f32).collect();
+        delay_buf.delay(&mut sig);
+        assert_eq!(sig, vec![0., 0., 0., 1., 2., 3.]);
+
+        delay_buf.freeze(1.0);
+        let mut sig = vec![9.0; 9];
+        delay_buf.delay(&mut sig);
+        assert_eq!(sig, vec![4., 5., 6., 4., 5., 6., 4., 5., 6.]);
+
+        // every pass through the loop also gets the filter and feedback
+        delay_buf.freeze(0.5);
+        let mut sig = vec![9.0; 6];
+        delay_buf.delay_with_loop_filter(&mut sig, |v| -v);
+        assert_eq!(sig, vec![4., 5., 6., -2., -2.5, -3.]);
+
+        delay_buf.unfreeze();
+        let mut sig: Vec<f32> = (1..7).map(|x| x as f32).collect();
+        delay_buf.delay(&mut sig);
+        assert_eq!(sig, vec![1., 1.25, 1.5, 1., 2., 3.]);
+    }
+
     #[test]
     fn delay_5_samples() {
         let mut sig: Vec<f32> = (1..10).map(|x| x as f32).collect();
@@ -261,6 +401,16 @@ mod tests {
         check_results(&sig, &expected_result);
     }
 
+    #[test]
+    fn delay_f64() {
+        let mut sig: Vec<f64> = (1..10).map(|x| x as f64 + 0.125).collect();
+        let mut delay_buf = GenericCircularDelayBuffer::<f64>::new(5);
+
+        delay_buf.delay(&mut sig);
+        let expected_result = vec![0., 0., 0., 0., 1.125, 2.125, 3.125, 4.125, 5.125];
+        assert_eq!(sig, expected_result);
+    }
+
     #[test]
     fn delay_over_2_blocks() {
         let mut sig_1: Vec<f32> = (1..9).map(|x| x as f32).collect();
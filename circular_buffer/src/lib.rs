@@ -1,3 +1,4 @@
 #![feature(portable_simd)]
 
 pub mod circular_buffer;
+pub mod static_buffer;
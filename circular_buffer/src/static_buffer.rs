@@ -0,0 +1,316 @@
+use sample::{MemoryFootprint, Sample};
+
+/// Array backed delay line, delays by exactly `N` samples. Nothing is allocated on the heap,
+/// so it can live in a `static` or on the stack on targets without an allocator.
+#[derive(Debug, Clone)]
+pub struct StaticDelayBuffer<T: Sample, const N: usize> {
+    data: [T; N],
+    pos: usize,
+}
+
+impl<T: Sample, const N: usize> StaticDelayBuffer<T, N> {
+    pub const fn new() -> Self {
+        assert!(N > 0, "delay length must be at least one sample");
+        StaticDelayBuffer {
+            data: [T::ZERO; N],
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, val: T) -> T {
+        let out = self.data[self.pos];
+        self.data[self.pos] = val;
+        self.pos = if self.pos + 1 == N { 0 } else { self.pos + 1 };
+        out
+    }
+
+    pub fn delay(&mut self, input: &mut [T]) {
+        input.iter_mut().for_each(|v| *v = self.process(*v));
+    }
+
+    pub fn reset(&mut self) {
+        self.data = [T::ZERO; N];
+        self.pos = 0;
+    }
+}
+
+impl<T: Sample, const N: usize> Default for StaticDelayBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Sample, const N: usize> MemoryFootprint for StaticDelayBuffer<T, N> {}
+
+/// Direct form FIR with a fixed number of taps, the history is kept in an array
+#[derive(Debug, Clone)]
+pub struct StaticFir<const TAPS: usize> {
+    kernel: [f32; TAPS],
+    history: [f32; TAPS],
+    // where the next input goes, the newest sample sits just before it
+    pos: usize,
+}
+
+impl<const TAPS: usize> StaticFir<TAPS> {
+    pub const fn new(kernel: [f32; TAPS]) -> Self {
+        assert!(TAPS > 0, "a filter needs at least one tap");
+        StaticFir {
+            kernel,
+            history: [0.0; TAPS],
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, val: f32) -> f32 {
+        self.history[self.pos] = val;
+        self.pos = if self.pos + 1 == TAPS {
+            0
+        } else {
+            self.pos + 1
+        };
+
+        // history[..pos] holds the newest samples and history[pos..] the oldest, both run
+        // newest to oldest backwards
+        let (newest, oldest) = self.history.split_at(self.pos);
+        let (newest_taps, oldest_taps) = self.kernel.split_at(self.pos);
+        newest
+            .iter()
+            .rev()
+            .zip(newest_taps.iter())
+            .chain(oldest.iter().rev().zip(oldest_taps.iter()))
+            .map(|(x, k)| x * k)
+            .sum()
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        block.iter_mut().for_each(|v| *v = self.process(*v));
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS];
+        self.pos = 0;
+    }
+}
+
+impl<const TAPS: usize> MemoryFootprint for StaticFir<TAPS> {}
+
+/// Block processor that keeps all of its state inline, what a `StaticChain` is built from.
+/// Arrays and tuples of them run their elements in order.
+pub trait StaticProcessor {
+    fn process_block(&mut self, block: &mut [f32]);
+
+    /// Clears the signal history, the configuration is kept
+    fn reset(&mut self);
+}
+
+impl<const N: usize> StaticProcessor for StaticDelayBuffer<f32, N> {
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.delay(block);
+    }
+
+    fn reset(&mut self) {
+        StaticDelayBuffer::reset(self);
+    }
+}
+
+impl<const TAPS: usize> StaticProcessor for StaticFir<TAPS> {
+    fn process_block(&mut self, block: &mut [f32]) {
+        StaticFir::process_block(self, block);
+    }
+
+    fn reset(&mut self) {
+        StaticFir::reset(self);
+    }
+}
+
+impl<P: StaticProcessor, const N: usize> StaticProcessor for [P; N] {
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.iter_mut().for_each(|node| node.process_block(block));
+    }
+
+    fn reset(&mut self) {
+        self.iter_mut().for_each(|node| node.reset());
+    }
+}
+
+macro_rules! impl_static_processor_tuple {
+    ($($p:ident $idx:tt),+) => {
+        impl<$($p: StaticProcessor),+> StaticProcessor for ($($p,)+) {
+            fn process_block(&mut self, block: &mut [f32]) {
+                $(self.$idx.process_block(block);)+
+            }
+
+            fn reset(&mut self) {
+                $(self.$idx.reset();)+
+            }
+        }
+    };
+}
+
+impl_static_processor_tuple!(A 0);
+impl_static_processor_tuple!(A 0, B 1);
+impl_static_processor_tuple!(A 0, B 1, C 2);
+impl_static_processor_tuple!(A 0, B 1, C 2, D 3);
+impl_static_processor_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_static_processor_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_static_processor_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_static_processor_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// `chain::Chain` without the boxes: the processors are held inline, as a tuple for
+/// different kinds or an array for several of one kind, so building and running it never
+/// touches the heap
+#[derive(Debug, Clone, Default)]
+pub struct StaticChain<P: StaticProcessor> {
+    nodes: P,
+}
+
+impl<P: StaticProcessor> StaticChain<P> {
+    pub const fn new(nodes: P) -> Self {
+        StaticChain { nodes }
+    }
+
+    pub fn nodes_mut(&mut self) -> &mut P {
+        &mut self.nodes
+    }
+}
+
+impl<P: StaticProcessor> StaticProcessor for StaticChain<P> {
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.nodes.process_block(block);
+    }
+
+    fn reset(&mut self) {
+        self.nodes.reset();
+    }
+}
+
+impl<P: StaticProcessor> MemoryFootprint for StaticChain<P> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    // counts per thread so tests running in parallel don't show up
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(|count| count.get())
+    }
+
+    #[test]
+    fn delays_by_n() {
+        let mut delay = StaticDelayBuffer::<f64, 3>::new();
+        let mut sig = [1., 2., 3., 4., 5.];
+        delay.delay(&mut sig);
+        assert_eq!(sig, [0., 0., 0., 1., 2.]);
+
+        delay.reset();
+        assert_eq!(delay.process(9.), 0.);
+    }
+
+    #[test]
+    fn fir_matches_convolution() {
+        let kernel = [0.5, 0.25, -0.125, 0.0625];
+        let input: Vec<f32> = (0..16).map(|i| (i as f32 * 0.7).sin()).collect();
+        let expected: Vec<f32> = (0..input.len())
+            .map(|n| {
+                (0..kernel.len())
+                    .filter(|k| *k <= n)
+                    .map(|k| kernel[k] * input[n - k])
+                    .sum()
+            })
+            .collect();
+
+        let mut fir = StaticFir::new(kernel);
+        let mut result = input.clone();
+        fir.process_block(&mut result);
+        result
+            .iter()
+            .zip(expected.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn static_chain_runs_in_order() {
+        let kernel = [0.5, 0.25, -0.125];
+        let input: Vec<f32> = (0..32).map(|i| (i as f32 * 0.3).sin()).collect();
+
+        let mut fir = StaticFir::new(kernel);
+        let mut delays = [StaticDelayBuffer::<f32, 2>::new(), StaticDelayBuffer::new()];
+        let mut last = StaticDelayBuffer::<f32, 3>::new();
+        let mut expected = input.clone();
+        fir.process_block(&mut expected);
+        delays.iter_mut().for_each(|d| d.delay(&mut expected));
+        last.delay(&mut expected);
+
+        let mut chain = StaticChain::new((
+            StaticFir::new(kernel),
+            [StaticDelayBuffer::<f32, 2>::new(), StaticDelayBuffer::new()],
+            StaticDelayBuffer::<f32, 3>::new(),
+        ));
+        let mut result = input.clone();
+        result
+            .chunks_mut(5)
+            .for_each(|block| chain.process_block(block));
+        assert_eq!(result, expected);
+        assert_eq!(result[..7], [0.0; 7]);
+
+        chain.reset();
+        let mut block = [0.0_f32; 8];
+        chain.process_block(&mut block);
+        assert_eq!(block, [0.0; 8]);
+    }
+
+    #[test]
+    fn processing_never_allocates() {
+        let mut fir = StaticFir::new([0.25_f32; 31]);
+        let mut delay = StaticDelayBuffer::<f32, 64>::new();
+        let mut block = [0.5_f32; 128];
+
+        let before = allocations();
+        let mut chain = StaticChain::new((
+            StaticFir::new([0.125_f32; 15]),
+            std::array::from_fn::<_, 4, _>(|_| StaticDelayBuffer::<f32, 16>::new()),
+        ));
+        (0..32).for_each(|_| {
+            fir.process_block(&mut block);
+            delay.delay(&mut block);
+            chain.process_block(&mut block);
+        });
+        fir.reset();
+        delay.reset();
+        chain.reset();
+        assert_eq!(allocations(), before);
+        assert_eq!(
+            fir.memory_footprint() + delay.memory_footprint() + chain.memory_footprint(),
+            0
+        );
+
+        // the counter does see heap use
+        let buff: Vec<f32> = Vec::with_capacity(16);
+        assert_eq!(allocations(), before + 1);
+        assert_eq!(buff.memory_footprint(), 64);
+    }
+}
@@ -0,0 +1,159 @@
+/// How the samples between two control ticks are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    /// Largest value, pass magnitudes in for a peak meter
+    Max,
+    Mean,
+}
+
+/// Brings an audio rate stream (envelope, gain reduction, meter level) down to a fixed
+/// control rate for a GUI. Ticks land on the nearest sample to `n * sample_rate / rate`
+/// counted from the start of the stream, so how the audio is split into blocks doesn't move
+/// them and the rounding error never builds up.
+#[derive(Debug, Clone)]
+pub struct ControlRateDecimator {
+    aggregation: Aggregation,
+    samples_per_tick: f64,
+    samples_seen: u64,
+    ticks: u64,
+    next_tick_at: u64,
+    acc: f32,
+    count: usize,
+    latest: f32,
+}
+
+impl ControlRateDecimator {
+    pub fn new(sample_rate: f32, control_rate_hz: f32, aggregation: Aggregation) -> Self {
+        assert!(
+            control_rate_hz > 0.0 && control_rate_hz <= sample_rate,
+            "control rate must be above zero and at most the sample rate"
+        );
+        let mut decimator = ControlRateDecimator {
+            aggregation,
+            samples_per_tick: sample_rate as f64 / control_rate_hz as f64,
+            samples_seen: 0,
+            ticks: 0,
+            next_tick_at: 0,
+            acc: 0.0,
+            count: 0,
+            latest: 0.0,
+        };
+        decimator.reset();
+        decimator
+    }
+
+    /// Aggregates `block` and calls `emit` with the value of every tick that completes in it
+    pub fn process<F: FnMut(f32)>(&mut self, block: &[f32], mut emit: F) {
+        let mut rest = block;
+        while !rest.is_empty() {
+            let until_tick = (self.next_tick_at - self.samples_seen) as usize;
+            let (now, later) = rest.split_at(until_tick.min(rest.len()));
+            self.accumulate(now);
+            self.samples_seen += now.len() as u64;
+            rest = later;
+
+            if self.samples_seen == self.next_tick_at {
+                self.latest = match self.aggregation {
+                    Aggregation::Max => self.acc,
+                    Aggregation::Mean => self.acc / self.count as f32,
+                };
+                emit(self.latest);
+                self.ticks += 1;
+                self.start_tick();
+            }
+        }
+    }
+
+    /// Value of the last completed tick
+    pub fn latest(&self) -> f32 {
+        self.latest
+    }
+
+    /// Completed ticks since the start or the last reset
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Starts counting ticks from the next sample again
+    pub fn reset(&mut self) {
+        self.samples_seen = 0;
+        self.ticks = 0;
+        self.latest = 0.0;
+        self.start_tick();
+    }
+
+    fn start_tick(&mut self) {
+        self.next_tick_at = ((self.ticks + 1) as f64 * self.samples_per_tick).round() as u64;
+        self.acc = match self.aggregation {
+            Aggregation::Max => f32::NEG_INFINITY,
+            Aggregation::Mean => 0.0,
+        };
+        self.count = 0;
+    }
+
+    #[inline]
+    fn accumulate(&mut self, samples: &[f32]) {
+        self.count += samples.len();
+        self.acc = match self.aggregation {
+            Aggregation::Max => samples.iter().fold(self.acc, |m, v| m.max(*v)),
+            Aggregation::Mean => self.acc + samples.iter().sum::<f32>(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(decimator: &mut ControlRateDecimator, signal: &[f32], blocks: &[usize]) -> Vec<f32> {
+        let mut out = Vec::new();
+        let mut rest = signal;
+        let mut lens = blocks.iter().cycle();
+        while !rest.is_empty() {
+            let (block, later) = rest.split_at((*lens.next().unwrap()).min(rest.len()));
+            decimator.process(block, |v| out.push(v));
+            rest = later;
+        }
+        out
+    }
+
+    #[test]
+    fn block_size_does_not_move_ticks() {
+        // 685.71 samples a tick
+        let signal: Vec<f32> = (0..48000).map(|n| (n as f32 * 0.001).sin().abs()).collect();
+        let mut whole = ControlRateDecimator::new(48000.0, 70.0, Aggregation::Max);
+        let expected = run(&mut whole, &signal, &[48000]);
+        assert_eq!(expected.len(), 70);
+
+        [&[64][..], &[1, 511, 37, 2048], &[685, 1, 1]]
+            .iter()
+            .for_each(|blocks| {
+                let mut split = ControlRateDecimator::new(48000.0, 70.0, Aggregation::Max);
+                assert_eq!(run(&mut split, &signal, blocks), expected);
+            });
+    }
+
+    #[test]
+    fn ticks_do_not_drift() {
+        let mut decimator = ControlRateDecimator::new(48000.0, 7.0, Aggregation::Mean);
+        let signal = vec![0.5_f32; 48000 * 10];
+        let out = run(&mut decimator, &signal, &[480]);
+        // 48000 / 7 samples a tick doesn't divide, after 10 s there are still exactly 70
+        assert_eq!(out.len(), 70);
+        assert_eq!(decimator.ticks(), 70);
+        assert!(out.iter().all(|v| (v - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn max_and_mean() {
+        let signal = [0.0_f32, 1.0, 0.5, 0.5, -1.0, 0.0, 0.25, 0.75];
+        let mut max = ControlRateDecimator::new(8.0, 2.0, Aggregation::Max);
+        let mut mean = ControlRateDecimator::new(8.0, 2.0, Aggregation::Mean);
+        assert_eq!(run(&mut max, &signal, &[3]), vec![1.0, 0.75]);
+        assert_eq!(run(&mut mean, &signal, &[3]), vec![0.5, 0.0]);
+        assert_eq!(mean.latest(), 0.0);
+
+        mean.reset();
+        assert_eq!(run(&mut mean, &signal[..4], &[4]), vec![0.5]);
+    }
+}
@@ -1,22 +1,35 @@
-pub struct DCFilter {
-    xn: f32,
-    yn: f32,
-    r: f32,
+use sample::{MemoryFootprint, Sample};
+
+pub type DCFilter = GenericDCFilter<f32>;
+pub type DCFilter64 = GenericDCFilter<f64>;
+
+#[derive(Debug, Clone)]
+pub struct GenericDCFilter<T: Sample> {
+    xn: T,
+    yn: T,
+    r: T,
 }
 
-impl DCFilter {
+impl<T: Sample> GenericDCFilter<T> {
     pub fn new() -> Self {
-        DCFilter {
-            xn: 0.0,
-            yn: 0.0,
-            r: 0.995,
+        GenericDCFilter {
+            xn: T::ZERO,
+            yn: T::ZERO,
+            r: T::from_f64(0.995),
         }
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
+    pub fn reset(&mut self) {
+        self.xn = T::ZERO;
+        self.yn = T::ZERO;
+    }
+
+    pub fn process(&mut self, input: T) -> T {
         let this_output = input - self.xn + (self.r * self.yn);
         self.xn = input;
         self.yn = this_output;
         this_output
     }
 }
+
+impl<T: Sample> MemoryFootprint for GenericDCFilter<T> {}
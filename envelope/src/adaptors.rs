@@ -0,0 +1,665 @@
+use sample::Sample;
+
+use crate::{step_progress, Env};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Looping<E> {
+    initial: E,
+    current: E,
+    remaining_cycles: Option<u32>,
+}
+
+impl<E: Clone> Looping<E> {
+    pub(crate) fn new(env: E, remaining_cycles: Option<u32>) -> Self {
+        Looping {
+            initial: env.clone(),
+            current: env,
+            remaining_cycles,
+        }
+    }
+
+    /// Starts the next cycle once the current one has reached its target, returns whether
+    /// it did
+    fn restart_if_finished<T: Sample>(&mut self) -> bool
+    where
+        E: Env<T>,
+    {
+        if !self.current.target_reached() {
+            return false;
+        }
+        match self.remaining_cycles {
+            Some(0) => return false,
+            Some(n) => self.remaining_cycles = Some(n - 1),
+            None => (),
+        }
+        self.current = self.initial.clone();
+        true
+    }
+}
+
+impl<T: Sample, E: Env<T> + Clone> Env<T> for Looping<E> {
+    fn consume(&mut self) -> T {
+        let value = self.current.consume();
+        self.restart_if_finished();
+        value
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        if steps <= 0 {
+            return self.current_value();
+        }
+        // the partial first cycle, whatever is left of the one in flight
+        let in_current = steps.min(self.current.remaining().max(1));
+        let value = self.current.advance(in_current);
+        let mut steps = steps - in_current;
+        if !self.restart_if_finished() {
+            // past the last cycle the target is held. `remaining` is only an estimate for
+            // some envelopes, one that came up short is stepped through.
+            return if steps > 0 && self.current.target_reached() {
+                self.current.advance(steps)
+            } else {
+                (0..steps).fold(value, |_, _| self.consume())
+            };
+        }
+        if steps == 0 {
+            return value;
+        }
+
+        // whole cycles in between only count down, the last step is always played
+        let cycle_len = self.initial.remaining().max(1);
+        let mut skipped = (steps - 1) / cycle_len;
+        if let Some(n) = self.remaining_cycles {
+            skipped = skipped.min(n.min(i32::MAX as u32) as i32);
+            self.remaining_cycles = Some(n - skipped as u32);
+        }
+        steps -= skipped * cycle_len;
+
+        let value = self.current.advance(steps);
+        self.restart_if_finished();
+        value
+    }
+
+    fn target_reached(&self) -> bool {
+        self.remaining_cycles == Some(0) && self.current.target_reached()
+    }
+
+    fn current_value(&self) -> T {
+        self.current.current_value()
+    }
+
+    /// Finishes the current cycle and drops any remaining ones, this also ends `loop_forever`
+    fn force_finish(&mut self) {
+        self.remaining_cycles = Some(0);
+        self.current.force_finish();
+    }
+
+    /// Infinite when looping forever, saturates at `i32::MAX`
+    fn remaining(&self) -> i32 {
+        match self.remaining_cycles {
+            None => i32::MAX,
+            Some(n) => self
+                .initial
+                .remaining()
+                .saturating_mul(n as i32)
+                .saturating_add(self.current.remaining()),
+        }
+    }
+
+    /// Progress through the current cycle
+    fn progress(&self) -> T {
+        self.current.progress()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Inverted<E, T: Sample = f32> {
+    env: E,
+    max: T,
+}
+
+impl<E, T: Sample> Inverted<E, T> {
+    pub(crate) fn new(env: E, max: T) -> Self {
+        Inverted { env, max }
+    }
+}
+
+impl<T: Sample, E: Env<T>> Env<T> for Inverted<E, T> {
+    fn consume(&mut self) -> T {
+        self.max - self.env.consume()
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        self.max - self.env.advance(steps)
+    }
+
+    fn target_reached(&self) -> bool {
+        self.env.target_reached()
+    }
+
+    fn current_value(&self) -> T {
+        self.max - self.env.current_value()
+    }
+
+    fn force_finish(&mut self) {
+        self.env.force_finish();
+    }
+
+    fn remaining(&self) -> i32 {
+        self.env.remaining()
+    }
+
+    fn progress(&self) -> T {
+        self.env.progress()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scaled<E, T: Sample = f32> {
+    env: E,
+    min: T,
+    range: T,
+    // min and range are in the log domain
+    exponential: bool,
+}
+
+impl<E, T: Sample> Scaled<E, T> {
+    pub(crate) fn new(env: E, min: T, max: T) -> Self {
+        Scaled {
+            env,
+            min,
+            range: max - min,
+            exponential: false,
+        }
+    }
+
+    pub(crate) fn new_exp(env: E, min: T, max: T) -> Self {
+        debug_assert!(
+            min > T::ZERO && max > T::ZERO,
+            "exponential scaling needs positive bounds"
+        );
+        Scaled {
+            env,
+            min: min.ln(),
+            range: max.ln() - min.ln(),
+            exponential: true,
+        }
+    }
+
+    #[inline]
+    fn map(&self, value: T) -> T {
+        let scaled = self.min + self.range * value;
+        if self.exponential {
+            scaled.exp()
+        } else {
+            scaled
+        }
+    }
+}
+
+impl<T: Sample, E: Env<T>> Env<T> for Scaled<E, T> {
+    fn consume(&mut self) -> T {
+        let value = self.env.consume();
+        self.map(value)
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        let value = self.env.advance(steps);
+        self.map(value)
+    }
+
+    fn target_reached(&self) -> bool {
+        self.env.target_reached()
+    }
+
+    fn current_value(&self) -> T {
+        self.map(self.env.current_value())
+    }
+
+    fn force_finish(&mut self) {
+        self.env.force_finish();
+    }
+
+    fn remaining(&self) -> i32 {
+        self.env.remaining()
+    }
+
+    fn progress(&self) -> T {
+        self.env.progress()
+    }
+}
+
+#[derive(Clone)]
+pub struct Mapped<E, F> {
+    env: E,
+    f: F,
+}
+
+impl<E, F> Mapped<E, F> {
+    pub(crate) fn new(env: E, f: F) -> Self {
+        Mapped { env, f }
+    }
+}
+
+impl<T: Sample, E: Env<T>, F: Fn(T) -> T> Env<T> for Mapped<E, F> {
+    fn consume(&mut self) -> T {
+        (self.f)(self.env.consume())
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        (self.f)(self.env.advance(steps))
+    }
+
+    fn target_reached(&self) -> bool {
+        self.env.target_reached()
+    }
+
+    fn current_value(&self) -> T {
+        (self.f)(self.env.current_value())
+    }
+
+    fn force_finish(&mut self) {
+        self.env.force_finish();
+    }
+
+    fn remaining(&self) -> i32 {
+        self.env.remaining()
+    }
+
+    fn progress(&self) -> T {
+        self.env.progress()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Delayed<E> {
+    env: E,
+    delay_steps: i32,
+}
+
+impl<E> Delayed<E> {
+    pub(crate) fn new(env: E, delay_steps: i32) -> Self {
+        Delayed {
+            env,
+            delay_steps: delay_steps.max(0),
+        }
+    }
+}
+
+impl<T: Sample, E: Env<T>> Env<T> for Delayed<E> {
+    fn consume(&mut self) -> T {
+        if self.delay_steps > 0 {
+            self.delay_steps -= 1;
+            self.env.current_value()
+        } else {
+            self.env.consume()
+        }
+    }
+
+    fn target_reached(&self) -> bool {
+        self.delay_steps == 0 && self.env.target_reached()
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        let held = steps.clamp(0, self.delay_steps);
+        self.delay_steps -= held;
+        self.env.advance(steps - held)
+    }
+
+    fn current_value(&self) -> T {
+        self.env.current_value()
+    }
+
+    fn force_finish(&mut self) {
+        self.delay_steps = 0;
+        self.env.force_finish();
+    }
+
+    fn remaining(&self) -> i32 {
+        self.delay_steps.saturating_add(self.env.remaining())
+    }
+
+    /// Stays at 0.0 until the delay has run out
+    fn progress(&self) -> T {
+        self.env.progress()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chained<A, B> {
+    first: A,
+    second: B,
+    second_started: bool,
+    tot_steps: i32,
+}
+
+impl<A, B> Chained<A, B> {
+    pub(crate) fn new<T: Sample>(first: A, second: B) -> Self
+    where
+        A: Env<T>,
+        B: Env<T>,
+    {
+        Chained {
+            tot_steps: first.remaining().saturating_add(second.remaining()),
+            first,
+            second,
+            second_started: false,
+        }
+    }
+}
+
+impl<T: Sample, A: Env<T>, B: Env<T>> Env<T> for Chained<A, B> {
+    fn consume(&mut self) -> T {
+        if self.first.target_reached() {
+            self.second_started = true;
+            self.second.consume()
+        } else {
+            self.first.consume()
+        }
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        let in_first = steps.clamp(0, self.first.remaining());
+        let mut value = self.current_value();
+        if in_first > 0 {
+            value = self.first.advance(in_first);
+        }
+        if steps > in_first {
+            self.second_started = true;
+            value = self.second.advance(steps - in_first);
+        }
+        value
+    }
+
+    fn target_reached(&self) -> bool {
+        self.first.target_reached() && self.second.target_reached()
+    }
+
+    fn current_value(&self) -> T {
+        if self.second_started {
+            self.second.current_value()
+        } else {
+            self.first.current_value()
+        }
+    }
+
+    fn force_finish(&mut self) {
+        self.first.force_finish();
+        self.second.force_finish();
+        self.second_started = true;
+    }
+
+    fn remaining(&self) -> i32 {
+        self.first
+            .remaining()
+            .saturating_add(self.second.remaining())
+    }
+
+    fn progress(&self) -> T {
+        step_progress(self.remaining(), self.tot_steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Env, LinearEnvelope, SCurveEnvelope};
+    use float_cmp::assert_approx_eq;
+
+    #[test]
+    fn repeat_plays_n_times() {
+        let mut env = LinearEnvelope::fade_in(4).repeat(2);
+        let result = (0..10).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.25, 0.5, 0.75, 1.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+        assert!(env.target_reached());
+    }
+
+    #[test]
+    fn repeat_once_is_a_single_play() {
+        let mut env = LinearEnvelope::fade_out(2).repeat(1);
+        assert!(!env.target_reached());
+        env.consume();
+        assert_approx_eq!(f32, env.consume(), 0.0);
+        assert!(env.target_reached());
+    }
+
+    #[test]
+    fn loop_forever_never_finishes() {
+        let mut env = LinearEnvelope::fade_in(3).loop_forever();
+        for _ in 0..100 {
+            let v = env.consume();
+            assert!(v > 0.0 && v <= 1.0);
+            assert!(!env.target_reached());
+        }
+        // 100 = 33 full cycles + 1 step
+        assert_approx_eq!(f32, env.consume(), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn inverted_is_complementary() {
+        let mut env = LinearEnvelope::fade_in(4);
+        let mut inv = LinearEnvelope::fade_in(4).inverted();
+        assert_approx_eq!(f32, inv.current_value(), 1.0);
+        for _ in 0..4 {
+            assert_approx_eq!(f32, env.consume() + inv.consume(), 1.0);
+        }
+        assert!(inv.target_reached());
+        assert_approx_eq!(f32, inv.current_value(), 0.0);
+
+        let mut env = LinearEnvelope::new(0.0, 0.5, 4);
+        let mut inv = LinearEnvelope::new(0.0, 0.5, 4).inverted_from(0.5);
+        assert_approx_eq!(f32, inv.current_value(), 0.5);
+        for _ in 0..4 {
+            assert_approx_eq!(f32, env.consume() + inv.consume(), 0.5);
+        }
+        assert_approx_eq!(f32, inv.current_value(), 0.0);
+    }
+
+    #[test]
+    fn scaled_maps_range() {
+        let mut env = LinearEnvelope::fade_out(4).scaled(200.0, 1000.0);
+        assert_approx_eq!(f32, env.current_value(), 1000.0);
+        let result = (0..4).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![800.0, 600.0, 400.0, 200.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e, epsilon = 1e-4));
+        assert!(env.target_reached());
+    }
+
+    #[test]
+    fn delayed_holds_start_value() {
+        let mut env = LinearEnvelope::fade_in(2).delayed(3);
+        let result = (0..6).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.0, 0.0, 0.0, 0.5, 1.0, 1.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+        assert!(env.target_reached());
+
+        let mut not_started = LinearEnvelope::fade_in(0).delayed(1);
+        assert!(!not_started.target_reached());
+        not_started.consume();
+        assert!(not_started.target_reached());
+
+        // 1.5 ms at 48 kHz
+        let env = LinearEnvelope::fade_in(4).delayed_ms(1.5, 48000.0);
+        assert_eq!(env.remaining(), 72 + 4);
+    }
+
+    #[test]
+    fn adaptors_compose() {
+        let mut env = LinearEnvelope::fade_in(2)
+            .inverted()
+            .scaled(0.0, 0.5)
+            .delayed(1);
+        let result = (0..3).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.5, 0.25, 0.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+    }
+
+    #[test]
+    fn adaptors_advance() {
+        let mut env = LinearEnvelope::fade_in(4)
+            .inverted()
+            .scaled(0.0, 2.0)
+            .delayed(2);
+        assert_approx_eq!(f32, env.advance(1), 2.0);
+        assert_approx_eq!(f32, env.advance(3), 1.0);
+        assert!(!env.target_reached());
+        assert_approx_eq!(f32, env.advance(5), 0.0);
+        assert!(env.target_reached());
+
+        let mut looping = LinearEnvelope::fade_in(4).repeat(3);
+        assert_approx_eq!(f32, looping.advance(6), 0.5);
+        assert_approx_eq!(f32, looping.advance(100), 1.0);
+        assert!(looping.target_reached());
+    }
+
+    #[test]
+    fn looping_advance_matches_consume() {
+        fn check<E: Env + Clone>(env: E) {
+            for first in [0, 1, 3, 5] {
+                for steps in [1, 2, 4, 5, 9, 13, 20, 41] {
+                    let mut stepped = env.clone();
+                    stepped.advance(first);
+                    let mut skipped = stepped.clone();
+                    let expected = (0..steps).fold(0.0, |_, _| stepped.consume());
+                    assert_eq!(skipped.advance(steps), expected, "{} {}", first, steps);
+                    assert_eq!(
+                        skipped.current_value(),
+                        stepped.current_value(),
+                        "{} {}",
+                        first,
+                        steps
+                    );
+                    assert_eq!(skipped.remaining(), stepped.remaining());
+                    assert_eq!(skipped.target_reached(), stepped.target_reached());
+                }
+            }
+        }
+
+        check(LinearEnvelope::fade_in(4).repeat(3));
+        check(LinearEnvelope::fade_in(4).repeat(1));
+        check(LinearEnvelope::fade_in(4).loop_forever());
+        check(SCurveEnvelope::fade_out(3).delayed(2).repeat(4));
+        check(LinearEnvelope::fade_in(1).loop_forever());
+
+        // skips the whole cycles rather than stepping through them
+        let mut forever = LinearEnvelope::fade_in(4).loop_forever();
+        assert_approx_eq!(f32, forever.advance(i32::MAX), 0.75);
+    }
+
+    #[test]
+    fn force_finish_ends_adaptors() {
+        let mut env = LinearEnvelope::fade_in(8).loop_forever();
+        env.consume();
+        env.force_finish();
+        assert!(env.target_reached());
+        assert_eq!(env.consume(), 1.0);
+
+        let mut env = LinearEnvelope::fade_in(8)
+            .inverted()
+            .scaled(2.0, 4.0)
+            .delayed(3);
+        env.force_finish();
+        assert!(env.target_reached());
+        assert_eq!(env.current_value(), 2.0);
+    }
+
+    #[test]
+    fn adaptors_remaining_and_progress() {
+        let mut env = LinearEnvelope::fade_in(4).repeat(3);
+        assert_eq!(env.remaining(), 12);
+        env.advance(6);
+        assert_eq!(env.remaining(), 6);
+        assert_eq!(env.progress(), 0.5);
+        assert_eq!(env.peek(), env.clone().consume());
+        assert_eq!(
+            LinearEnvelope::fade_in(4).loop_forever().remaining(),
+            i32::MAX
+        );
+
+        let mut env = LinearEnvelope::fade_in(4).scaled(2.0, 4.0).delayed(3);
+        assert_eq!(env.remaining(), 7);
+        env.advance(3);
+        assert_eq!(env.progress(), 0.0);
+        assert_eq!(env.peek(), 2.5);
+        env.advance(2);
+        assert_eq!(env.remaining(), 2);
+        assert_eq!(env.progress(), 0.5);
+    }
+
+    #[test]
+    fn then_runs_in_sequence() {
+        let mut env = LinearEnvelope::fade_out(2).then(LinearEnvelope::fade_in(4));
+        assert_eq!(env.remaining(), 6);
+        let result = (0..8).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.5, 0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+        assert!(env.target_reached());
+        assert_eq!(env.progress(), 1.0);
+
+        let mut env = SCurveEnvelope::fade_out(8).then(SCurveEnvelope::fade_in(8));
+        let mut stepped = env.clone();
+        assert_eq!(env.advance(12), stepped.advance(8 + 4));
+        (0..6).for_each(|_| {
+            stepped.consume();
+        });
+        assert_eq!(env.advance(6), stepped.current_value());
+        assert!(env.target_reached());
+
+        let mut env = LinearEnvelope::fade_out(2).then(LinearEnvelope::fade_in(4));
+        env.force_finish();
+        assert_eq!(env.current_value(), 1.0);
+    }
+
+    #[test]
+    fn scaled_exp_sweeps_octaves() {
+        let mut env = LinearEnvelope::fade_in(4).scaled_exp(200.0, 3200.0);
+        assert_approx_eq!(f32, env.current_value(), 200.0, epsilon = 1e-3);
+        let result = (0..4).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![400.0, 800.0, 1600.0, 3200.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e, epsilon = 1e-2));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn scaled_exp_rejects_zero_bound() {
+        LinearEnvelope::fade_in(4).scaled_exp(0.0, 3200.0);
+    }
+
+    #[test]
+    fn map_output_applies_function() {
+        let mut env = LinearEnvelope::fade_in(2).map_output(|v| v * v);
+        assert_approx_eq!(f32, env.consume(), 0.25);
+        assert_approx_eq!(f32, env.consume(), 1.0);
+        assert!(env.target_reached());
+        assert_approx_eq!(f32, env.current_value(), 1.0);
+    }
+}
@@ -1,54 +1,257 @@
+pub mod adaptors;
+pub mod scheduler;
+
+pub use adaptors::{Chained, Delayed, Inverted, Looping, Mapped, Scaled};
+pub use scheduler::EnvelopeScheduler;
+
+use sample::Sample;
+
+/// Envelope producing `T` values, `f32` unless the caller asks for more precision, e.g. a
+/// fade applied to an f64 signal path
 #[allow(dead_code)]
-pub trait Env {
-    fn consume(&mut self) -> f32;
+pub trait Env<T: Sample = f32> {
+    fn consume(&mut self) -> T;
     fn target_reached(&self) -> bool;
+
+    /// The most recently produced value, or the start value before the first `consume`.
+    /// Envelopes written before this existed report 0.0, every envelope in this crate
+    /// overrides it.
+    fn current_value(&self) -> T {
+        T::ZERO
+    }
+
+    /// Jumps straight to the end, afterwards `target_reached` is true and `current_value`
+    /// is the target. The default consumes until then, so an envelope that never reaches its
+    /// target has to override it.
+    fn force_finish(&mut self) {
+        while !self.target_reached() {
+            self.consume();
+        }
+    }
+
+    /// Calls to `consume` left until `target_reached` is true. The default only knows whether
+    /// there is at least one left.
+    fn remaining(&self) -> i32 {
+        if self.target_reached() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// How far along the envelope is, from 0.0 at the start to 1.0 once the target is reached.
+    /// The default jumps from 0.0 to 1.0 at the target.
+    fn progress(&self) -> T {
+        if self.target_reached() {
+            T::ONE
+        } else {
+            T::ZERO
+        }
+    }
+
+    /// The value the next `consume` will return, without consuming it
+    fn peek(&self) -> T
+    where
+        Self: Clone,
+    {
+        self.clone().consume()
+    }
+
+    /// Skips ahead as if `consume` had been called `steps` times and returns the value the
+    /// last of those calls would have produced. The envelopes in this crate compute the
+    /// result directly, the default implementation just loops.
+    fn advance(&mut self, steps: i32) -> T {
+        let mut value = self.current_value();
+        for _ in 0..steps {
+            value = self.consume();
+        }
+        value
+    }
+
+    /// Plays the envelope `times` times in total, restarting from a copy of its initial state
+    fn repeat(self, times: u32) -> Looping<Self>
+    where
+        Self: Sized + Clone,
+    {
+        Looping::new(self, Some(times.saturating_sub(1)))
+    }
+
+    /// Restarts the envelope every time it reaches its target, `target_reached` never returns true
+    fn loop_forever(self) -> Looping<Self>
+    where
+        Self: Sized + Clone,
+    {
+        Looping::new(self, None)
+    }
+
+    /// Outputs `1.0 - value`, e.g. the complementary half of a crossfade
+    fn inverted(self) -> Inverted<Self, T>
+    where
+        Self: Sized,
+    {
+        Inverted::new(self, T::ONE)
+    }
+
+    /// Outputs `max - value`, the mirror image of an envelope running between 0.0 and `max`
+    fn inverted_from(self, max: T) -> Inverted<Self, T>
+    where
+        Self: Sized,
+    {
+        Inverted::new(self, max)
+    }
+
+    /// Maps the 0.0..=1.0 output range onto `min..=max`
+    fn scaled(self, min: T, max: T) -> Scaled<Self, T>
+    where
+        Self: Sized,
+    {
+        Scaled::new(self, min, max)
+    }
+
+    /// Maps the 0.0..=1.0 output range onto `min..=max` exponentially, equal steps of the
+    /// envelope give equal ratios, e.g. octaves for a cutoff sweep. `min` and `max` must be
+    /// positive, debug builds panic otherwise and release builds output NaN or infinity.
+    fn scaled_exp(self, min: T, max: T) -> Scaled<Self, T>
+    where
+        Self: Sized,
+    {
+        Scaled::new_exp(self, min, max)
+    }
+
+    /// Passes every output value through `f`
+    fn map_output<F: Fn(T) -> T>(self, f: F) -> Mapped<Self, F>
+    where
+        Self: Sized,
+    {
+        Mapped::new(self, f)
+    }
+
+    /// Holds the start value for `steps` calls to `consume` before the envelope begins
+    fn delayed(self, steps: i32) -> Delayed<Self>
+    where
+        Self: Sized,
+    {
+        Delayed::new(self, steps)
+    }
+
+    /// Runs `next` once this envelope has reached its target, the pair only reaches its
+    /// target when `next` does
+    fn then<E: Env<T>>(self, next: E) -> Chained<Self, E>
+    where
+        Self: Sized,
+    {
+        Chained::new(self, next)
+    }
+
+    /// `delayed` with the hold given in milliseconds, e.g. to wait out another stage's
+    /// latency before a crossfade starts
+    fn delayed_ms(self, time_ms: f32, sample_rate: f32) -> Delayed<Self>
+    where
+        Self: Sized,
+    {
+        Delayed::new(self, (time_ms * 0.001 * sample_rate).round() as i32)
+    }
+}
+
+/// Number of steps in a `note` long duration at `bpm`, where `note` is a fraction of a whole
+/// note (0.125 for an eighth, 1.0 / 12.0 for an eighth triplet), assuming quarter note beats.
+/// Never less than one step.
+pub fn tempo_synced_steps(note: f32, bpm: f32, sample_rate: f32) -> i32 {
+    let seconds = note * 4.0 * 60.0 / bpm;
+    ((seconds * sample_rate).round() as i32).max(1)
+}
+
+/// Depth for a note played at `velocity` (0.0..=1.0), `sensitivity` 0.0 ignores velocity and
+/// 1.0 makes the depth follow it exactly
+pub fn velocity_depth(velocity: f32, sensitivity: f32) -> f32 {
+    1.0 - sensitivity.clamp(0.0, 1.0) * (1.0 - velocity.clamp(0.0, 1.0))
+}
+
+#[inline]
+fn from_steps<T: Sample>(steps: i32) -> T {
+    T::from_f64(steps as f64)
 }
 
+#[inline]
+fn step_progress<T: Sample>(remaining: i32, total: i32) -> T {
+    if total > 0 {
+        T::ONE - from_steps::<T>(remaining.clamp(0, total)) / from_steps(total)
+    } else {
+        T::ONE
+    }
+}
+
+pub type LinearEnvelope = GenericLinearEnvelope<f32>;
+pub type LinearEnvelope64 = GenericLinearEnvelope<f64>;
+
 #[derive(Debug, Clone)]
-pub struct LinearEnvelope {
-    current_value: f32,
-    target_value: f32,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericLinearEnvelope<T: Sample> {
+    current_value: T,
+    target_value: T,
     num_steps: i32,
-    step_size: f32,
+    tot_steps: i32,
+    step_size: T,
 }
 
 #[allow(dead_code)]
-impl LinearEnvelope {
-    pub fn new(start: f32, end: f32, steps: i32) -> Self {
-        LinearEnvelope {
+impl<T: Sample> GenericLinearEnvelope<T> {
+    pub fn new(start: T, end: T, steps: i32) -> Self {
+        GenericLinearEnvelope {
             current_value: start,
             target_value: end,
             num_steps: steps,
-            step_size: (end - start) / (steps as f32),
+            tot_steps: steps,
+            step_size: (end - start) / from_steps(steps),
         }
     }
 
+    /// `new` with the length given as a note value, see [`tempo_synced_steps`]
+    pub fn new_synced(start: T, end: T, note: f32, bpm: f32, sample_rate: f32) -> Self {
+        GenericLinearEnvelope::new(start, end, tempo_synced_steps(note, bpm, sample_rate))
+    }
+
     pub fn fade_in(steps: i32) -> Self {
-        LinearEnvelope {
-            current_value: 0.0,
-            target_value: 1.0,
+        GenericLinearEnvelope {
+            current_value: T::ZERO,
+            target_value: T::ONE,
             num_steps: steps,
-            step_size: 1.0 / (steps as f32),
+            tot_steps: steps,
+            step_size: T::ONE / from_steps(steps),
         }
     }
 
     pub fn fade_out(steps: i32) -> Self {
-        LinearEnvelope {
-            current_value: 1.0,
-            target_value: 0.0,
+        GenericLinearEnvelope {
+            current_value: T::ONE,
+            target_value: T::ZERO,
             num_steps: steps,
-            step_size: -1.0 / (steps as f32),
+            tot_steps: steps,
+            step_size: -T::ONE / from_steps(steps),
         }
     }
+
+    /// Multiplies every value the envelope produces by `depth`, see also [`velocity_depth`]
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        let depth = T::from_f32(depth);
+        self.current_value *= depth;
+        self.target_value *= depth;
+        self.step_size *= depth;
+        self
+    }
 }
 
 #[allow(dead_code)]
-impl Env for LinearEnvelope {
-    fn consume(&mut self) -> f32 {
+impl<T: Sample> Env<T> for GenericLinearEnvelope<T> {
+    fn consume(&mut self) -> T {
         assert!(self.num_steps >= 0);
         if self.num_steps > 0 {
             self.current_value += self.step_size;
             self.num_steps -= 1;
+            if self.num_steps == 0 {
+                // don't leave the accumulated rounding error behind
+                self.current_value = self.target_value;
+            }
             self.current_value
         } else {
             self.target_value
@@ -58,23 +261,63 @@ impl Env for LinearEnvelope {
     fn target_reached(&self) -> bool {
         self.num_steps == 0
     }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        self.current_value = self.target_value;
+        self.num_steps = 0;
+    }
+
+    fn remaining(&self) -> i32 {
+        self.num_steps
+    }
+
+    fn progress(&self) -> T {
+        step_progress(self.num_steps, self.tot_steps)
+    }
+
+    fn peek(&self) -> T {
+        if self.num_steps > 1 {
+            self.current_value + self.step_size
+        } else {
+            self.target_value
+        }
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        let n = steps.clamp(0, self.num_steps);
+        self.current_value += self.step_size * from_steps(n);
+        self.num_steps -= n;
+        if self.num_steps == 0 {
+            self.current_value = self.target_value;
+        }
+        if n < steps {
+            self.target_value
+        } else {
+            self.current_value
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
-struct ExponentialEnvelope {
-    start_value: f32,
-    current_value: f32,
-    target_value: f32,
+struct ExponentialEnvelope<T: Sample> {
+    start_value: T,
+    current_value: T,
+    target_value: T,
     tot_steps: i32,
     curr_step: i32,
-    z: f32,
-    delta: f32,
+    z: T,
+    delta: T,
 }
 
 #[allow(dead_code)]
-impl ExponentialEnvelope {
-    fn new(start: f32, end: f32, steps: i32, curve: f32) -> Self {
+impl<T: Sample> ExponentialEnvelope<T> {
+    fn new(start: T, end: T, steps: i32, curve: T) -> Self {
         ExponentialEnvelope {
             start_value: start,
             current_value: start,
@@ -87,13 +330,19 @@ impl ExponentialEnvelope {
     }
 }
 
-impl Env for ExponentialEnvelope {
-    fn consume(&mut self) -> f32 {
+impl<T: Sample> ExponentialEnvelope<T> {
+    #[inline]
+    fn value_at(&self, step: i32) -> T {
+        self.delta * (from_steps::<T>(step) / from_steps(self.tot_steps - 1)).powf(self.z)
+            + self.start_value
+    }
+}
+
+impl<T: Sample> Env<T> for ExponentialEnvelope<T> {
+    fn consume(&mut self) -> T {
         assert!(self.curr_step >= 0);
         if self.curr_step <= self.tot_steps {
-            self.current_value = self.delta
-                * (self.curr_step as f32 / (self.tot_steps - 1) as f32).powf(self.z)
-                + self.start_value;
+            self.current_value = self.value_at(self.curr_step);
             self.curr_step += 1;
             self.current_value
         } else {
@@ -101,9 +350,707 @@ impl Env for ExponentialEnvelope {
         }
     }
 
+    fn target_reached(&self) -> bool {
+        self.curr_step >= self.tot_steps
+    }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        self.current_value = self.target_value;
+        self.curr_step = self.tot_steps + 1;
+    }
+
+    fn remaining(&self) -> i32 {
+        (self.tot_steps - self.curr_step).max(0)
+    }
+
+    fn progress(&self) -> T {
+        step_progress(self.remaining(), self.tot_steps)
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        if steps <= 0 {
+            return self.current_value;
+        }
+        let last_step = self.curr_step + steps - 1;
+        if self.curr_step <= self.tot_steps {
+            self.current_value = self.value_at(last_step.min(self.tot_steps));
+            self.curr_step = (last_step + 1).min(self.tot_steps + 1);
+        }
+        if last_step <= self.tot_steps {
+            self.current_value
+        } else {
+            self.target_value
+        }
+    }
+}
+
+pub type SCurveEnvelope = GenericSCurveEnvelope<f32>;
+pub type SCurveEnvelope64 = GenericSCurveEnvelope<f64>;
+
+/// Raised cosine (S-curve) ramp, the slope is zero at both the start and the end
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericSCurveEnvelope<T: Sample> {
+    start_value: T,
+    current_value: T,
+    target_value: T,
+    tot_steps: i32,
+    curr_step: i32,
+    delta: T,
+}
+
+#[allow(dead_code)]
+impl<T: Sample> GenericSCurveEnvelope<T> {
+    pub fn new(start: T, end: T, steps: i32) -> Self {
+        GenericSCurveEnvelope {
+            start_value: start,
+            current_value: start,
+            target_value: end,
+            tot_steps: steps,
+            curr_step: 0,
+            delta: end - start,
+        }
+    }
+
+    /// `new` with the length given as a note value, see [`tempo_synced_steps`]
+    pub fn new_synced(start: T, end: T, note: f32, bpm: f32, sample_rate: f32) -> Self {
+        GenericSCurveEnvelope::new(start, end, tempo_synced_steps(note, bpm, sample_rate))
+    }
+
+    pub fn fade_in(steps: i32) -> Self {
+        GenericSCurveEnvelope::new(T::ZERO, T::ONE, steps)
+    }
+
+    pub fn fade_out(steps: i32) -> Self {
+        GenericSCurveEnvelope::new(T::ONE, T::ZERO, steps)
+    }
+
+    /// Multiplies every value the envelope produces by `depth`, see also [`velocity_depth`]
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        let depth = T::from_f32(depth);
+        self.start_value *= depth;
+        self.current_value *= depth;
+        self.target_value *= depth;
+        self.delta *= depth;
+        self
+    }
+}
+
+impl<T: Sample> GenericSCurveEnvelope<T> {
+    #[inline]
+    fn update_value(&mut self) {
+        let phase = T::PI * (from_steps::<T>(self.curr_step) / from_steps(self.tot_steps));
+        self.current_value =
+            self.start_value + self.delta * T::from_f32(0.5) * (T::ONE - phase.cos());
+    }
+}
+
+impl<T: Sample> Env<T> for GenericSCurveEnvelope<T> {
+    fn consume(&mut self) -> T {
+        assert!(self.curr_step >= 0);
+        if self.curr_step < self.tot_steps {
+            self.curr_step += 1;
+            self.update_value();
+            self.current_value
+        } else {
+            self.target_value
+        }
+    }
+
     fn target_reached(&self) -> bool {
         self.curr_step == self.tot_steps
     }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        self.current_value = self.target_value;
+        self.curr_step = self.tot_steps;
+    }
+
+    fn remaining(&self) -> i32 {
+        (self.tot_steps - self.curr_step).max(0)
+    }
+
+    fn progress(&self) -> T {
+        step_progress(self.remaining(), self.tot_steps)
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        let n = steps.clamp(0, self.tot_steps - self.curr_step);
+        if n > 0 {
+            self.curr_step += n;
+            self.update_value();
+        }
+        if n < steps {
+            self.target_value
+        } else {
+            self.current_value
+        }
+    }
+}
+
+pub const DEFAULT_DB_FLOOR: f32 = -80.0;
+
+// `param_range`'s conversions in `T`, so an f64 fade keeps its precision along the dB path
+#[inline]
+fn db_to_gain<T: Sample>(db: T) -> T {
+    T::from_f32(10.0).powf(db / T::from_f32(20.0))
+}
+
+#[inline]
+fn gain_to_db<T: Sample>(gain: T, floor_db: T) -> T {
+    if gain <= T::ZERO {
+        floor_db
+    } else {
+        (T::from_f32(20.0) * gain.log10()).max(floor_db)
+    }
+}
+
+pub type DecibelEnvelope = GenericDecibelEnvelope<f32>;
+pub type DecibelEnvelope64 = GenericDecibelEnvelope<f64>;
+
+/// Fades between two linear gain values along a straight line in dB. Gains at or below
+/// `floor_db` (including 0.0) are treated as the floor, the final step always lands
+/// exactly on the target gain.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericDecibelEnvelope<T: Sample> {
+    current_value: T,
+    current_db: T,
+    target_value: T,
+    num_steps: i32,
+    tot_steps: i32,
+    step_size_db: T,
+}
+
+#[allow(dead_code)]
+impl<T: Sample> GenericDecibelEnvelope<T> {
+    pub fn new(start: T, end: T, steps: i32) -> Self {
+        GenericDecibelEnvelope::with_floor(start, end, steps, DEFAULT_DB_FLOOR)
+    }
+
+    pub fn with_floor(start: T, end: T, steps: i32, floor_db: f32) -> Self {
+        let floor_db = T::from_f32(floor_db);
+        let start_db = gain_to_db(start, floor_db);
+        let end_db = gain_to_db(end, floor_db);
+        GenericDecibelEnvelope {
+            current_value: start,
+            current_db: start_db,
+            target_value: end,
+            num_steps: steps,
+            tot_steps: steps,
+            step_size_db: (end_db - start_db) / from_steps(steps),
+        }
+    }
+
+    /// `new` with the length given as a note value, see [`tempo_synced_steps`]
+    pub fn new_synced(start: T, end: T, note: f32, bpm: f32, sample_rate: f32) -> Self {
+        GenericDecibelEnvelope::new(start, end, tempo_synced_steps(note, bpm, sample_rate))
+    }
+
+    pub fn fade_in(steps: i32) -> Self {
+        GenericDecibelEnvelope::new(T::ZERO, T::ONE, steps)
+    }
+
+    pub fn fade_out(steps: i32) -> Self {
+        GenericDecibelEnvelope::new(T::ONE, T::ZERO, steps)
+    }
+
+    /// Multiplies every gain the envelope produces by `depth` (clamped to be non-negative),
+    /// i.e. offsets the whole dB path
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        let depth = T::from_f32(depth.max(0.0));
+        self.current_value *= depth;
+        self.current_db += T::from_f32(20.0) * depth.log10();
+        self.target_value *= depth;
+        self
+    }
+}
+
+impl<T: Sample> Env<T> for GenericDecibelEnvelope<T> {
+    fn consume(&mut self) -> T {
+        assert!(self.num_steps >= 0);
+        if self.num_steps > 1 {
+            self.current_db += self.step_size_db;
+            self.num_steps -= 1;
+            self.current_value = db_to_gain(self.current_db);
+        } else {
+            self.num_steps = 0;
+            self.current_value = self.target_value;
+        }
+        self.current_value
+    }
+
+    fn target_reached(&self) -> bool {
+        self.num_steps == 0
+    }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        self.current_value = self.target_value;
+        self.num_steps = 0;
+    }
+
+    fn remaining(&self) -> i32 {
+        self.num_steps
+    }
+
+    fn progress(&self) -> T {
+        step_progress(self.num_steps, self.tot_steps)
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        if steps <= 0 {
+            return self.current_value;
+        }
+        if steps < self.num_steps {
+            self.current_db += self.step_size_db * from_steps(steps);
+            self.num_steps -= steps;
+            self.current_value = db_to_gain(self.current_db);
+        } else {
+            self.num_steps = 0;
+            self.current_value = self.target_value;
+        }
+        self.current_value
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Curve {
+    Linear,
+    /// Power curve, values above 1.0 start slow and end fast
+    Exponential(f32),
+}
+
+impl Curve {
+    #[inline]
+    fn shape<T: Sample>(&self, t: T) -> T {
+        match self {
+            Curve::Linear => t,
+            Curve::Exponential(z) => t.powf(T::from_f32(*z)),
+        }
+    }
+}
+
+pub type BreakpointEnvelope = GenericBreakpointEnvelope<f32>;
+pub type BreakpointEnvelope64 = GenericBreakpointEnvelope<f64>;
+
+/// Plays back `(duration_samples, target_value, curve)` segments in order, each one
+/// starting from where the previous one ended
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericBreakpointEnvelope<T: Sample> {
+    segments: Vec<(i32, T, Curve)>,
+    segment_idx: usize,
+    segment_start: T,
+    curr_step: i32,
+    current_value: T,
+}
+
+#[allow(dead_code)]
+impl<T: Sample> GenericBreakpointEnvelope<T> {
+    pub fn new(start: T, segments: Vec<(i32, T, Curve)>) -> Self {
+        let mut env = GenericBreakpointEnvelope {
+            segments,
+            segment_idx: 0,
+            segment_start: start,
+            curr_step: 0,
+            current_value: start,
+        };
+        env.skip_empty_segments();
+        env
+    }
+
+    /// Multiplies every value the envelope produces by `depth`, see also [`velocity_depth`]
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        let depth = T::from_f32(depth);
+        self.segment_start *= depth;
+        self.current_value *= depth;
+        self.segments
+            .iter_mut()
+            .for_each(|(_, target, _)| *target *= depth);
+        self
+    }
+
+    fn skip_empty_segments(&mut self) {
+        while let Some((duration, target, _)) = self.segments.get(self.segment_idx) {
+            if *duration > 0 {
+                break;
+            }
+            self.current_value = *target;
+            self.segment_start = *target;
+            self.segment_idx += 1;
+        }
+    }
+}
+
+impl<T: Sample> Env<T> for GenericBreakpointEnvelope<T> {
+    fn consume(&mut self) -> T {
+        self.advance(1)
+    }
+
+    fn target_reached(&self) -> bool {
+        self.segment_idx >= self.segments.len()
+    }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        if let Some((_, target, _)) = self.segments.last() {
+            self.current_value = *target;
+            self.segment_start = *target;
+        }
+        self.segment_idx = self.segments.len();
+        self.curr_step = 0;
+    }
+
+    fn remaining(&self) -> i32 {
+        let later: i32 = self
+            .segments
+            .iter()
+            .skip(self.segment_idx)
+            .map(|(duration, _, _)| *duration)
+            .sum();
+        later - self.curr_step
+    }
+
+    fn progress(&self) -> T {
+        let total = self.segments.iter().map(|(duration, _, _)| *duration).sum();
+        step_progress(self.remaining(), total)
+    }
+
+    // same as `advance(1)` without the clone, which would copy the segment list
+    fn peek(&self) -> T {
+        let Some((duration, target, curve)) = self.segments.get(self.segment_idx) else {
+            return self.current_value;
+        };
+        if self.curr_step + 1 < *duration {
+            let t = from_steps::<T>(self.curr_step + 1) / from_steps(*duration);
+            return self.segment_start + (*target - self.segment_start) * curve.shape(t);
+        }
+        self.segments
+            .iter()
+            .skip(self.segment_idx + 1)
+            .take_while(|(duration, _, _)| *duration <= 0)
+            .last()
+            .map_or(*target, |(_, target, _)| *target)
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        let mut remaining = steps.max(0);
+        while remaining > 0 {
+            let Some((duration, target, curve)) = self.segments.get(self.segment_idx) else {
+                break;
+            };
+            let left_in_segment = duration - self.curr_step;
+            if remaining >= left_in_segment {
+                remaining -= left_in_segment;
+                self.current_value = *target;
+                self.segment_start = *target;
+                self.segment_idx += 1;
+                self.curr_step = 0;
+                self.skip_empty_segments();
+            } else {
+                self.curr_step += remaining;
+                remaining = 0;
+                let t = from_steps::<T>(self.curr_step) / from_steps(*duration);
+                self.current_value =
+                    self.segment_start + (*target - self.segment_start) * curve.shape(t);
+            }
+        }
+        self.current_value
+    }
+}
+
+/// Distance from the target below which `Smoother` snaps onto it, relative to the target
+/// once that is past 1 so it stays above the f32 spacing at cutoff sized values
+const SMOOTHER_SNAP_THRESHOLD: f32 = 1e-5;
+
+pub type Smoother = GenericSmoother<f32>;
+pub type Smoother64 = GenericSmoother<f64>;
+
+/// One-pole lowpass moving toward a target, for de-zippering parameter changes.
+/// After `time_ms` the value has covered ~63% of the distance to the target.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericSmoother<T: Sample> {
+    start_value: T,
+    current_value: T,
+    target_value: T,
+    coef: T,
+    time_ms: f32,
+    sample_rate: f32,
+}
+
+#[allow(dead_code)]
+impl<T: Sample> GenericSmoother<T> {
+    pub fn new(initial_value: T, time_ms: f32, sample_rate: f32) -> Self {
+        let mut smoother = GenericSmoother {
+            start_value: initial_value,
+            current_value: initial_value,
+            target_value: initial_value,
+            coef: T::ZERO,
+            time_ms,
+            sample_rate,
+        };
+        smoother.update_coef();
+        smoother
+    }
+
+    pub fn set_target(&mut self, target: T) {
+        self.start_value = self.current_value;
+        self.target_value = target;
+    }
+
+    pub fn get_target(&self) -> T {
+        self.target_value
+    }
+
+    /// Jumps straight to `value` without smoothing
+    pub fn reset(&mut self, value: T) {
+        self.start_value = value;
+        self.current_value = value;
+        self.target_value = value;
+    }
+
+    pub fn set_time_ms(&mut self, time_ms: f32) {
+        self.time_ms = time_ms;
+        self.update_coef();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_coef();
+    }
+
+    #[inline]
+    fn snap_threshold(&self) -> T {
+        T::from_f32(SMOOTHER_SNAP_THRESHOLD) * self.target_value.abs().max(T::ONE)
+    }
+
+    fn update_coef(&mut self) {
+        let time_samples = self.time_ms * 0.001 * self.sample_rate;
+        self.coef = if time_samples > 0.0 {
+            (-T::ONE / T::from_f32(time_samples)).exp()
+        } else {
+            T::ZERO
+        };
+    }
+}
+
+impl<T: Sample> Env<T> for GenericSmoother<T> {
+    fn consume(&mut self) -> T {
+        self.advance(1)
+    }
+
+    fn target_reached(&self) -> bool {
+        (self.current_value - self.target_value).abs() < self.snap_threshold()
+    }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        self.current_value = self.target_value;
+    }
+
+    fn remaining(&self) -> i32 {
+        let distance = (self.current_value - self.target_value).abs();
+        let threshold = self.snap_threshold();
+        if distance < threshold {
+            0
+        } else if self.coef <= T::ZERO {
+            1
+        } else {
+            // first n with coef^n * distance below the snap threshold
+            ((threshold / distance).ln() / self.coef.ln())
+                .to_f64()
+                .floor()
+                .max(0.0) as i32
+                + 1
+        }
+    }
+
+    /// Fraction of the distance covered since the last `set_target`
+    fn progress(&self) -> T {
+        let total = self.target_value - self.start_value;
+        if self.target_reached() || total == T::ZERO {
+            T::ONE
+        } else {
+            ((self.current_value - self.start_value) / total).clamp(T::ZERO, T::ONE)
+        }
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        if steps > 0 {
+            let next = self.target_value
+                + self.coef.powi(steps) * (self.current_value - self.target_value);
+            // a step too small to move the value would never get any closer
+            self.current_value = if next == self.current_value
+                || (next - self.target_value).abs() < self.snap_threshold()
+            {
+                self.target_value
+            } else {
+                next
+            };
+        }
+        self.current_value
+    }
+}
+
+/// Overshoot used by `RcEnvelope::new` for rising stages, aims 30% past the target
+pub const RC_ATTACK_OVERSHOOT: f32 = 0.3;
+/// Overshoot used by `RcEnvelope::new` for falling stages, close to a pure exponential decay
+pub const RC_DECAY_OVERSHOOT: f32 = 0.001;
+
+/// Distance short of the target at which `RcEnvelope` counts as having crossed it, absorbs
+/// rounding in the coefficient so stages end on the step `time_ms` asks for
+const RC_SNAP_THRESHOLD: f32 = 1e-5;
+
+pub type RcEnvelope = GenericRcEnvelope<f32>;
+pub type RcEnvelope64 = GenericRcEnvelope<f64>;
+
+/// Analog style stage: a one-pole (RC) curve charging toward a point past the target, that
+/// stops once it crosses the target. `overshoot` is how far past the target it aims, as a
+/// fraction of the distance from start to target. Small values give a long exponential
+/// tail, larger ones move toward a straight line. The coefficient is chosen so that the
+/// target is reached after `time_ms`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericRcEnvelope<T: Sample> {
+    start_value: T,
+    current_value: T,
+    target_value: T,
+    aim_value: T,
+    coef: T,
+    finished: bool,
+}
+
+#[allow(dead_code)]
+impl<T: Sample> GenericRcEnvelope<T> {
+    /// Uses `RC_ATTACK_OVERSHOOT` when rising and `RC_DECAY_OVERSHOOT` when falling
+    pub fn new(start: T, end: T, time_ms: f32, sample_rate: f32) -> Self {
+        let overshoot = if end >= start {
+            RC_ATTACK_OVERSHOOT
+        } else {
+            RC_DECAY_OVERSHOOT
+        };
+        GenericRcEnvelope::with_overshoot(start, end, time_ms, sample_rate, overshoot)
+    }
+
+    pub fn with_overshoot(
+        start: T,
+        end: T,
+        time_ms: f32,
+        sample_rate: f32,
+        overshoot: f32,
+    ) -> Self {
+        let overshoot = T::from_f32(overshoot.max(1e-6));
+        let time_samples = time_ms * 0.001 * sample_rate;
+        // aim + (start - aim) * coef^n lands on end after time_samples steps
+        let coef = if time_samples > 0.0 {
+            ((overshoot / (T::ONE + overshoot)).ln() / T::from_f32(time_samples)).exp()
+        } else {
+            T::ZERO
+        };
+        GenericRcEnvelope {
+            start_value: start,
+            current_value: start,
+            target_value: end,
+            aim_value: end + (end - start) * overshoot,
+            coef,
+            finished: start == end,
+        }
+    }
+
+    pub fn attack(time_ms: f32, sample_rate: f32) -> Self {
+        GenericRcEnvelope::new(T::ZERO, T::ONE, time_ms, sample_rate)
+    }
+
+    pub fn release(time_ms: f32, sample_rate: f32) -> Self {
+        GenericRcEnvelope::new(T::ONE, T::ZERO, time_ms, sample_rate)
+    }
+
+    #[inline]
+    fn settle(&mut self) {
+        let rising = self.aim_value > self.target_value;
+        let snap = T::from_f32(RC_SNAP_THRESHOLD);
+        if (rising && self.current_value >= self.target_value - snap)
+            || (!rising && self.current_value <= self.target_value + snap)
+        {
+            self.current_value = self.target_value;
+            self.finished = true;
+        }
+    }
+}
+
+impl<T: Sample> Env<T> for GenericRcEnvelope<T> {
+    fn consume(&mut self) -> T {
+        self.advance(1)
+    }
+
+    fn target_reached(&self) -> bool {
+        self.finished
+    }
+
+    fn current_value(&self) -> T {
+        self.current_value
+    }
+
+    fn force_finish(&mut self) {
+        self.current_value = self.target_value;
+        self.finished = true;
+    }
+
+    fn remaining(&self) -> i32 {
+        if self.finished {
+            0
+        } else if self.coef <= T::ZERO {
+            1
+        } else {
+            // first n where the curve gets within the snap threshold of the target
+            let rising = self.aim_value > self.target_value;
+            let snap = if rising {
+                self.target_value - T::from_f32(RC_SNAP_THRESHOLD)
+            } else {
+                self.target_value + T::from_f32(RC_SNAP_THRESHOLD)
+            };
+            let ratio = (snap - self.aim_value) / (self.current_value - self.aim_value);
+            (ratio.ln() / self.coef.ln()).to_f64().ceil().max(1.0) as i32
+        }
+    }
+
+    fn progress(&self) -> T {
+        if self.finished {
+            T::ONE
+        } else {
+            ((self.current_value - self.start_value) / (self.target_value - self.start_value))
+                .clamp(T::ZERO, T::ONE)
+        }
+    }
+
+    fn advance(&mut self, steps: i32) -> T {
+        if steps > 0 && !self.finished {
+            self.current_value =
+                self.aim_value + self.coef.powi(steps) * (self.current_value - self.aim_value);
+            self.settle();
+        }
+        self.current_value
+    }
 }
 
 #[cfg(test)]
@@ -208,4 +1155,551 @@ mod tests {
             .zip(expected_result)
             .for_each(|(r, e)| assert_approx_eq!(f32, r, *e));
     }
+
+    #[test]
+    fn s_curve_up() {
+        let mut env = SCurveEnvelope::fade_in(10);
+        let result = (0..10).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![
+            0.02447174, 0.0954915, 0.2061074, 0.3454915, 0.5, 0.6545085, 0.7938926, 0.9045085,
+            0.9755283, 1.0,
+        ];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e, epsilon = 1e-6));
+        assert!(env.target_reached());
+        assert_approx_eq!(f32, env.consume(), 1.0);
+    }
+
+    #[test]
+    fn s_curve_down() {
+        let mut env = SCurveEnvelope::new(1.0, 0.0, 10);
+        let result = (0..10).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![
+            0.9755283, 0.9045085, 0.7938926, 0.6545085, 0.5, 0.3454915, 0.2061074, 0.0954915,
+            0.02447174, 0.0,
+        ];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn s_curve_flat_ends() {
+        let steps = 1000;
+        let mut env = SCurveEnvelope::fade_in(steps);
+        let result = (0..steps).map(|_| env.consume()).collect::<Vec<_>>();
+        let linear_step = 1.0 / steps as f32;
+
+        // the first and last increments are much smaller than a linear ramp's
+        assert!(result[0] < 0.01 * linear_step);
+        assert!(result[steps as usize - 1] - result[steps as usize - 2] < 0.01 * linear_step);
+        // and the midpoint is steeper
+        assert!(result[500] - result[499] > linear_step);
+    }
+
+    #[test]
+    fn db_fade_down() {
+        let mut env = DecibelEnvelope::new(1.0, 0.1, 2);
+        assert_approx_eq!(f32, env.consume(), 0.31622776, epsilon = 1e-6);
+        assert!(!env.target_reached());
+        assert_approx_eq!(f32, env.consume(), 0.1);
+        assert!(env.target_reached());
+        assert_approx_eq!(f32, env.consume(), 0.1);
+    }
+
+    #[test]
+    fn db_fade_out_to_silence() {
+        let mut env = DecibelEnvelope::fade_out(4);
+        let result = (0..4).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.1, 0.01, 0.001, 0.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn db_fade_in_custom_floor() {
+        let mut env = DecibelEnvelope::with_floor(0.0, 1.0, 3, -60.0);
+        let result = (0..3).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.01, 0.1, 1.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn breakpoint_linear_segments() {
+        let mut env =
+            BreakpointEnvelope::new(0.0, vec![(4, 1.0, Curve::Linear), (2, 0.5, Curve::Linear)]);
+        let result = (0..8).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.25, 0.5, 0.75, 1.0, 0.75, 0.5, 0.5, 0.5];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+        assert!(env.target_reached());
+    }
+
+    #[test]
+    fn breakpoint_exponential_segment() {
+        let mut env = BreakpointEnvelope::new(1.0, vec![(4, 0.0, Curve::Exponential(2.0))]);
+        let result = (0..4).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.9375, 0.75, 0.4375, 0.0];
+
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+    }
+
+    #[test]
+    fn breakpoint_zero_length_segments_jump() {
+        let mut env = BreakpointEnvelope::new(
+            0.0,
+            vec![
+                (0, 1.0, Curve::Linear),
+                (2, 0.0, Curve::Linear),
+                (0, 0.5, Curve::Linear),
+            ],
+        );
+        assert!(!env.target_reached());
+        assert_approx_eq!(f32, env.consume(), 0.5);
+        assert!(!env.target_reached());
+        assert_approx_eq!(f32, env.consume(), 0.5);
+        assert!(env.target_reached());
+
+        let mut empty = BreakpointEnvelope::new(0.3, vec![]);
+        assert!(empty.target_reached());
+        assert_approx_eq!(f32, empty.consume(), 0.3);
+    }
+
+    #[test]
+    fn smoother_time_constant() {
+        // 1 ms at 10 kHz is 10 samples
+        let mut smoother = Smoother::new(0.0, 1.0, 10000.0);
+        smoother.set_target(1.0);
+        assert!(!smoother.target_reached());
+        let result = (0..10).map(|_| smoother.consume()).collect::<Vec<_>>();
+        assert_approx_eq!(f32, result[0], 1.0 - (-0.1_f32).exp(), epsilon = 1e-6);
+        assert_approx_eq!(f32, result[9], 1.0 - (-1.0_f32).exp(), epsilon = 1e-5);
+        assert!(result.windows(2).all(|w| w[1] > w[0]));
+    }
+
+    #[test]
+    fn smoother_reaches_target() {
+        let mut smoother = Smoother::new(1.0, 1.0, 10000.0);
+        smoother.set_target(-1.0);
+        let steps = (0..1000).take_while(|_| {
+            smoother.consume();
+            !smoother.target_reached()
+        });
+        assert!(steps.count() < 200);
+        assert_eq!(smoother.current_value(), -1.0);
+        assert_eq!(smoother.consume(), -1.0);
+    }
+
+    #[test]
+    fn smoother_reaches_large_targets() {
+        // 10 ms at 48 kHz, where an absolute threshold is finer than the f32 spacing
+        for (start, target) in [(200.0, 8000.0), (8000.0, 200.0), (20.0, 20000.0)] {
+            let mut smoother = Smoother::new(start, 10.0, 48000.0);
+            smoother.set_target(target);
+            let expected_steps = smoother.remaining();
+            let steps = (0..48000)
+                .take_while(|_| {
+                    smoother.consume();
+                    !smoother.target_reached()
+                })
+                .count();
+            assert!(steps < 48000, "{} -> {} never settled", start, target);
+            assert!(steps as i32 <= expected_steps);
+            assert_eq!(smoother.current_value(), target);
+            assert_eq!(smoother.remaining(), 0);
+            assert_eq!(smoother.progress(), 1.0);
+        }
+    }
+
+    #[test]
+    fn smoother_zero_time_jumps() {
+        let mut smoother = Smoother::new(0.0, 0.0, 44100.0);
+        smoother.set_target(0.5);
+        assert_eq!(smoother.consume(), 0.5);
+        assert!(smoother.target_reached());
+
+        smoother.set_time_ms(10.0);
+        smoother.set_target(1.0);
+        smoother.consume();
+        smoother.reset(0.25);
+        assert!(smoother.target_reached());
+        assert_eq!(smoother.consume(), 0.25);
+    }
+
+    fn check_advance<E: Env + Clone>(env: E, steps: &[i32]) {
+        let mut stepped = env.clone();
+        let mut skipped = env;
+        for n in steps {
+            let expected = (0..*n).fold(stepped.current_value(), |_, _| stepped.consume());
+            let result = skipped.advance(*n);
+            assert_approx_eq!(f32, result, expected, epsilon = 1e-5);
+            assert_approx_eq!(
+                f32,
+                skipped.current_value(),
+                stepped.current_value(),
+                epsilon = 1e-5
+            );
+            assert_eq!(skipped.target_reached(), stepped.target_reached());
+        }
+        assert_approx_eq!(f32, skipped.consume(), stepped.consume(), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn advance_matches_consume() {
+        let steps = [0, 3, 1, 4, 0, 10];
+        check_advance(LinearEnvelope::new(0.5, -0.5, 10), &steps);
+        check_advance(ExponentialEnvelope::new(0.0, 1.0, 10, 2.0), &steps);
+        check_advance(SCurveEnvelope::fade_out(10), &steps);
+        check_advance(DecibelEnvelope::fade_in(10), &steps);
+        check_advance(
+            BreakpointEnvelope::new(
+                0.0,
+                vec![
+                    (4, 1.0, Curve::Linear),
+                    (0, 0.2, Curve::Linear),
+                    (5, 0.7, Curve::Exponential(3.0)),
+                ],
+            ),
+            &steps,
+        );
+
+        let mut smoother = Smoother::new(0.0, 0.1, 48000.0);
+        smoother.set_target(1.0);
+        check_advance(smoother, &[0, 2, 1, 3, 1000]);
+    }
+
+    #[test]
+    fn linear_finishes_on_target() {
+        // 0.1 isn't representable, so the accumulated value drifts away from the target
+        let mut env = LinearEnvelope::new(0.0, 1.0, 1000);
+        env.advance(999);
+        assert_ne!(env.current_value(), 1.0);
+        assert_eq!(env.consume(), 1.0);
+        assert_eq!(env.current_value(), 1.0);
+        assert!(env.target_reached());
+
+        let mut env = LinearEnvelope::new(0.3, 0.7, 77);
+        (0..77).for_each(|_| {
+            env.consume();
+        });
+        assert_eq!(env.current_value(), 0.7);
+    }
+
+    #[test]
+    fn force_finish_snaps_to_target() {
+        fn check<E: Env>(mut env: E, target: f32) {
+            env.consume();
+            assert!(!env.target_reached());
+            env.force_finish();
+            assert!(env.target_reached());
+            assert_eq!(env.current_value(), target);
+            assert_eq!(env.consume(), target);
+            assert!(env.target_reached());
+        }
+
+        check(LinearEnvelope::new(0.2, 0.6, 100), 0.6);
+        check(ExponentialEnvelope::new(0.0, 0.5, 100, 3.0), 0.5);
+        check(SCurveEnvelope::fade_out(100), 0.0);
+        check(DecibelEnvelope::fade_in(100), 1.0);
+        check(
+            BreakpointEnvelope::new(
+                0.0,
+                vec![(10, 1.0, Curve::Linear), (10, 0.25, Curve::Linear)],
+            ),
+            0.25,
+        );
+
+        let mut smoother = Smoother::new(0.0, 10.0, 48000.0);
+        smoother.set_target(0.8);
+        check(smoother, 0.8);
+    }
+
+    #[test]
+    fn smoother_reached_within_threshold() {
+        let mut smoother = Smoother::new(1.0, 10.0, 48000.0);
+        smoother.set_target(1.0 + SMOOTHER_SNAP_THRESHOLD * 0.5);
+        assert!(smoother.target_reached());
+        smoother.set_target(0.0);
+        assert!(!smoother.target_reached());
+    }
+
+    #[test]
+    fn remaining_progress_and_peek() {
+        fn check<E: Env + Clone>(mut env: E) {
+            let mut last_progress = env.progress();
+            assert_eq!(last_progress, 0.0);
+            while !env.target_reached() {
+                let remaining = env.remaining();
+                assert!(remaining > 0);
+                let peeked = env.peek();
+                assert_eq!(env.consume(), peeked);
+                assert_eq!(env.remaining(), remaining - 1);
+                assert!(env.progress() >= last_progress);
+                last_progress = env.progress();
+            }
+            assert_eq!(env.remaining(), 0);
+            assert_eq!(env.progress(), 1.0);
+        }
+
+        check(LinearEnvelope::new(0.2, 0.6, 100));
+        check(ExponentialEnvelope::new(0.0, 0.5, 100, 3.0));
+        check(SCurveEnvelope::fade_out(100));
+        check(DecibelEnvelope::fade_in(100));
+        check(BreakpointEnvelope::new(
+            0.0,
+            vec![
+                (10, 1.0, Curve::Linear),
+                (0, 0.5, Curve::Linear),
+                (10, 0.25, Curve::Exponential(2.0)),
+            ],
+        ));
+        check(RcEnvelope::attack(1.0, 48000.0));
+        check(RcEnvelope::release(1.0, 48000.0));
+
+        let mut smoother = Smoother::new(0.0, 1.0, 48000.0);
+        smoother.set_target(0.8);
+        check(smoother);
+    }
+
+    #[test]
+    fn tempo_synced_lengths() {
+        // an eighth at 120 bpm is a quarter of a second
+        assert_eq!(tempo_synced_steps(0.125, 120.0, 48000.0), 12000);
+        assert_eq!(tempo_synced_steps(1.0, 60.0, 44100.0), 176400);
+        assert_eq!(tempo_synced_steps(1.0 / 12.0, 100.0, 48000.0), 9600);
+        assert_eq!(tempo_synced_steps(1e-9, 120.0, 48000.0), 1);
+
+        let mut env = LinearEnvelope::new_synced(0.0, 1.0, 0.25, 120.0, 100.0);
+        env.advance(49);
+        assert!(!env.target_reached());
+        assert_eq!(env.consume(), 1.0);
+        assert!(env.target_reached());
+
+        let mut env = SCurveEnvelope::new_synced(1.0, 0.0, 0.25, 120.0, 100.0);
+        env.advance(50);
+        assert!(env.target_reached());
+
+        let mut env = DecibelEnvelope::new_synced(1.0, 0.5, 0.25, 120.0, 100.0);
+        env.advance(50);
+        assert!(env.target_reached());
+    }
+
+    #[test]
+    fn depth_scales_envelopes() {
+        let mut env = LinearEnvelope::fade_in(4).with_depth(0.5);
+        let result = (0..4).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![0.125, 0.25, 0.375, 0.5];
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+
+        let mut plain = SCurveEnvelope::fade_out(8);
+        let mut deep = SCurveEnvelope::fade_out(8).with_depth(0.25);
+        assert_approx_eq!(f32, deep.current_value(), 0.25);
+        (0..8).for_each(|_| assert_approx_eq!(f32, deep.consume(), plain.consume() * 0.25));
+
+        let mut plain = DecibelEnvelope::new(1.0, 0.1, 8);
+        let mut deep = DecibelEnvelope::new(1.0, 0.1, 8).with_depth(0.5);
+        (0..8).for_each(|_| {
+            assert_approx_eq!(f32, deep.consume(), plain.consume() * 0.5, epsilon = 1e-6)
+        });
+
+        let mut silent = DecibelEnvelope::fade_in(4).with_depth(0.0);
+        (0..4).for_each(|_| assert_eq!(silent.consume(), 0.0));
+
+        let mut env =
+            BreakpointEnvelope::new(1.0, vec![(2, 0.0, Curve::Linear), (2, 0.5, Curve::Linear)])
+                .with_depth(2.0);
+        assert_approx_eq!(f32, env.current_value(), 2.0);
+        let result = (0..4).map(|_| env.consume()).collect::<Vec<_>>();
+        let expected_result = vec![1.0, 0.0, 0.5, 1.0];
+        result
+            .into_iter()
+            .zip(expected_result)
+            .for_each(|(r, e)| assert_approx_eq!(f32, r, e));
+    }
+
+    #[test]
+    fn velocity_depth_follows_sensitivity() {
+        assert_approx_eq!(f32, velocity_depth(0.5, 0.0), 1.0);
+        assert_approx_eq!(f32, velocity_depth(0.5, 1.0), 0.5);
+        assert_approx_eq!(f32, velocity_depth(0.0, 0.5), 0.5);
+        assert_approx_eq!(f32, velocity_depth(1.0, 0.7), 1.0);
+        assert_approx_eq!(f32, velocity_depth(2.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn rc_reaches_target_in_time() {
+        // 10 ms at 4.8 kHz is 48 steps
+        for (start, end) in [(0.0, 1.0), (1.0, 0.0), (0.2, 0.9), (0.5, -0.5)] {
+            let mut env = RcEnvelope::new(start, end, 10.0, 4800.0);
+            let result = (0..48).map(|_| env.consume()).collect::<Vec<_>>();
+            assert!(env.target_reached(), "{} -> {}", start, end);
+            assert_eq!(result[47], end);
+            assert!(!result[..46].contains(&end), "{} -> {}", start, end);
+            assert!(result
+                .windows(2)
+                .all(|w| (w[1] - w[0]) * (end - start) >= 0.0));
+            assert_eq!(env.consume(), end);
+        }
+    }
+
+    #[test]
+    fn rc_curve_shapes() {
+        // charging curves move fastest at the start
+        let mut attack = RcEnvelope::attack(10.0, 4800.0);
+        assert!(attack.advance(24) > 0.6);
+
+        let mut release = RcEnvelope::release(10.0, 4800.0);
+        assert!(release.advance(24) < 0.05);
+
+        // a huge overshoot is close to linear
+        let mut env = RcEnvelope::with_overshoot(0.0, 1.0, 10.0, 4800.0, 1e3);
+        assert_approx_eq!(f32, env.advance(24), 0.5, epsilon = 1e-3);
+
+        let mut instant = RcEnvelope::attack(0.0, 4800.0);
+        assert!(!instant.target_reached());
+        assert_eq!(instant.consume(), 1.0);
+        assert!(instant.target_reached());
+    }
+
+    #[test]
+    fn f64_envelopes_match_f32() {
+        fn check<A: Env<f32>, B: Env<f64>>(mut single: A, mut double: B) {
+            loop {
+                let (s, d) = (single.current_value(), double.current_value());
+                assert!(
+                    (s as f64 - d).abs() < 1e-5 * d.abs().max(1.0),
+                    "{} {}",
+                    s,
+                    d
+                );
+                assert_eq!(single.target_reached(), double.target_reached());
+                if single.target_reached() {
+                    break;
+                }
+                single.consume();
+                double.consume();
+            }
+        }
+
+        check(LinearEnvelope::fade_in(100), LinearEnvelope64::fade_in(100));
+        check(
+            SCurveEnvelope::fade_out(100),
+            SCurveEnvelope64::fade_out(100),
+        );
+        check(
+            DecibelEnvelope::fade_in(100),
+            DecibelEnvelope64::fade_in(100),
+        );
+        check(
+            RcEnvelope::attack(10.0, 4800.0),
+            RcEnvelope64::attack(10.0, 4800.0),
+        );
+        check(
+            Smoother::new(0.0, 1.0, 4800.0),
+            Smoother64::new(0.0, 1.0, 4800.0),
+        );
+        check(
+            BreakpointEnvelope::new(0.0, vec![(10, 1.0, Curve::Exponential(2.0))]),
+            BreakpointEnvelope64::new(0.0, vec![(10, 1.0, Curve::Exponential(2.0))]),
+        );
+        check(
+            LinearEnvelope::fade_in(10).then(SCurveEnvelope::fade_out(10)),
+            LinearEnvelope64::fade_in(10).then(SCurveEnvelope64::fade_out(10)),
+        );
+        check(
+            LinearEnvelope::fade_in(10).scaled_exp(20.0, 20000.0),
+            LinearEnvelope64::fade_in(10).scaled_exp(20.0, 20000.0),
+        );
+
+        // the step size rounds in f32 and the error adds up over a long fade
+        let mut single = LinearEnvelope::fade_in(1_000_000);
+        let mut double = LinearEnvelope64::fade_in(1_000_000);
+        assert!((single.advance(999_999) as f64 - 0.999999).abs() > 1e-9);
+        assert!((double.advance(999_999) - 0.999999).abs() < 1e-9);
+    }
+
+    /// Implements only the methods `Env` had at first, like an envelope written downstream
+    struct Countdown(i32);
+
+    impl Env for Countdown {
+        fn consume(&mut self) -> f32 {
+            self.0 = (self.0 - 1).max(0);
+            self.0 as f32
+        }
+
+        fn target_reached(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    #[test]
+    fn provided_methods_have_defaults() {
+        let mut env = Countdown(3);
+        assert_eq!(env.current_value(), 0.0);
+        assert_eq!(env.advance(2), 1.0);
+        assert_eq!(env.remaining(), 1);
+        assert_eq!(env.progress(), 0.0);
+        env.force_finish();
+        assert!(env.target_reached());
+        assert_eq!(env.remaining(), 0);
+        assert_eq!(env.progress(), 1.0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_mid_fade() {
+        fn check<E>(mut env: E)
+        where
+            E: Env + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        {
+            env.advance(3);
+            let saved = serde_json::to_string(&env).unwrap();
+            let mut restored: E = serde_json::from_str(&saved).unwrap();
+            assert_eq!(restored.current_value(), env.current_value());
+            for _ in 0..10 {
+                assert_eq!(restored.consume(), env.consume());
+                assert_eq!(restored.target_reached(), env.target_reached());
+            }
+        }
+
+        check(LinearEnvelope::new(0.2, 0.9, 8));
+        check(SCurveEnvelope::fade_in(8));
+        check(DecibelEnvelope::fade_out(8));
+        check(BreakpointEnvelope::new(
+            0.0,
+            vec![(4, 1.0, Curve::Exponential(2.0)), (4, 0.5, Curve::Linear)],
+        ));
+        check(RcEnvelope::attack(1.0, 8000.0));
+        check(
+            LinearEnvelope::fade_in(4)
+                .repeat(2)
+                .scaled(1.0, 2.0)
+                .delayed(2),
+        );
+
+        let mut smoother = Smoother::new(0.0, 1.0, 8000.0);
+        smoother.set_target(1.0);
+        check(smoother);
+    }
 }
@@ -0,0 +1,149 @@
+use sample::{MemoryFootprint, Sample};
+
+use crate::Env;
+
+/// Starts envelopes at sample offsets inside a block, for sample accurate automation of a
+/// gain. The queue is allocated up front so scheduling never allocates on the audio thread.
+#[derive(Debug, Clone)]
+pub struct EnvelopeScheduler<E, T: Sample = f32> {
+    active: Option<E>,
+    // output once the active envelope is replaced or before the first one starts
+    held_value: T,
+    events: Vec<(usize, E)>,
+    capacity: usize,
+}
+
+impl<T: Sample, E: Env<T>> EnvelopeScheduler<E, T> {
+    /// `capacity` is the most events that can be queued at once
+    pub fn new(initial_value: T, capacity: usize) -> Self {
+        EnvelopeScheduler {
+            active: None,
+            held_value: initial_value,
+            events: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Queues `env` to start at `offset` samples into the next processed block, offsets past
+    /// the end of that block carry over into the following ones. Events at the same offset
+    /// start in the order they were scheduled, so the last one wins. Returns false without
+    /// queueing when the queue is full.
+    pub fn schedule(&mut self, offset: usize, env: E) -> bool {
+        if self.events.len() == self.capacity {
+            return false;
+        }
+        let idx = self.events.partition_point(|(o, _)| *o <= offset);
+        self.events.insert(idx, (offset, env));
+        true
+    }
+
+    pub fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn current_value(&self) -> T {
+        self.active
+            .as_ref()
+            .map_or(self.held_value, |env| env.current_value())
+    }
+
+    /// Writes the envelope values for this block into `block`
+    pub fn fill(&mut self, block: &mut [T]) {
+        self.run(block, |out, value| *out = value);
+    }
+
+    /// Multiplies `block` by the envelope, sample by sample
+    pub fn process_block(&mut self, block: &mut [T]) {
+        self.run(block, |out, value| *out *= value);
+    }
+
+    fn run<F: Fn(&mut T, T)>(&mut self, block: &mut [T], apply: F) {
+        let len = block.len();
+        let mut pos = 0;
+        while pos < len {
+            let next_event = match self.events.first() {
+                Some((offset, _)) if *offset < len => *offset,
+                _ => len,
+            };
+            block[pos..next_event.max(pos)]
+                .iter_mut()
+                .for_each(|out| apply(out, self.next_value()));
+            pos = next_event.max(pos);
+
+            while matches!(self.events.first(), Some((offset, _)) if *offset <= pos && pos < len) {
+                self.held_value = self.current_value();
+                self.active = Some(self.events.remove(0).1);
+            }
+        }
+
+        self.events
+            .iter_mut()
+            .for_each(|(offset, _)| *offset = offset.saturating_sub(len));
+    }
+
+    #[inline]
+    fn next_value(&mut self) -> T {
+        match &mut self.active {
+            Some(env) => env.consume(),
+            None => self.held_value,
+        }
+    }
+}
+
+/// The event queue, whatever the queued envelopes allocate themselves isn't counted
+impl<T: Sample, E: Env<T>> MemoryFootprint for EnvelopeScheduler<E, T> {
+    fn memory_footprint(&self) -> usize {
+        self.events.capacity() * std::mem::size_of::<(usize, E)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinearEnvelope;
+
+    #[test]
+    fn starts_on_the_scheduled_sample() {
+        let mut gain = EnvelopeScheduler::new(1.0, 4);
+        assert!(gain.schedule(2, LinearEnvelope::new(1.0, 0.0, 2)));
+        assert!(gain.schedule(6, LinearEnvelope::new(0.0, 1.0, 4)));
+        let mut block = [2.0_f32; 8];
+        gain.process_block(&mut block);
+        assert_eq!(block, [2.0, 2.0, 1.0, 0.0, 0.0, 0.0, 0.5, 1.0]);
+
+        // the rest of the second ramp runs into the next block
+        let mut block = [0.0_f32; 4];
+        gain.fill(&mut block);
+        assert_eq!(block, [0.75, 1.0, 1.0, 1.0]);
+        assert_eq!(gain.pending_events(), 0);
+    }
+
+    #[test]
+    fn late_events_carry_over() {
+        let mut gain = EnvelopeScheduler::new(0.0, 2);
+        assert!(gain.schedule(5, LinearEnvelope::fade_in(1)));
+        assert!(gain.schedule(5, LinearEnvelope::new(0.5, 0.5, 1)));
+        assert!(!gain.schedule(0, LinearEnvelope::fade_in(1)));
+
+        let mut block = [0.0_f32; 4];
+        gain.fill(&mut block);
+        assert_eq!(block, [0.0; 4]);
+        assert_eq!(gain.pending_events(), 2);
+
+        gain.fill(&mut block);
+        assert_eq!(block, [0.0, 0.5, 0.5, 0.5]);
+        assert_eq!(gain.pending_events(), 0);
+    }
+
+    #[test]
+    fn footprint_is_the_event_queue() {
+        let mut gain = EnvelopeScheduler::new(0.0, 8);
+        let expected = 8 * std::mem::size_of::<(usize, LinearEnvelope)>();
+        assert_eq!(gain.memory_footprint(), expected);
+
+        // scheduling and running events never grows the queue
+        (0..8).for_each(|i| assert!(gain.schedule(i, LinearEnvelope::fade_in(2))));
+        gain.fill(&mut [0.0_f32; 16]);
+        assert_eq!(gain.memory_footprint(), expected);
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::{Arc, Mutex};
+
+use envelope::{Env, LinearEnvelope};
+use sample::MemoryFootprint;
+
+struct Shared<T> {
+    pending: Mutex<Option<T>>,
+    retired: Mutex<Option<T>>,
+}
+
+/// Worker side of a [`HotSwappable`], owns every allocation and deallocation
+pub struct HotSwapSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> HotSwapSender<T> {
+    /// Queues `value` for the next block boundary, a value that was queued but never picked
+    /// up is dropped here
+    pub fn send(&self, value: T) {
+        let replaced = self.shared.pending.lock().unwrap().replace(value);
+        drop(replaced);
+        drop(self.collect_retired());
+    }
+
+    /// Takes back a value the audio side is done with so it can be dropped or reused on this
+    /// thread
+    pub fn collect_retired(&self) -> Option<T> {
+        self.shared.retired.lock().unwrap().take()
+    }
+}
+
+/// Double buffered processor state (kernels, coefficients, whole filters) that is rebuilt on
+/// a caller provided thread and swapped in at a block boundary.
+///
+/// The audio side never blocks, it only `try_lock`s the hand over slots. While a swap is
+/// crossfading the old and the new value both run, and the old one is handed back to the
+/// sender afterwards instead of being dropped on the audio thread. A new value is only
+/// picked up once the previous one has been handed back.
+pub struct HotSwappable<T> {
+    active: T,
+    previous: Option<T>,
+    fade: Option<LinearEnvelope>,
+    fade_len: i32,
+    scratch: Vec<f32>,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> HotSwappable<T> {
+    /// `max_block_size` sizes the crossfade scratch buffer, `fade_len` is in samples
+    pub fn new(initial: T, fade_len: i32, max_block_size: usize) -> (Self, HotSwapSender<T>) {
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(None),
+            retired: Mutex::new(None),
+        });
+        (
+            HotSwappable {
+                active: initial,
+                previous: None,
+                fade: None,
+                fade_len,
+                scratch: vec![0.0_f32; max_block_size],
+                shared: shared.clone(),
+            },
+            HotSwapSender { shared },
+        )
+    }
+
+    pub fn get(&self) -> &T {
+        &self.active
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.active
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Picks up a pending value if there is one, call once per block before processing.
+    /// Returns true if a swap started.
+    pub fn swap_at_block_boundary(&mut self) -> bool {
+        if self.fade.is_none() {
+            self.retire_previous();
+        }
+        if self.previous.is_some() {
+            return false;
+        }
+
+        let Ok(mut pending) = self.shared.pending.try_lock() else {
+            return false;
+        };
+        let Some(new_value) = pending.take() else {
+            return false;
+        };
+        drop(pending);
+
+        self.previous = Some(std::mem::replace(&mut self.active, new_value));
+        self.fade = if self.fade_len > 0 {
+            Some(LinearEnvelope::fade_in(self.fade_len))
+        } else {
+            None
+        };
+        true
+    }
+
+    /// Runs `process` on the active value, during a swap it also runs on the previous one
+    /// with a copy of the input and the two outputs are crossfaded
+    pub fn process_with<F>(&mut self, buffer: &mut [f32], mut process: F)
+    where
+        F: FnMut(&mut T, &mut [f32]),
+    {
+        let (Some(previous), Some(fade)) = (&mut self.previous, &mut self.fade) else {
+            process(&mut self.active, buffer);
+            return;
+        };
+
+        let scratch = &mut self.scratch[..buffer.len()];
+        scratch.copy_from_slice(buffer);
+        process(previous, scratch);
+        process(&mut self.active, buffer);
+
+        buffer
+            .iter_mut()
+            .zip(scratch.iter())
+            .for_each(|(new, old)| {
+                let gain = fade.consume();
+                *new = *old + gain * (*new - *old);
+            });
+
+        if fade.target_reached() {
+            self.fade = None;
+            self.retire_previous();
+        }
+    }
+
+    fn retire_previous(&mut self) {
+        if self.previous.is_none() {
+            return;
+        }
+        if let Ok(mut retired) = self.shared.retired.try_lock() {
+            if retired.is_none() {
+                *retired = self.previous.take();
+            }
+        }
+    }
+}
+
+/// The scratch buffer plus the active value, and the previous one while it's still held.
+/// The hand over slots are shared with the sender and aren't counted.
+impl<T: MemoryFootprint> MemoryFootprint for HotSwappable<T> {
+    fn memory_footprint(&self) -> usize {
+        self.scratch.memory_footprint()
+            + self.active.memory_footprint()
+            + self.previous.as_ref().map_or(0, |p| p.memory_footprint())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use circular_buffer::circular_buffer::{CircularDelayBuffer, TiledConv};
+    use iir_biquad_filter::iir_biquad_filter::{FilterOrder, FilterType, IIRBiquadFilter};
+
+    const BLOCK: usize = 32;
+
+    fn make_filter(cutoff: f32) -> IIRBiquadFilter {
+        let mut f = IIRBiquadFilter::new(FilterType::Lowpass);
+        f.init(&48000.0, &cutoff, FilterOrder::Second);
+        f
+    }
+
+    #[test]
+    fn swaps_filter_built_on_worker_thread() {
+        let (mut filter, sender) = HotSwappable::new(make_filter(1000.0), 64, BLOCK);
+
+        let worker = std::thread::spawn(move || {
+            sender.send(make_filter(5000.0));
+            sender
+        });
+        let sender = worker.join().unwrap();
+
+        let mut out = Vec::new();
+        let mut started = 0;
+        for _ in 0..8 {
+            if filter.swap_at_block_boundary() {
+                started += 1;
+            }
+            let mut block = [1.0_f32; BLOCK];
+            filter.process_with(&mut block, |f, b| f.process_block(b));
+            out.extend_from_slice(&block);
+        }
+
+        assert_eq!(started, 1);
+        assert!(!filter.is_fading());
+        assert_eq!(filter.get().get_current_cutoff(), 5000.0);
+        assert!(out.iter().all(|v| v.is_finite()));
+        // the old filter came back to the worker side
+        let retired = sender.collect_retired().unwrap();
+        assert_eq!(retired.get_current_cutoff(), 1000.0);
+        assert!(sender.collect_retired().is_none());
+    }
+
+    #[test]
+    fn crossfade_is_continuous() {
+        // swapping between two gains, the output moves linearly from one to the other
+        let (mut gain, sender) = HotSwappable::new(1.0_f32, 8, BLOCK);
+        sender.send(3.0);
+        assert!(gain.swap_at_block_boundary());
+        let mut block = [1.0_f32; 16];
+        gain.process_with(&mut block, |g, b| b.iter_mut().for_each(|v| *v *= *g));
+
+        let expected = [
+            1.25, 1.5, 1.75, 2.0, 2.25, 2.5, 2.75, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0,
+        ];
+        assert_eq!(block, expected);
+        assert_eq!(sender.collect_retired(), Some(1.0));
+    }
+
+    #[test]
+    fn waits_for_fade_and_hand_back() {
+        type Conv = (TiledConv, Vec<f32>);
+        fn conv(kernel: &[f32]) -> Conv {
+            (TiledConv::new(kernel.len(), BLOCK), kernel.to_vec())
+        }
+        fn run(c: &mut Conv, b: &mut [f32]) {
+            c.0.convolve::<f32, 8>(b, &c.1);
+        }
+        fn queue(sender: &HotSwapSender<Conv>, c: Conv) {
+            // skips the clean up `send` does to keep the first kernel unclaimed
+            sender.shared.pending.lock().unwrap().replace(c);
+        }
+
+        let (mut kernel, sender) = HotSwappable::new(conv(&[1.0, 0.0]), BLOCK as i32, BLOCK);
+
+        queue(&sender, conv(&[0.5, 0.5]));
+        assert!(kernel.swap_at_block_boundary());
+        queue(&sender, conv(&[0.0, 1.0]));
+        // still fading, the newest kernel has to wait
+        assert!(!kernel.swap_at_block_boundary());
+        let mut block = [1.0_f32; BLOCK];
+        kernel.process_with(&mut block, run);
+        assert!(!kernel.is_fading());
+
+        assert!(kernel.swap_at_block_boundary());
+        let mut block = [1.0_f32; BLOCK];
+        kernel.process_with(&mut block, run);
+        queue(&sender, conv(&[0.25, 0.75]));
+        // faded out, but the worker never collected the first kernel so the second one
+        // can't be handed back yet
+        assert!(!kernel.swap_at_block_boundary());
+
+        assert_eq!(sender.collect_retired().unwrap().1, vec![1.0, 0.0]);
+        assert!(kernel.swap_at_block_boundary());
+        assert_eq!(kernel.get().1, vec![0.25, 0.75]);
+        assert_eq!(sender.collect_retired().unwrap().1, vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn footprint_counts_both_values_while_fading() {
+        let delay_footprint = CircularDelayBuffer::new(16).memory_footprint();
+        let scratch = BLOCK * std::mem::size_of::<f32>();
+        let (mut delay, sender) = HotSwappable::new(CircularDelayBuffer::new(16), 8, BLOCK);
+        assert_eq!(delay.memory_footprint(), scratch + delay_footprint);
+
+        sender.send(CircularDelayBuffer::new(16));
+        assert!(delay.swap_at_block_boundary());
+        assert_eq!(delay.memory_footprint(), scratch + 2 * delay_footprint);
+
+        let mut block = [1.0_f32; BLOCK];
+        delay.process_with(&mut block, |d, b| d.delay(b));
+        assert!(!delay.is_fading());
+        assert_eq!(delay.memory_footprint(), scratch + delay_footprint);
+        assert!(sender.collect_retired().is_some());
+    }
+}
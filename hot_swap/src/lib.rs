@@ -0,0 +1 @@
+pub mod hot_swap;
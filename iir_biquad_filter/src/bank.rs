@@ -0,0 +1,180 @@
+use std::simd::{LaneCount, Simd, SupportedLaneCount};
+
+use sample::MemoryFootprint;
+
+use crate::iir_biquad_filter::{FilterType, IIRBiquadFilter, MAX_SECTIONS};
+
+/// `N` channels of one f32 filter run in the lanes of a SIMD vector. Same results as a
+/// `MultiChannelBiquad`, every lane does the same arithmetic a scalar filter would.
+#[derive(Debug)]
+pub struct BiquadBank<const N: usize>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    filter: IIRBiquadFilter,
+    coefs: [[Simd<f32, N>; 5]; MAX_SECTIONS],
+    states: [[Simd<f32, N>; 2]; MAX_SECTIONS],
+    running: usize,
+}
+
+impl<const N: usize> BiquadBank<N>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    pub fn new(ft: FilterType) -> Self {
+        let filter = IIRBiquadFilter::new(ft);
+        let running = filter.num_sections();
+        BiquadBank {
+            filter,
+            coefs: [[Simd::splat(0.0); 5]; MAX_SECTIONS],
+            states: [[Simd::splat(0.0); 2]; MAX_SECTIONS],
+            running,
+        }
+    }
+
+    /// The shared settings, configure the filter through this. Its own state isn't used.
+    pub fn filter(&self) -> &IIRBiquadFilter {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut IIRBiquadFilter {
+        &mut self.filter
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+        self.states = [[Simd::splat(0.0); 2]; MAX_SECTIONS];
+    }
+
+    /// One sample of every channel
+    pub fn process_frame(&mut self, frame: &mut [f32; N]) {
+        let sections = self.sync_sections();
+        *frame = self
+            .run_frame(sections, Simd::from_array(*frame))
+            .to_array();
+    }
+
+    /// `block` holds whole frames of `N` interleaved channels
+    pub fn process_interleaved(&mut self, block: &mut [f32]) {
+        assert!(block.len().is_multiple_of(N));
+        let sections = self.sync_sections();
+        block.chunks_exact_mut(N).for_each(|frame| {
+            let y = self.run_frame(sections, Simd::from_slice(frame));
+            y.copy_to_slice(frame);
+        });
+    }
+
+    /// One block per channel, all the same length
+    pub fn process_planar(&mut self, channels: &mut [&mut [f32]; N]) {
+        let len = channels.first().map_or(0, |c| c.len());
+        assert!(channels.iter().all(|c| c.len() == len));
+        let sections = self.sync_sections();
+        (0..len).for_each(|n| {
+            let x = Simd::from_array(std::array::from_fn(|c| channels[c][n]));
+            let y = self.run_frame(sections, x).to_array();
+            channels.iter_mut().zip(y).for_each(|(c, y)| c[n] = y);
+        });
+    }
+
+    #[inline]
+    fn run_frame(&mut self, sections: usize, x: Simd<f32, N>) -> Simd<f32, N> {
+        if self.filter.is_ramping() {
+            self.filter.advance_ramp();
+            self.splat_coefs();
+        }
+        self.coefs
+            .iter()
+            .zip(self.states.iter_mut())
+            .take(sections)
+            .fold(x, |x, ([b0, b1, b2, a1, a2], [w1, w2])| {
+                let y = (*b0 * x) + *w1;
+                *w1 = (*b1 * x) - (*a1 * y) + *w2;
+                *w2 = (*b2 * x) - (*a2 * y);
+                y
+            })
+    }
+
+    fn splat_coefs(&mut self) {
+        self.coefs
+            .iter_mut()
+            .zip(self.filter.coefs().iter())
+            .for_each(|(lanes, c)| *lanes = c.map(Simd::splat));
+    }
+
+    /// Picks up setting changes at the start of every call, a section that was not running
+    /// has no meaningful state
+    fn sync_sections(&mut self) -> usize {
+        self.splat_coefs();
+        let sections = self.filter.num_sections();
+        self.states
+            .iter_mut()
+            .take(sections)
+            .skip(self.running)
+            .for_each(|s| *s = [Simd::splat(0.0); 2]);
+        self.running = sections;
+        sections
+    }
+}
+
+impl<const N: usize> MemoryFootprint for BiquadBank<N> where LaneCount<N>: SupportedLaneCount {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iir_biquad_filter::FilterOrder;
+
+    fn channel(c: usize) -> Vec<f32> {
+        (0..256)
+            .map(|n| (n as f32 * (0.05 + 0.3 * c as f32)).sin())
+            .collect()
+    }
+
+    #[test]
+    fn lanes_match_scalar_filters() {
+        let mut bank = BiquadBank::<4>::new(FilterType::Highpass);
+        bank.filter_mut().init(&48000.0, &800.0, FilterOrder::Third);
+        let mut scalar: Vec<IIRBiquadFilter> = (0..4)
+            .map(|_| {
+                let mut f = IIRBiquadFilter::new(FilterType::Highpass);
+                f.init(&48000.0, &800.0, FilterOrder::Third);
+                f
+            })
+            .collect();
+
+        let mut interleaved: Vec<f32> = (0..256)
+            .flat_map(|n| (0..4).map(move |c| channel(c)[n]))
+            .collect();
+        bank.process_interleaved(&mut interleaved);
+        (0..4).for_each(|c| {
+            let mut expected = channel(c);
+            scalar[c].process_block(&mut expected);
+            let lane: Vec<f32> = interleaved.iter().skip(c).step_by(4).copied().collect();
+            assert_eq!(lane, expected);
+        });
+
+        // settings changes reach the lanes, ramps included
+        bank.filter_mut().set_cutoff_smoothing(32);
+        bank.filter_mut().set_cutoff(3000.0);
+        let mut planar: Vec<Vec<f32>> = (0..4).map(channel).collect();
+        let [a, b, c, d] = &mut planar[..] else {
+            unreachable!()
+        };
+        bank.process_planar(&mut [&mut a[..], &mut b[..], &mut c[..], &mut d[..]]);
+        scalar
+            .iter_mut()
+            .zip(planar.iter())
+            .enumerate()
+            .for_each(|(c, (f, out))| {
+                f.set_cutoff_smoothing(32);
+                f.set_cutoff(3000.0);
+                let mut expected = channel(c);
+                f.process_block(&mut expected);
+                assert_eq!(*out, expected);
+            });
+
+        bank.reset();
+        let mut frame = [0.0; 4];
+        bank.process_frame(&mut frame);
+        assert_eq!(frame, [0.0; 4]);
+    }
+}
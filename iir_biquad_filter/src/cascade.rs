@@ -0,0 +1,216 @@
+use sample::{MemoryFootprint, Sample};
+
+use crate::iir_biquad_filter::{
+    magnitude, phase, sections_response, tdf2_step, BiquadError, GenericIIRBiquadFilter, PolesZeros,
+};
+
+pub type BiquadCascade = GenericBiquadCascade<f32>;
+pub type BiquadCascade64 = GenericBiquadCascade<f64>;
+
+/// Any number of TDF2 sections in series, for orders past the `FilterOrder` ones and for
+/// second order sections designed elsewhere. A section is `[b0, b1, b2, a1, a2]` with `a0`
+/// divided out. Adding sections allocates, the rest doesn't.
+#[derive(Debug, Clone)]
+pub struct GenericBiquadCascade<T: Sample> {
+    sample_rate: T,
+    coefs: Vec<[T; 5]>,
+    states: Vec<[T; 2]>,
+}
+
+impl<T: Sample> GenericBiquadCascade<T> {
+    /// No sections, passes the signal through
+    pub fn new(sample_rate: T) -> Self {
+        GenericBiquadCascade {
+            sample_rate,
+            coefs: Vec::new(),
+            states: Vec::new(),
+        }
+    }
+
+    /// Rows of `[b0, b1, b2, a0, a1, a2]`, the SOS layout scipy and MATLAB export
+    pub fn from_sos(sos: &[[T; 6]], sample_rate: T) -> Result<Self, BiquadError> {
+        let mut cascade = Self::new(sample_rate);
+        for [b0, b1, b2, a0, a1, a2] in sos.iter().copied() {
+            let section = [b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0];
+            if !section.iter().all(|c| c.to_f64().is_finite()) {
+                return Err(BiquadError::NonFiniteCoefficients);
+            }
+            cascade.push_section(section);
+        }
+        Ok(cascade)
+    }
+
+    /// The running sections of `filter`, without its state
+    pub fn from_filter(filter: &GenericIIRBiquadFilter<T>) -> Self {
+        let mut cascade = Self::new(filter.get_sample_rate());
+        filter.coefs()[..filter.num_sections()]
+            .iter()
+            .for_each(|c| cascade.push_section(*c));
+        cascade
+    }
+
+    /// Appends a section that starts from silence
+    pub fn push_section(&mut self, coefs: [T; 5]) {
+        self.coefs.push(coefs);
+        self.states.push([T::ZERO; 2]);
+    }
+
+    /// Appends the running sections of `filter`, to chain a lowpass and a highpass into one
+    pub fn extend_from_filter(&mut self, filter: &GenericIIRBiquadFilter<T>) {
+        filter.coefs()[..filter.num_sections()]
+            .iter()
+            .for_each(|c| self.push_section(*c));
+    }
+
+    pub fn num_sections(&self) -> usize {
+        self.coefs.len()
+    }
+
+    pub fn sections(&self) -> &[[T; 5]] {
+        &self.coefs
+    }
+
+    pub fn section(&self, index: usize) -> [T; 5] {
+        self.coefs[index]
+    }
+
+    /// Replaces the coefficients of one section, its state is kept
+    pub fn set_section(&mut self, index: usize, coefs: [T; 5]) {
+        self.coefs[index] = coefs;
+    }
+
+    pub fn get_sample_rate(&self) -> T {
+        self.sample_rate
+    }
+
+    pub fn reset(&mut self) {
+        self.states.iter_mut().for_each(|s| *s = [T::ZERO; 2]);
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, sample: &mut T) {
+        *sample = self
+            .coefs
+            .iter()
+            .zip(self.states.iter_mut())
+            .fold(*sample, |x, (c, state)| tdf2_step(c, state, x));
+    }
+
+    pub fn process_block(&mut self, input_signal: &mut [T]) {
+        input_signal.iter_mut().for_each(|s| self.process_sample(s));
+    }
+
+    /// `process_block` that leaves `input` alone
+    pub fn process_block_to(&mut self, input: &[T], output: &mut [T]) {
+        assert_eq!(input.len(), output.len());
+        output.iter_mut().zip(input.iter()).for_each(|(y, x)| {
+            *y = *x;
+            self.process_sample(y);
+        });
+    }
+
+    /// Combined response of every section at `freq` Hz as (re, im)
+    pub fn response_at(&self, freq: T) -> (T, T) {
+        sections_response(&self.coefs, freq, self.sample_rate)
+    }
+
+    pub fn magnitude_at(&self, freq: T) -> T {
+        magnitude(self.response_at(freq))
+    }
+
+    pub fn phase_at(&self, freq: T) -> T {
+        phase(self.response_at(freq))
+    }
+
+    pub fn response(&self, freqs: &[T]) -> Vec<(T, T)> {
+        freqs.iter().map(|f| self.response_at(*f)).collect()
+    }
+
+    pub fn poles_zeros(&self) -> PolesZeros<T> {
+        PolesZeros::of_sections(&self.coefs)
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.coefs.iter().flatten().all(|c| c.to_f64().is_finite())
+            && self.poles_zeros().stability_margin() > T::ZERO
+    }
+}
+
+impl<T: Sample> MemoryFootprint for GenericBiquadCascade<T> {
+    fn memory_footprint(&self) -> usize {
+        self.coefs.capacity() * std::mem::size_of::<[T; 5]>()
+            + self.states.capacity() * std::mem::size_of::<[T; 2]>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iir_biquad_filter::{FilterOrder, FilterType, IIRBiquadFilter64};
+
+    #[test]
+    fn matches_the_filter_it_came_from() {
+        let mut f = IIRBiquadFilter64::new(FilterType::Highpass);
+        f.init(&48000.0, &300.0, FilterOrder::Fourth);
+        let mut cascade = BiquadCascade64::from_filter(&f);
+        assert_eq!(cascade.num_sections(), 4);
+
+        let input: Vec<f64> = (0..256).map(|n| (n as f64 * 0.37).sin()).collect();
+        let (mut a, mut b) = (input.clone(), input.clone());
+        f.process_block(&mut a);
+        cascade.process_block(&mut b);
+        assert_eq!(a, b);
+        assert_eq!(cascade.response_at(300.0), f.response_at(300.0));
+        assert_eq!(cascade.poles_zeros(), f.poles_zeros());
+
+        // 16th order, past what FilterOrder goes to
+        cascade.extend_from_filter(&f);
+        assert_eq!(cascade.num_sections(), 8);
+        let mag = cascade.magnitude_at(300.0);
+        assert!((mag - f.magnitude_at(300.0).powi(2)).abs() < 1e-12);
+        assert!(cascade.is_stable());
+        assert_eq!(
+            cascade.memory_footprint(),
+            cascade.coefs.capacity() * 40 + cascade.states.capacity() * 16
+        );
+    }
+
+    #[test]
+    fn sos_import() {
+        let mut f = IIRBiquadFilter64::new(FilterType::Lowpass);
+        f.init(&48000.0, &2000.0, FilterOrder::Third);
+        // scaled by a0 = 2 the way an unnormalized export would come in
+        let sos: Vec<[f64; 6]> = f.coefs()[..f.num_sections()]
+            .iter()
+            .map(|[b0, b1, b2, a1, a2]| [2. * b0, 2. * b1, 2. * b2, 2., 2. * a1, 2. * a2])
+            .collect();
+        let mut cascade = BiquadCascade64::from_sos(&sos, 48000.0).unwrap();
+        assert_eq!(cascade.sections(), &f.coefs()[..3]);
+
+        let input: Vec<f64> = (0..128).map(|n| (n as f64 * 0.9).cos()).collect();
+        let mut out = vec![0.0; input.len()];
+        let mut expected = input.clone();
+        cascade.process_block_to(&input, &mut out);
+        f.process_block(&mut expected);
+        assert_eq!(out, expected);
+        assert!((cascade.phase_at(2000.0) - f.phase_at(2000.0)).abs() < 1e-12);
+
+        // the state survives a coefficient swap, a reset clears it
+        cascade.set_section(0, [1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(cascade.section(0), [1.0, 0.0, 0.0, 0.0, 0.0]);
+        cascade.reset();
+        let mut sample = 0.0;
+        cascade.process_sample(&mut sample);
+        assert_eq!(sample, 0.0);
+
+        assert_eq!(
+            BiquadCascade64::from_sos(&[[1.0, 0.0, 0.0, 0.0, 0.5, 0.0]], 48000.0).err(),
+            Some(BiquadError::NonFiniteCoefficients)
+        );
+        let mut empty = BiquadCascade::new(48000.0);
+        let mut sample = 0.75;
+        empty.process_sample(&mut sample);
+        assert_eq!(sample, 0.75);
+        assert!(empty.is_stable());
+    }
+}
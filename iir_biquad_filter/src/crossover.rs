@@ -0,0 +1,171 @@
+use sample::{MemoryFootprint, Sample};
+
+use crate::iir_biquad_filter::{FilterOrder, FilterType, GenericIIRBiquadFilter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CrossoverOrder {
+    /// 12 dB/oct, the high band comes out inverted so the bands still sum flat
+    LR2,
+    /// 24 dB/oct
+    LR4,
+}
+
+pub type LinkwitzRileyCrossover = GenericLinkwitzRileyCrossover<f32>;
+pub type LinkwitzRileyCrossover64 = GenericLinkwitzRileyCrossover<f64>;
+
+/// Splits a signal into a low and a high band that add back up to an allpass of the input,
+/// both bands are 6 dB down at the crossover
+#[derive(Debug)]
+pub struct GenericLinkwitzRileyCrossover<T: Sample> {
+    lowpass: GenericIIRBiquadFilter<T>,
+    highpass: GenericIIRBiquadFilter<T>,
+    order: CrossoverOrder,
+    sample_rate: T,
+}
+
+impl<T: Sample> GenericLinkwitzRileyCrossover<T> {
+    pub fn new(order: CrossoverOrder, sample_rate: T, crossover_freq: T) -> Self {
+        let mut crossover = GenericLinkwitzRileyCrossover {
+            lowpass: GenericIIRBiquadFilter::new(FilterType::Lowpass),
+            highpass: GenericIIRBiquadFilter::new(FilterType::Highpass),
+            order,
+            sample_rate,
+        };
+        crossover.init(sample_rate, crossover_freq);
+        crossover
+    }
+
+    fn init(&mut self, sample_rate: T, crossover_freq: T) {
+        self.sample_rate = sample_rate;
+        // LR2 is a Q 0.5 biquad, LR4 is two Butterworth biquads
+        let (filter_order, q) = match self.order {
+            CrossoverOrder::LR2 => (FilterOrder::First, 0.5),
+            CrossoverOrder::LR4 => (FilterOrder::Second, std::f32::consts::FRAC_1_SQRT_2),
+        };
+        [&mut self.lowpass, &mut self.highpass]
+            .into_iter()
+            .for_each(|f| {
+                f.set_q(T::from_f32(q));
+                f.init(&sample_rate, &crossover_freq, filter_order);
+            });
+    }
+
+    pub fn set_crossover(&mut self, crossover_freq: T) {
+        self.lowpass.set_cutoff(crossover_freq);
+        self.highpass.set_cutoff(crossover_freq);
+    }
+
+    pub fn get_crossover(&self) -> T {
+        self.lowpass.get_current_cutoff()
+    }
+
+    /// Keeps the state like the biquad does
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.lowpass.set_sample_rate(sample_rate);
+        self.highpass.set_sample_rate(sample_rate);
+    }
+
+    pub fn get_sample_rate(&self) -> T {
+        self.sample_rate
+    }
+
+    pub fn set_order(&mut self, order: CrossoverOrder) {
+        self.order = order;
+        self.init(self.sample_rate, self.get_crossover());
+    }
+
+    pub fn get_order(&self) -> CrossoverOrder {
+        self.order
+    }
+
+    pub fn reset(&mut self) {
+        self.lowpass.reset();
+        self.highpass.reset();
+    }
+
+    /// Gives the low and the high band
+    #[inline]
+    pub fn process_sample(&mut self, sample: T) -> (T, T) {
+        let mut low = sample;
+        let mut high = sample;
+        self.lowpass.process_sample(&mut low);
+        self.highpass.process_sample(&mut high);
+        match self.order {
+            CrossoverOrder::LR2 => (low, T::ZERO - high),
+            CrossoverOrder::LR4 => (low, high),
+        }
+    }
+
+    /// Splits `input` into `low` and `high`, all three have to be the same length
+    pub fn process_block(&mut self, input: &[T], low: &mut [T], high: &mut [T]) {
+        assert!(input.len() == low.len() && input.len() == high.len());
+        input
+            .iter()
+            .zip(low.iter_mut().zip(high.iter_mut()))
+            .for_each(|(x, (l, h))| (*l, *h) = self.process_sample(*x));
+    }
+}
+
+impl<T: Sample> MemoryFootprint for GenericLinkwitzRileyCrossover<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn band_gains(crossover: &mut LinkwitzRileyCrossover64, freq: f64) -> (f64, f64, f64) {
+        let input: Vec<f64> = (0..48000)
+            .map(|n| (2. * std::f64::consts::PI * freq * n as f64 / 48000.).sin())
+            .collect();
+        let mut low = vec![0.0; input.len()];
+        let mut high = vec![0.0; input.len()];
+        crossover.reset();
+        crossover.process_block(&input, &mut low, &mut high);
+        // from the power, a peak search misses the top of a phase shifted sine
+        let peak = |s: &[f64]| (2. * s[24000..].iter().map(|v| v * v).sum::<f64>() / 24000.).sqrt();
+        let sum: Vec<f64> = low.iter().zip(high.iter()).map(|(l, h)| l + h).collect();
+        (peak(&low), peak(&high), peak(&sum))
+    }
+
+    #[test]
+    fn bands_sum_flat() {
+        [CrossoverOrder::LR2, CrossoverOrder::LR4]
+            .iter()
+            .for_each(|order| {
+                let mut crossover = LinkwitzRileyCrossover64::new(*order, 48000.0, 1000.0);
+                assert_eq!(crossover.get_order(), *order);
+                [50., 300., 1000., 3000., 12000.].iter().for_each(|freq| {
+                    let (_, _, sum) = band_gains(&mut crossover, *freq);
+                    assert!((sum - 1.).abs() < 1e-3, "{:?} {} {}", order, freq, sum);
+                });
+
+                let (low, high, _) = band_gains(&mut crossover, 1000.);
+                assert!((low - 0.5).abs() < 1e-3 && (high - 0.5).abs() < 1e-3);
+            });
+    }
+
+    #[test]
+    fn lr4_is_steeper() {
+        let mut lr2 = LinkwitzRileyCrossover64::new(CrossoverOrder::LR2, 48000.0, 1000.0);
+        let mut lr4 = LinkwitzRileyCrossover64::new(CrossoverOrder::LR2, 48000.0, 1000.0);
+        lr4.set_order(CrossoverOrder::LR4);
+        let (_, lr2_high, _) = band_gains(&mut lr2, 250.);
+        let (_, lr4_high, _) = band_gains(&mut lr4, 250.);
+        // two octaves down, 24 and 48 dB
+        assert!(
+            (lr2_high - 10_f64.powf(-24. / 20.)).abs() < 0.01,
+            "{}",
+            lr2_high
+        );
+        assert!(lr4_high < 10_f64.powf(-46. / 20.), "{}", lr4_high);
+
+        lr4.set_sample_rate(96000.);
+        lr4.set_sample_rate(48000.);
+        assert_eq!(lr4.get_sample_rate(), 48000.);
+        lr4.set_crossover(2000.);
+        assert_eq!(lr4.get_crossover(), 2000.);
+        let (low, high, _) = band_gains(&mut lr4, 2000.);
+        assert!((low - 0.5).abs() < 1e-3 && (high - 0.5).abs() < 1e-3);
+    }
+}
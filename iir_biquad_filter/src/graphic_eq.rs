@@ -0,0 +1,331 @@
+use sample::{MemoryFootprint, Sample};
+
+use crate::iir_biquad_filter::{Discretization, FilterOrder, FilterType, GenericIIRBiquadFilter};
+
+/// ISO 266 centers
+const OCTAVE_CENTERS: [f32; 10] = [
+    31.5, 63., 125., 250., 500., 1000., 2000., 4000., 8000., 16000.,
+];
+const THIRD_OCTAVE_CENTERS: [f32; 31] = [
+    20., 25., 31.5, 40., 50., 63., 80., 100., 125., 160., 200., 250., 315., 400., 500., 630., 800.,
+    1000., 1250., 1600., 2000., 2500., 3150., 4000., 5000., 6300., 8000., 10000., 12500., 16000.,
+    20000.,
+];
+
+/// Bands centered at or above this fraction of the sample rate are skipped
+const MAX_CENTER_RATIO: f32 = 0.45;
+
+/// Crossfade after a band gain change, in ms
+const BAND_FADE_MS: f32 = 5.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GraphicEqBands {
+    /// 10 bands from 31.5 Hz to 16 kHz
+    Octave,
+    /// 31 bands from 20 Hz to 20 kHz
+    ThirdOctave,
+}
+
+impl GraphicEqBands {
+    pub fn center_freqs(&self) -> &'static [f32] {
+        match self {
+            GraphicEqBands::Octave => &OCTAVE_CENTERS,
+            GraphicEqBands::ThirdOctave => &THIRD_OCTAVE_CENTERS,
+        }
+    }
+
+    /// Q of a peak whose bandwidth is the band spacing
+    fn q(&self) -> f32 {
+        let octaves: f32 = match self {
+            GraphicEqBands::Octave => 1.,
+            GraphicEqBands::ThirdOctave => 1. / 3.,
+        };
+        let ratio = octaves.exp2();
+        ratio.sqrt() / (ratio - 1.)
+    }
+}
+
+pub type GraphicEq = GenericGraphicEq<f32>;
+pub type GraphicEq64 = GenericGraphicEq<f64>;
+
+/// One peak section per band, in series. The bands use the matched design so the top ones
+/// keep their shape at 44.1 kHz. Neighbouring boosts add up past the band gains, nothing
+/// corrects for that. Bands at 0 dB are skipped once their gain fade is over, and bands
+/// centered at or above 0.45 of the sample rate are always skipped.
+#[derive(Debug)]
+pub struct GenericGraphicEq<T: Sample> {
+    bands: GraphicEqBands,
+    sample_rate: T,
+    gains_db: Vec<f32>,
+    filters: Vec<GenericIIRBiquadFilter<T>>,
+}
+
+impl<T: Sample> GenericGraphicEq<T> {
+    pub fn new(bands: GraphicEqBands, sample_rate: T) -> Self {
+        let q = bands.q();
+        let filters = bands
+            .center_freqs()
+            .iter()
+            .map(|fc| {
+                let mut f = GenericIIRBiquadFilter::new(FilterType::Peak { gain_db: 0., q });
+                f.set_discretization(Discretization::Matched);
+                f.set_type_fade(Self::fade_samples(sample_rate));
+                f.init(
+                    &sample_rate,
+                    &Self::clamp_center(*fc, sample_rate),
+                    FilterOrder::First,
+                );
+                f
+            })
+            .collect();
+        GenericGraphicEq {
+            bands,
+            sample_rate,
+            gains_db: vec![0.; bands.center_freqs().len()],
+            filters,
+        }
+    }
+
+    /// A band past `MAX_CENTER_RATIO` is never run, this only keeps its coefficients finite
+    fn clamp_center(fc: f32, sample_rate: T) -> T {
+        T::from_f32(fc.min(MAX_CENTER_RATIO * sample_rate.to_f32()))
+    }
+
+    fn fade_samples(sample_rate: T) -> usize {
+        (BAND_FADE_MS * 1e-3 * sample_rate.to_f32()) as usize
+    }
+
+    pub fn get_bands(&self) -> GraphicEqBands {
+        self.bands
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Crossfades from the old gain. A band coming back from 0 dB starts from silence
+    /// rather than from wherever it was left.
+    pub fn set_band_gain_db(&mut self, band: usize, gain_db: f32) {
+        if !self.is_active(band) {
+            self.filters[band].reset();
+        }
+        self.gains_db[band] = gain_db;
+        let q = self.bands.q();
+        self.filters[band].set_filter_type(FilterType::Peak { gain_db, q });
+    }
+
+    pub fn get_band_gain_db(&self, band: usize) -> f32 {
+        self.gains_db[band]
+    }
+
+    /// One gain per band, from the lowest band up
+    pub fn set_gains_db(&mut self, gains_db: &[f32]) {
+        assert_eq!(gains_db.len(), self.num_bands());
+        gains_db
+            .iter()
+            .enumerate()
+            .for_each(|(band, gain_db)| self.set_band_gain_db(band, *gain_db));
+    }
+
+    pub fn gains_db(&self) -> &[f32] {
+        &self.gains_db
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        let centers = self.bands.center_freqs();
+        self.filters.iter_mut().zip(centers).for_each(|(f, fc)| {
+            f.set_type_fade(Self::fade_samples(sample_rate));
+            f.init(
+                &sample_rate,
+                &Self::clamp_center(*fc, sample_rate),
+                FilterOrder::First,
+            )
+        });
+    }
+
+    pub fn get_sample_rate(&self) -> T {
+        self.sample_rate
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(|f| f.reset());
+    }
+
+    /// A band going to 0 dB keeps running until its fade is over
+    fn is_active(&self, band: usize) -> bool {
+        (self.gains_db[band] != 0. || self.filters[band].is_fading())
+            && self.bands.center_freqs()[band] < MAX_CENTER_RATIO * self.sample_rate.to_f32()
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, sample: &mut T) {
+        for band in 0..self.num_bands() {
+            if self.is_active(band) {
+                self.filters[band].process_sample(sample);
+            }
+        }
+    }
+
+    /// Band by band over the whole block
+    pub fn process_block(&mut self, input_signal: &mut [T]) {
+        for band in 0..self.num_bands() {
+            if self.is_active(band) {
+                self.filters[band].process_block(input_signal);
+            }
+        }
+    }
+
+    /// Response of every active band at `freq` Hz as (re, im)
+    pub fn response_at(&self, freq: T) -> (T, T) {
+        (0..self.num_bands())
+            .filter(|band| self.is_active(*band))
+            .map(|band| self.filters[band].response_at(freq))
+            .fold((T::ONE, T::ZERO), |(re, im), (r, i)| {
+                (re * r - im * i, re * i + im * r)
+            })
+    }
+
+    pub fn magnitude_at(&self, freq: T) -> T {
+        let (re, im) = self.response_at(freq);
+        (re * re + im * im).sqrt()
+    }
+
+    pub fn response(&self, freqs: &[T]) -> Vec<(T, T)> {
+        freqs.iter().map(|f| self.response_at(*f)).collect()
+    }
+}
+
+impl<T: Sample> MemoryFootprint for GenericGraphicEq<T> {
+    fn memory_footprint(&self) -> usize {
+        self.gains_db.capacity() * std::mem::size_of::<f32>()
+            + self.filters.capacity() * std::mem::size_of::<GenericIIRBiquadFilter<T>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine_gain(eq: &mut GraphicEq64, freq: f64) -> f64 {
+        let mut block: Vec<f64> = (0..48000)
+            .map(|n| (2. * std::f64::consts::PI * freq * n as f64 / 48000.).sin())
+            .collect();
+        eq.reset();
+        eq.process_block(&mut block);
+        (2. * block[24000..].iter().map(|v| v * v).sum::<f64>() / 24000.).sqrt()
+    }
+
+    #[test]
+    fn band_gains_at_centers() {
+        [GraphicEqBands::Octave, GraphicEqBands::ThirdOctave]
+            .iter()
+            .for_each(|bands| {
+                let mut eq = GraphicEq64::new(*bands, 48000.0);
+                assert_eq!(eq.num_bands(), bands.center_freqs().len());
+                assert_eq!(eq.magnitude_at(1000.), 1.);
+                let band = bands
+                    .center_freqs()
+                    .iter()
+                    .position(|f| *f == 1000.)
+                    .unwrap();
+                eq.set_band_gain_db(band, 9.);
+                assert_eq!(eq.get_band_gain_db(band), 9.);
+                let expected = 10_f64.powf(9. / 20.);
+                assert!((eq.magnitude_at(1000.) - expected).abs() < 1e-6);
+                assert!((sine_gain(&mut eq, 1000.) - expected).abs() < 1e-3);
+                // an octave away is three third octave bands over and close to flat
+                if *bands == GraphicEqBands::ThirdOctave {
+                    assert!(eq.magnitude_at(2000.) < 1.2, "{}", eq.magnitude_at(2000.));
+                }
+            });
+    }
+
+    #[test]
+    fn combined_response() {
+        let mut eq = GraphicEq64::new(GraphicEqBands::Octave, 44100.0);
+        let gains = [3., -6., 0., 2., 0., 0., -4., 0., 5., 8.];
+        eq.set_gains_db(&gains);
+        assert_eq!(eq.gains_db(), &gains);
+        // the product of the bands on their own
+        [40., 400., 3000., 15000.].iter().for_each(|freq| {
+            let expected = gains.iter().enumerate().fold(1., |m, (band, g)| {
+                let mut single = GraphicEq64::new(GraphicEqBands::Octave, 44100.0);
+                single.set_band_gain_db(band, *g);
+                m * single.magnitude_at(*freq)
+            });
+            assert!((eq.magnitude_at(*freq) - expected).abs() < 1e-9);
+        });
+        // the matched top band reaches its gain at 16 kHz
+        let mut top = GraphicEq64::new(GraphicEqBands::Octave, 44100.0);
+        top.set_band_gain_db(9, 8.);
+        assert!((top.magnitude_at(16000.) - 10_f64.powf(8. / 20.)).abs() < 1e-6);
+
+        // 20 kHz is past Nyquist at 32 kHz and gets skipped
+        let mut eq = GraphicEq64::new(GraphicEqBands::ThirdOctave, 48000.0);
+        eq.set_band_gain_db(30, 6.);
+        eq.set_sample_rate(32000.);
+        assert_eq!(eq.get_sample_rate(), 32000.);
+        assert_eq!(eq.magnitude_at(15000.), 1.);
+        let mut block = [0.5, -0.5];
+        eq.process_block(&mut block);
+        assert_eq!(block, [0.5, -0.5]);
+        let mut sample = 0.25;
+        eq.process_sample(&mut sample);
+        assert_eq!(sample, 0.25);
+        assert_eq!(eq.response(&[100.]), vec![(1., 0.)]);
+
+        // 20 kHz is under Nyquist at 44.1 kHz but past 0.45 of it, so it's skipped too
+        let mut eq = GraphicEq64::new(GraphicEqBands::ThirdOctave, 44100.0);
+        eq.set_band_gain_db(30, 6.);
+        let fc = eq.get_bands().center_freqs()[30];
+        assert!(fc > MAX_CENTER_RATIO * 44100. && fc < 0.5 * 44100.);
+        assert_eq!(eq.magnitude_at(20000.), 1.);
+        let mut block = [0.5, -0.5];
+        eq.process_block(&mut block);
+        assert_eq!(block, [0.5, -0.5]);
+    }
+
+    #[test]
+    fn band_switching_is_smooth() {
+        let sine: Vec<f64> = (0..9600)
+            .map(|n| (2. * std::f64::consts::PI * 1000. * n as f64 / 48000.).sin())
+            .collect();
+        let boosted = || {
+            let mut eq = GraphicEq64::new(GraphicEqBands::Octave, 48000.0);
+            eq.set_band_gain_db(5, 12.);
+            // up to a peak of the sine
+            let mut block = sine[..4812].to_vec();
+            eq.process_block(&mut block);
+            eq
+        };
+        let mut held = boosted();
+        let mut eq = boosted();
+
+        // going to 0 dB fades out of the boost instead of jumping to the dry signal
+        eq.set_band_gain_db(5, 0.);
+        let (mut y_held, mut y) = (sine[4812], sine[4812]);
+        held.process_sample(&mut y_held);
+        eq.process_sample(&mut y);
+        assert!(y_held.abs() > 1.5, "{}", y_held);
+        assert!((y - y_held).abs() < 0.05, "{} {}", y, y_held);
+
+        // and stops running once the fade is over, with its state left behind
+        let mut block = sine[4813..].to_vec();
+        eq.process_block(&mut block);
+        let mut tail = sine[..480].to_vec();
+        eq.process_block(&mut tail);
+        assert_eq!(tail, sine[..480]);
+
+        // coming back it starts from silence like a fresh band
+        let mut fresh = GraphicEq64::new(GraphicEqBands::Octave, 48000.0);
+        fresh.set_band_gain_db(5, 12.);
+        eq.set_band_gain_db(5, 12.);
+        let mut expected = sine[..960].to_vec();
+        fresh.process_block(&mut expected);
+        let mut block = sine[..960].to_vec();
+        eq.process_block(&mut block);
+        assert_eq!(block, expected);
+    }
+}
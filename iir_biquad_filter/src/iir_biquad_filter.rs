@@ -1,251 +1,2299 @@
-use std::f32::consts::PI;
+use std::fmt;
 
-#[derive(Debug)]
+use envelope::{Env, LinearEnvelope, Smoother};
+use param_range::param_range::db_to_gain;
+use sample::{MemoryFootprint, Sample};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum FilterType {
     Lowpass,
     Highpass,
     Bandpass,
     Bandreject,
+    /// Boosts or cuts below the cutoff by the shelf gain
+    LowShelf,
+    /// Boosts or cuts above the cutoff by the shelf gain
+    HighShelf,
+    /// Bell centered on the cutoff, higher orders split the gain over the sections
+    Peak {
+        gain_db: f32,
+        q: f32,
+    },
+    /// Unity gain, the phase turns through -180 degrees per section at the cutoff
+    Allpass {
+        q: f32,
+    },
+}
+
+/// Number of cascaded biquad sections, each adds 12 dB/oct. The Q values of the sections
+/// give a 2nd, 4th, 6th or 8th order Butterworth response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum FilterOrder {
+    First,
+    Second,
+    Third,
+    Fourth,
+}
+
+pub(crate) const MAX_SECTIONS: usize = 4;
+
+/// How the lowpass and highpass cascades are designed, the other types don't change
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FilterDesign {
+    /// Maximally flat, the default Q tables
+    Butterworth,
+    /// Equiripple passband at most `ripple_db` down, steeper than Butterworth past the cutoff.
+    /// The response is `ripple_db` down at the cutoff.
+    ChebyshevI { ripple_db: f32 },
+    /// Flat passband, equiripple stopband at least `attenuation_db` down from the cutoff on.
+    /// The cutoff is where the stopband starts.
+    ChebyshevII { attenuation_db: f32 },
+}
+
+/// How the analog lowpass, highpass, bandpass and peak sections become digital ones
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Discretization {
+    /// The RBJ cookbook bilinear transform, exact at the cutoff but the response is squeezed
+    /// to zero or unity at Nyquist, badly so above about a sixth of the sample rate
+    #[default]
+    Bilinear,
+    /// Vicanek's matched design, the poles are matched with the impulse response and the
+    /// zeros picked to match the analog magnitude at DC and at the cutoff. Stays close to
+    /// the analog response up to Nyquist. The Chebyshev designs stay bilinear.
+    Matched,
+}
+
+/// Time constant of the output gain glide until `set_output_gain_smoothing_ms` changes it
+pub const DEFAULT_OUTPUT_GAIN_SMOOTHING_MS: f32 = 10.0;
+
+const W1: usize = 0;
+const W2: usize = 1;
+
+const B0: usize = 0;
+const B1: usize = 1;
+const B2: usize = 2;
+const A1: usize = 3;
+const A2: usize = 4;
+
+// coupled form parameters, poles at alpha +- j beta and the numerator taps on the state
+const ALPHA: usize = 0;
+const BETA: usize = 1;
+const C1: usize = 2;
+const C2: usize = 3;
+
+const FIRST_ORDER_Q_VALS: [f32; 1] = [0.70710677];
+const SECOND_ORDER_Q_VALS: [f32; 2] = [0.54, 1.31];
+const THIRD_ORDER_Q_VALS: [f32; 3] = [0.5176381, 0.70710677, 1.9318516];
+const FOURTH_ORDER_Q_VALS: [f32; 4] = [0.5097956, 0.6013449, 0.8999762, 2.5629154];
+
+/// Roots of the transfer function in the z plane as (re, im), complex roots come with their
+/// conjugate. Every section of a cascade is included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolesZeros<T: Sample> {
+    pub poles: Vec<(T, T)>,
+    pub zeros: Vec<(T, T)>,
+}
+
+impl<T: Sample> PolesZeros<T> {
+    /// Distance from the outermost pole to the unit circle, positive while the filter is
+    /// stable
+    pub fn stability_margin(&self) -> T {
+        let max_radius = self
+            .poles
+            .iter()
+            .map(|(re, im)| (*re * *re + *im * *im).sqrt())
+            .fold(T::ZERO, |m, r| if r > m { r } else { m });
+        T::ONE - max_radius
+    }
+}
+
+/// Roots of a z^2 + b z + c, a degree is dropped for every leading zero coefficient
+fn quadratic_roots<T: Sample>(a: T, b: T, c: T, roots: &mut Vec<(T, T)>) {
+    let two = T::from_f32(2.);
+    if a == T::ZERO {
+        if b != T::ZERO {
+            roots.push((-c / b, T::ZERO));
+        }
+        return;
+    }
+
+    let discriminant = b * b - T::from_f32(4.) * a * c;
+    let center = -b / (two * a);
+    if discriminant >= T::ZERO {
+        let offset = discriminant.sqrt() / (two * a);
+        roots.push((center + offset, T::ZERO));
+        roots.push((center - offset, T::ZERO));
+    } else {
+        let offset = (-discriminant).sqrt() / (two * a);
+        roots.push((center, offset));
+        roots.push((center, -offset));
+    }
+}
+
+/// Why `validate` rejected the current settings
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum BiquadError {
+    InvalidSampleRate(f64),
+    CutoffOutOfRange {
+        cutoff_hz: f64,
+        nyquist_hz: f64,
+    },
+    /// A Q, gain or slope gave an infinite or NaN coefficient
+    NonFiniteCoefficients,
+    /// A pole is on or outside the unit circle
+    Unstable,
+}
+
+impl fmt::Display for BiquadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BiquadError::InvalidSampleRate(sr) => write!(f, "invalid sample rate {}", sr),
+            BiquadError::CutoffOutOfRange {
+                cutoff_hz,
+                nyquist_hz,
+            } => write!(
+                f,
+                "cutoff {} Hz is not between 0 and Nyquist at {} Hz",
+                cutoff_hz, nyquist_hz
+            ),
+            BiquadError::NonFiniteCoefficients => write!(f, "non-finite filter coefficients"),
+            BiquadError::Unstable => write!(f, "filter is unstable"),
+        }
+    }
+}
+
+impl std::error::Error for BiquadError {}
+
+/// How the sections are realized, they all give the same response
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Topology {
+    /// Keeps the input and output history, so a coefficient change can't leave the state
+    /// somewhere the new coefficients would blow up. Best under heavy modulation.
+    DirectForm1,
+    /// Two state values a section and the least rounding at rest
+    #[default]
+    TransposedDirectForm2,
+    /// Gold-Rader coupled form, the state rotates by the pole angle. The rotation is worked
+    /// out in f64 from the design rather than from the rounded `a1`, `a2`, so it keeps its
+    /// precision with poles close to 1, for low cutoffs. Sections with real poles run as TDF2.
+    Coupled,
+}
+
+/// What a preset needs to rebuild a filter, the signal state and the smoothing lengths
+/// aren't included
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BiquadSettings<T: Sample> {
+    pub filter_type: FilterType,
+    pub order: FilterOrder,
+    pub design: FilterDesign,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub discretization: Discretization,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub topology: Topology,
+    pub sample_rate: T,
+    pub cutoff_freq: T,
+    pub q: Option<T>,
+    pub bandwidth_octaves: Option<T>,
+    pub shelf_gain_db: T,
+    pub shelf_slope: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub output_gain_db: T,
+}
+
+/// The filter a type change fades out of, it keeps running on its own state until the fade
+/// is done
+#[derive(Debug)]
+struct TypeFade<T: Sample> {
+    coefs: [[T; 5]; MAX_SECTIONS],
+    coupled: [[T; 4]; MAX_SECTIONS],
+    states: [[T; 2]; MAX_SECTIONS],
+    outputs: [[T; 2]; MAX_SECTIONS],
+    sections: usize,
+    fade_in: LinearEnvelope,
+}
+
+impl<T: Sample> PolesZeros<T> {
+    pub(crate) fn of_sections(coefs: &[[T; 5]]) -> Self {
+        let mut result = PolesZeros {
+            poles: Vec::with_capacity(2 * coefs.len()),
+            zeros: Vec::with_capacity(2 * coefs.len()),
+        };
+        coefs.iter().for_each(|c| {
+            quadratic_roots(T::ONE, c[A1], c[A2], &mut result.poles);
+            quadratic_roots(c[B0], c[B1], c[B2], &mut result.zeros);
+        });
+        result
+    }
+}
+
+/// Product of the section responses at `freq` Hz as (re, im), worked out in f64
+pub(crate) fn sections_response<T: Sample>(coefs: &[[T; 5]], freq: T, sample_rate: T) -> (T, T) {
+    let w = 2. * std::f64::consts::PI * freq.to_f64() / sample_rate.to_f64();
+    let (c1, s1, c2, s2) = (w.cos(), w.sin(), (2. * w).cos(), (2. * w).sin());
+    // z^-1 = e^-jw
+    let (re, im) = coefs.iter().fold((1., 0.), |(re, im): (f64, f64), c| {
+        let c = c.map(|v| v.to_f64());
+        let (num_re, num_im) = (c[B0] + c[B1] * c1 + c[B2] * c2, -(c[B1] * s1 + c[B2] * s2));
+        let (den_re, den_im) = (1. + c[A1] * c1 + c[A2] * c2, -(c[A1] * s1 + c[A2] * s2));
+        let den = den_re * den_re + den_im * den_im;
+        let (h_re, h_im) = (
+            (num_re * den_re + num_im * den_im) / den,
+            (num_im * den_re - num_re * den_im) / den,
+        );
+        (re * h_re - im * h_im, re * h_im + im * h_re)
+    });
+    (T::from_f64(re), T::from_f64(im))
+}
+
+pub(crate) fn magnitude<T: Sample>((re, im): (T, T)) -> T {
+    (re * re + im * im).sqrt()
+}
+
+pub(crate) fn phase<T: Sample>((re, im): (T, T)) -> T {
+    T::from_f64(im.to_f64().atan2(re.to_f64()))
+}
+
+#[inline]
+pub(crate) fn tdf2_step<T: Sample>(c: &[T; 5], state: &mut [T; 2], x: T) -> T {
+    let y = (c[B0] * x) + state[W1];
+    state[W1] = (c[B1] * x) - (c[A1] * y) + state[W2];
+    state[W2] = (c[B2] * x) - (c[A2] * y);
+    y
+}
+
+impl Topology {
+    #[inline]
+    fn run<T: Sample>(
+        self,
+        coefs: &[[T; 5]; MAX_SECTIONS],
+        coupled: &[[T; 4]; MAX_SECTIONS],
+        sections: usize,
+        states: &mut [[T; 2]; MAX_SECTIONS],
+        outputs: &mut [[T; 2]; MAX_SECTIONS],
+        x: T,
+    ) -> T {
+        let sections = coefs
+            .iter()
+            .zip(states.iter_mut().zip(outputs.iter_mut()))
+            .take(sections);
+        match self {
+            Topology::TransposedDirectForm2 => {
+                sections.fold(x, |x, (c, (state, _))| tdf2_step(c, state, x))
+            }
+            Topology::DirectForm1 => sections.fold(x, |x, (c, (inputs, outputs))| {
+                let y = (c[B0] * x) + (c[B1] * inputs[0]) + (c[B2] * inputs[1])
+                    - (c[A1] * outputs[0])
+                    - (c[A2] * outputs[1]);
+                *inputs = [x, inputs[0]];
+                *outputs = [y, outputs[0]];
+                y
+            }),
+            Topology::Coupled => sections
+                .zip(coupled.iter())
+                .fold(x, |x, ((c, (state, _)), p)| {
+                    if p[BETA] == T::ZERO {
+                        return tdf2_step(c, state, x);
+                    }
+                    let y = (c[B0] * x) + (p[C1] * state[0]) + (p[C2] * state[1]);
+                    *state = [
+                        (p[ALPHA] * state[0]) - (p[BETA] * state[1]) + x,
+                        (p[BETA] * state[0]) + (p[ALPHA] * state[1]),
+                    ];
+                    y
+                }),
+        }
+    }
+}
+
+pub type IIRBiquadFilter = GenericIIRBiquadFilter<f32>;
+pub type IIRBiquadFilter64 = GenericIIRBiquadFilter<f64>;
+
+#[derive(Debug)]
+pub struct GenericIIRBiquadFilter<T: Sample> {
+    coefs: [[T; 5]; MAX_SECTIONS],
+    /// Only kept up to date while the topology is `Coupled`
+    coupled: [[T; 4]; MAX_SECTIONS],
+    filter_type: FilterType,
+    states: [[T; 2]; MAX_SECTIONS],
+    /// DF1 keeps the inputs in `states` and the outputs here
+    outputs: [[T; 2]; MAX_SECTIONS],
+    topology: Topology,
+    order: FilterOrder,
+    cutoff_freq: T,
+    sample_rate: T,
+    shelf_gain_db: T,
+    shelf_slope: T,
+    q: Option<T>,
+    bandwidth_octaves: Option<T>,
+    design: FilterDesign,
+    discretization: Discretization,
+    ramp_samples: usize,
+    ramp_remaining: usize,
+    ramp_step: [[T; 5]; MAX_SECTIONS],
+    ramp_target: [[T; 5]; MAX_SECTIONS],
+    coupled_step: [[T; 4]; MAX_SECTIONS],
+    coupled_target: [[T; 4]; MAX_SECTIONS],
+    type_fade_samples: usize,
+    type_fade: Option<TypeFade<T>>,
+    output_gain_db: T,
+    output_gain: Smoother,
+}
+
+impl<T: Sample> Default for GenericIIRBiquadFilter<T> {
+    fn default() -> Self {
+        GenericIIRBiquadFilter {
+            coefs: [[T::ZERO; 5]; MAX_SECTIONS],
+            coupled: [[T::ZERO; 4]; MAX_SECTIONS],
+            filter_type: FilterType::Lowpass,
+            states: [[T::ZERO; 2]; MAX_SECTIONS],
+            outputs: [[T::ZERO; 2]; MAX_SECTIONS],
+            topology: Topology::default(),
+            order: FilterOrder::First,
+            cutoff_freq: T::from_f32(1000.0),
+            sample_rate: T::from_f32(44100.0),
+            shelf_gain_db: T::ZERO,
+            shelf_slope: T::ONE,
+            q: None,
+            bandwidth_octaves: None,
+            design: FilterDesign::Butterworth,
+            discretization: Discretization::default(),
+            ramp_samples: 0,
+            ramp_remaining: 0,
+            ramp_step: [[T::ZERO; 5]; MAX_SECTIONS],
+            ramp_target: [[T::ZERO; 5]; MAX_SECTIONS],
+            coupled_step: [[T::ZERO; 4]; MAX_SECTIONS],
+            coupled_target: [[T::ZERO; 4]; MAX_SECTIONS],
+            type_fade_samples: 0,
+            type_fade: None,
+            output_gain_db: T::ZERO,
+            output_gain: Smoother::new(1.0, DEFAULT_OUTPUT_GAIN_SMOOTHING_MS, 44100.0),
+        }
+    }
+}
+
+impl<T: Sample> GenericIIRBiquadFilter<T> {
+    pub fn new(ft: FilterType) -> Self {
+        let mut new_biquad = GenericIIRBiquadFilter::default();
+        new_biquad.set_filter_type(ft);
+        new_biquad
+    }
+
+    /// `new` and `init` that fail instead of giving a filter that outputs NaNs
+    pub fn try_new(
+        ft: FilterType,
+        sample_rate: T,
+        cutoff_freq: T,
+        order: FilterOrder,
+    ) -> Result<Self, BiquadError> {
+        let mut filter = Self::new(ft);
+        filter.init(&sample_rate, &cutoff_freq, order);
+        filter.validate()?;
+        Ok(filter)
+    }
+
+    /// Checks the sample rate, that the cutoff is below Nyquist and that the coefficients
+    /// are finite and stable
+    pub fn validate(&self) -> Result<(), BiquadError> {
+        let (sample_rate, cutoff_hz) = (self.sample_rate.to_f64(), self.cutoff_freq.to_f64());
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            return Err(BiquadError::InvalidSampleRate(sample_rate));
+        }
+        let nyquist_hz = sample_rate / 2.0;
+        if !(cutoff_hz > 0.0 && cutoff_hz < nyquist_hz) {
+            return Err(BiquadError::CutoffOutOfRange {
+                cutoff_hz,
+                nyquist_hz,
+            });
+        }
+        if !self.coefs_finite() {
+            return Err(BiquadError::NonFiniteCoefficients);
+        }
+        if !self.is_stable() {
+            return Err(BiquadError::Unstable);
+        }
+        Ok(())
+    }
+
+    /// Every pole strictly inside the unit circle, false for NaN coefficients too
+    pub fn is_stable(&self) -> bool {
+        self.coefs_finite() && self.poles_zeros().stability_margin() > T::ZERO
+    }
+
+    fn coefs_finite(&self) -> bool {
+        self.coefs[..self.num_sections()]
+            .iter()
+            .flatten()
+            .all(|c| c.to_f64().is_finite())
+    }
+
+    pub fn from_settings(settings: &BiquadSettings<T>) -> Self {
+        let mut filter = Self::default();
+        filter.apply_settings(settings);
+        filter
+    }
+
+    pub fn settings(&self) -> BiquadSettings<T> {
+        BiquadSettings {
+            filter_type: self.filter_type,
+            order: self.order,
+            design: self.design,
+            discretization: self.discretization,
+            topology: self.topology,
+            sample_rate: self.sample_rate,
+            cutoff_freq: self.cutoff_freq,
+            q: self.q,
+            bandwidth_octaves: self.bandwidth_octaves,
+            shelf_gain_db: self.shelf_gain_db,
+            shelf_slope: self.shelf_slope,
+            output_gain_db: self.output_gain_db,
+        }
+    }
+
+    /// Switches straight to the new settings, without a type fade, a cutoff ramp or an output
+    /// gain glide
+    pub fn apply_settings(&mut self, settings: &BiquadSettings<T>) {
+        self.filter_type = settings.filter_type;
+        self.design = settings.design;
+        self.discretization = settings.discretization;
+        self.sample_rate = settings.sample_rate;
+        self.cutoff_freq = settings.cutoff_freq;
+        self.q = settings.q;
+        self.bandwidth_octaves = settings.bandwidth_octaves;
+        self.shelf_gain_db = settings.shelf_gain_db;
+        self.shelf_slope = settings.shelf_slope;
+        self.output_gain_db = settings.output_gain_db;
+        self.output_gain
+            .reset(db_to_gain(settings.output_gain_db.to_f32()));
+        self.output_gain.set_sample_rate(self.sample_rate.to_f32());
+        self.type_fade = None;
+        if settings.topology != self.topology {
+            self.set_topology(settings.topology);
+        }
+        self.change_order(settings.order);
+        self.gen_coefficients();
+    }
+
+    pub fn init(&mut self, sample_rate: &T, cutoff_freq: &T, order: FilterOrder) {
+        self.sample_rate = *sample_rate;
+        self.output_gain.set_sample_rate(sample_rate.to_f32());
+        self.cutoff_freq = *cutoff_freq;
+        self.change_order(order);
+        self.gen_coefficients();
+    }
+
+    /// Clears the state, it means something else in every topology. The multichannel
+    /// biquad and the bank always run TDF2.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.reset();
+        self.update_coupled();
+    }
+
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Crossfades from the old type over the `set_type_fade` length. The new type starts
+    /// from silence, so going lowpass to highpass mid note doesn't click.
+    pub fn set_filter_type(&mut self, new_filter_type: FilterType) {
+        if self.type_fade_samples > 0 && new_filter_type != self.filter_type {
+            self.type_fade = Some(TypeFade {
+                coefs: self.coefs,
+                coupled: self.coupled,
+                states: self.states,
+                outputs: self.outputs,
+                sections: self.num_sections(),
+                fade_in: LinearEnvelope::fade_in(self.type_fade_samples as i32),
+            });
+            self.states = [[T::ZERO; 2]; MAX_SECTIONS];
+            self.outputs = [[T::ZERO; 2]; MAX_SECTIONS];
+        }
+        self.filter_type = new_filter_type;
+        self.gen_coefficients();
+    }
+
+    /// Length of the crossfade after a type change in samples, 0 switches straight over
+    /// with the old state. The multichannel biquad always switches straight over.
+    pub fn set_type_fade(&mut self, samples: usize) {
+        self.type_fade_samples = samples;
+    }
+
+    pub fn get_type_fade(&self) -> usize {
+        self.type_fade_samples
+    }
+
+    pub fn get_filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+
+    pub fn set_order(&mut self, new_order: FilterOrder) {
+        self.change_order(new_order);
+        self.gen_coefficients();
+    }
+
+    pub fn get_order(&self) -> FilterOrder {
+        self.order
+    }
+
+    /// Also finishes a cutoff ramp or a type fade
+    pub fn reset(&mut self) {
+        self.states = [[T::ZERO; 2]; MAX_SECTIONS];
+        self.outputs = [[T::ZERO; 2]; MAX_SECTIONS];
+        self.type_fade = None;
+        self.output_gain.force_finish();
+        if self.ramp_remaining > 0 {
+            self.coefs = self.ramp_target;
+            self.coupled = self.coupled_target;
+            self.ramp_remaining = 0;
+        }
+    }
+
+    pub fn get_current_cutoff(&self) -> T {
+        self.cutoff_freq
+    }
+
+    /// Regenerates the coefficients for the new rate, everything else is kept. The state is
+    /// kept too, which is close enough for a small change, call `reset` after a big one.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.output_gain.set_sample_rate(sample_rate.to_f32());
+        self.gen_coefficients();
+    }
+
+    pub fn get_sample_rate(&self) -> T {
+        self.sample_rate
+    }
+
+    /// Ramps to the new coefficients over the `set_cutoff_smoothing` length, the other
+    /// setters always jump
+    pub fn set_cutoff(&mut self, new_cutoff_freq: T) {
+        self.cutoff_freq = new_cutoff_freq;
+        let (from, from_coupled) = (self.coefs, self.coupled);
+        self.gen_coefficients();
+        if self.ramp_samples > 0 {
+            self.start_ramp(from, from_coupled);
+        }
+    }
+
+    /// Length of the coefficient ramp after a cutoff change in samples, 0 turns it off.
+    /// The ramp is linear in the coefficients, which keeps every section stable on the way
+    /// since the stable `a1`, `a2` pairs form a triangle.
+    pub fn set_cutoff_smoothing(&mut self, samples: usize) {
+        self.ramp_samples = samples;
+    }
+
+    pub fn get_cutoff_smoothing(&self) -> usize {
+        self.ramp_samples
+    }
+
+    /// Q of every lowpass, highpass, bandpass and notch section, in place of the Butterworth
+    /// values. Above 0.707 a first order lowpass peaks at the cutoff by about `q`.
+    pub fn set_q(&mut self, q: T) {
+        self.q = Some(q);
+        self.bandwidth_octaves = None;
+        self.gen_coefficients();
+    }
+
+    /// Goes back to the Butterworth Q values, also clears a bandwidth
+    pub fn clear_q(&mut self) {
+        self.q = None;
+        self.bandwidth_octaves = None;
+        self.gen_coefficients();
+    }
+
+    /// `None` while the Butterworth values or a bandwidth are in use
+    pub fn get_q(&self) -> Option<T> {
+        self.q
+    }
+
+    /// Width of the bandpass or notch between the -3 dB points in octaves, in place of a Q.
+    /// The Q follows the cutoff so the width stays put near Nyquist, and like `set_q` it
+    /// applies to the lowpass and highpass sections too.
+    pub fn set_bandwidth_octaves(&mut self, octaves: T) {
+        self.bandwidth_octaves = Some(octaves);
+        self.q = None;
+        self.gen_coefficients();
+    }
+
+    pub fn get_bandwidth_octaves(&self) -> Option<T> {
+        self.bandwidth_octaves
+    }
+
+    /// The Q every section uses in place of its table value, if any. RBJ's bandwidth
+    /// conversion, 1/Q = 2 sinh(ln 2 / 2 * bw * w0 / sin w0).
+    fn q_override(&self) -> Option<T> {
+        let Some(bw) = self.bandwidth_octaves else {
+            return self.q;
+        };
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (self.cutoff_freq / self.sample_rate);
+        let x: T = T::from_f64(std::f64::consts::LN_2) / two * bw * omega_0 / omega_0.sin();
+        let sinh: T = (x.exp() - (T::ZERO - x).exp()) / two;
+        Some(T::ONE / (two * sinh))
+    }
+
+    /// Only used by the lowpass and highpass, a Q set with `set_q` only applies to Butterworth
+    pub fn set_design(&mut self, design: FilterDesign) {
+        self.design = design;
+        self.gen_coefficients();
+    }
+
+    pub fn get_design(&self) -> FilterDesign {
+        self.design
+    }
+
+    pub fn set_discretization(&mut self, discretization: Discretization) {
+        self.discretization = discretization;
+        self.gen_coefficients();
+    }
+
+    pub fn get_discretization(&self) -> Discretization {
+        self.discretization
+    }
+
+    /// Applied after the sections, it glides to a new gain. The multichannel biquad and the
+    /// bank leave it out.
+    pub fn set_output_gain_db(&mut self, gain_db: T) {
+        self.output_gain_db = gain_db;
+        self.output_gain.set_target(db_to_gain(gain_db.to_f32()));
+    }
+
+    pub fn get_output_gain_db(&self) -> T {
+        self.output_gain_db
+    }
+
+    /// 0 jumps straight to a new output gain
+    pub fn set_output_gain_smoothing_ms(&mut self, time_ms: f32) {
+        self.output_gain.set_time_ms(time_ms);
+    }
+
+    /// Gain of the shelf types, higher orders split it over the sections
+    pub fn set_shelf_gain_db(&mut self, gain_db: T) {
+        self.shelf_gain_db = gain_db;
+        self.gen_coefficients();
+    }
+
+    pub fn get_shelf_gain_db(&self) -> T {
+        self.shelf_gain_db
+    }
+
+    /// RBJ shelf slope, 1.0 is the steepest without a bump at the corner
+    pub fn set_shelf_slope(&mut self, slope: T) {
+        self.shelf_slope = slope;
+        self.gen_coefficients();
+    }
+
+    pub fn get_shelf_slope(&self) -> T {
+        self.shelf_slope
+    }
+
+    /// Poles and zeros of the running sections, for pole-zero plots and stability checks
+    pub fn poles_zeros(&self) -> PolesZeros<T> {
+        PolesZeros::of_sections(&self.coefs[..self.num_sections()])
+    }
+
+    /// Complex response of the current coefficients at `freq` Hz as (re, im), mid ramp that
+    /// is the response of the coefficients the ramp has reached
+    pub fn response_at(&self, freq: T) -> (T, T) {
+        sections_response(&self.coefs[..self.num_sections()], freq, self.sample_rate)
+    }
+
+    /// Linear gain at `freq` Hz
+    pub fn magnitude_at(&self, freq: T) -> T {
+        magnitude(self.response_at(freq))
+    }
+
+    /// Phase at `freq` Hz in radians, wrapped to -pi..pi
+    pub fn phase_at(&self, freq: T) -> T {
+        phase(self.response_at(freq))
+    }
+
+    /// `response_at` for every frequency, for plotting
+    pub fn response(&self, freqs: &[T]) -> Vec<(T, T)> {
+        freqs.iter().map(|f| self.response_at(*f)).collect()
+    }
+
+    pub(crate) fn num_sections(&self) -> usize {
+        Self::q_vals(self.order).len()
+    }
+
+    fn q_vals(order: FilterOrder) -> &'static [f32] {
+        match order {
+            FilterOrder::First => &FIRST_ORDER_Q_VALS,
+            FilterOrder::Second => &SECOND_ORDER_Q_VALS,
+            FilterOrder::Third => &THIRD_ORDER_Q_VALS,
+            FilterOrder::Fourth => &FOURTH_ORDER_Q_VALS,
+        }
+    }
+
+    fn change_order(&mut self, new_order: FilterOrder) {
+        // a section that was not running has no meaningful state
+        let running = self.num_sections();
+        self.states
+            .iter_mut()
+            .zip(self.outputs.iter_mut())
+            .take(Self::q_vals(new_order).len())
+            .skip(running)
+            .for_each(|(s, o)| (*s, *o) = ([T::ZERO; 2], [T::ZERO; 2]));
+        self.order = new_order;
+    }
+
+    /// Regenerates every section from the stored type, order, cutoff and sample rate, so the
+    /// result does not depend on the order the setters were called in
+    #[inline]
+    fn gen_coefficients(&mut self) {
+        self.ramp_remaining = 0;
+        let q_vals = Self::q_vals(self.order);
+
+        let mut coefs = [[T::ZERO; 5]; MAX_SECTIONS];
+        let high = match (self.filter_type, self.design) {
+            (_, FilterDesign::Butterworth) => None,
+            (FilterType::Lowpass, _) => Some(false),
+            (FilterType::Highpass, _) => Some(true),
+            _ => None,
+        };
+        if let Some(high) = high {
+            let sections = self.num_sections();
+            coefs
+                .iter_mut()
+                .take(sections)
+                .enumerate()
+                .for_each(|(k, c)| *c = self.calculate_chebyshev_section(k, high));
+        } else {
+            let q_override = self.q_override();
+            coefs.iter_mut().zip(q_vals.iter()).for_each(|(c, q)| {
+                *c = self.calculate_sections(&q_override.unwrap_or(T::from_f32(*q)))
+            });
+        }
+        self.coefs = coefs;
+        self.update_coupled();
+    }
+
+    /// The coupled form parameters of every section, from the same design run in f64. At a
+    /// low cutoff in f32, `a2 - a1^2 / 4` cancels to nothing once `a1` and `a2` are rounded.
+    fn update_coupled(&mut self) {
+        if self.topology != Topology::Coupled {
+            return;
+        }
+        let settings = self.settings();
+        let design = GenericIIRBiquadFilter::<f64>::from_settings(&BiquadSettings {
+            filter_type: settings.filter_type,
+            order: settings.order,
+            design: settings.design,
+            discretization: settings.discretization,
+            topology: Topology::TransposedDirectForm2,
+            sample_rate: settings.sample_rate.to_f64(),
+            cutoff_freq: settings.cutoff_freq.to_f64(),
+            q: settings.q.map(|q| q.to_f64()),
+            bandwidth_octaves: settings.bandwidth_octaves.map(|bw| bw.to_f64()),
+            shelf_gain_db: settings.shelf_gain_db.to_f64(),
+            shelf_slope: settings.shelf_slope.to_f64(),
+            output_gain_db: 0.0,
+        });
+
+        let sections = self.num_sections();
+        self.coupled = [[T::ZERO; 4]; MAX_SECTIONS];
+        self.coupled
+            .iter_mut()
+            .zip(design.coefs.iter())
+            .take(sections)
+            .for_each(|(p, c)| {
+                // poles r e^(+-j theta), alpha = r cos(theta), beta = r sin(theta)
+                let alpha = -0.5 * c[A1];
+                let beta_sq = c[A2] - alpha * alpha;
+                // real poles keep beta at 0 and run as TDF2
+                if beta_sq > 0.0 {
+                    let beta = beta_sq.sqrt();
+                    let c1 = c[B1] - c[B0] * c[A1];
+                    let c2 = (c[B2] - c[B0] * c[A2] + c1 * alpha) / beta;
+                    *p = [alpha, beta, c1, c2].map(T::from_f64);
+                }
+            });
+        self.coupled_target = self.coupled;
+    }
+
+    #[inline]
+    fn calculate_sections(&self, q_value: &T) -> [T; 5] {
+        let (fc, fs) = (&self.cutoff_freq, &self.sample_rate);
+        if self.discretization == Discretization::Matched {
+            let matched = match self.filter_type {
+                FilterType::Lowpass | FilterType::Highpass | FilterType::Bandpass => {
+                    Some((q_value.to_f64(), 0.))
+                }
+                FilterType::Peak { gain_db, q } => {
+                    Some((q as f64, gain_db as f64 / self.num_sections() as f64))
+                }
+                _ => None,
+            };
+            if let Some((q, gain_db)) = matched {
+                return self.calculate_matched_section(q, gain_db);
+            }
+        }
+        match self.filter_type {
+            FilterType::Lowpass => Self::calculate_lowpass_sections(fc, fs, q_value),
+            FilterType::Highpass => Self::calculate_highpass_sections(fc, fs, q_value),
+            FilterType::Bandpass => Self::calculate_bandpass_sections(fc, fs, q_value),
+            FilterType::Bandreject => Self::calculate_bandreject_sections(fc, fs, q_value),
+            FilterType::LowShelf => self.calculate_shelf_sections(false),
+            FilterType::HighShelf => self.calculate_shelf_sections(true),
+            FilterType::Peak { gain_db, q } => Self::calculate_peak_sections(
+                fc,
+                fs,
+                &(T::from_f32(gain_db) / T::from_f64(self.num_sections() as f64)),
+                &T::from_f32(q),
+            ),
+            FilterType::Allpass { q } => Self::calculate_allpass_sections(fc, fs, &T::from_f32(q)),
+        }
+    }
+
+    #[inline]
+    fn calculate_lowpass_sections(fc: &T, fs: &T, q_value: &T) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (*fc / *fs);
+        let alpha: T = omega_0.sin() / (two * *q_value);
+        let cos_omega: T = omega_0.cos();
+        let a0: T = T::ONE + alpha;
+        let b0: T = ((T::ONE - cos_omega) / two) / a0;
+        let b1: T = (T::ONE - cos_omega) / a0;
+        let b2: T = ((T::ONE - cos_omega) / two) / a0;
+        let a1: T = (-two * cos_omega) / a0;
+        let a2: T = (T::ONE - alpha) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    #[inline]
+    fn calculate_highpass_sections(fc: &T, fs: &T, q_value: &T) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (*fc / *fs);
+        let alpha: T = omega_0.sin() / (two * *q_value);
+        let cos_omega: T = omega_0.cos();
+        let a0: T = T::ONE + alpha;
+        let b0: T = ((T::ONE + cos_omega) / two) / a0;
+        let b1: T = -(T::ONE + cos_omega) / a0;
+        let b2: T = ((T::ONE + cos_omega) / two) / a0;
+        let a1: T = (-two * cos_omega) / a0;
+        let a2: T = (T::ONE - alpha) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    /// Constant 0 dB peak gain at the cutoff
+    #[inline]
+    fn calculate_bandpass_sections(fc: &T, fs: &T, q_value: &T) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (*fc / *fs);
+        let alpha: T = omega_0.sin() / (two * *q_value);
+        let cos_omega: T = omega_0.cos();
+        let a0: T = T::ONE + alpha;
+        let b0: T = alpha / a0;
+        let b1: T = T::ZERO;
+        let b2: T = -alpha / a0;
+        let a1: T = (-two * cos_omega) / a0;
+        let a2: T = (T::ONE - alpha) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    #[inline]
+    fn calculate_bandreject_sections(fc: &T, fs: &T, q_value: &T) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (*fc / *fs);
+        let alpha: T = omega_0.sin() / (two * *q_value);
+        let cos_omega: T = omega_0.cos();
+        let a0: T = T::ONE + alpha;
+        let b0: T = T::ONE / a0;
+        let b1: T = (-two * cos_omega) / a0;
+        let b2: T = T::ONE / a0;
+        let a1: T = (-two * cos_omega) / a0;
+        let a2: T = (T::ONE - alpha) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    /// Section `k` of the analog prototype, normalized to a cutoff of 1 rad/s, bilinear
+    /// transformed with the cutoff prewarped. The highpass is the lowpass with s -> 1/s.
+    fn calculate_chebyshev_section(&self, k: usize, high: bool) -> [T; 5] {
+        let poles = 2 * self.num_sections();
+        let theta = std::f64::consts::PI * (2 * k + 1) as f64 / (2 * poles) as f64;
+        let chebyshev_i_pole = |eps: f64| {
+            let v0 = (1. / eps).asinh() / poles as f64;
+            (-v0.sinh() * theta.sin(), v0.cosh() * theta.cos())
+        };
+
+        // analog section as [s^2, s, 1] coefficients
+        let (num, den) = match self.design {
+            FilterDesign::ChebyshevI { ripple_db } => {
+                let eps = (10_f64.powf(ripple_db as f64 / 10.) - 1.).sqrt();
+                let (re, im) = chebyshev_i_pole(eps);
+                let mag_sq = re * re + im * im;
+                // peaks reach 0 dB, an even order starts at the bottom of a ripple
+                let gain = if k == 0 {
+                    10_f64.powf(-ripple_db as f64 / 20.)
+                } else {
+                    1.
+                };
+                ([0., 0., gain * mag_sq], [1., -2. * re, mag_sq])
+            }
+            FilterDesign::ChebyshevII { attenuation_db } => {
+                let eps = 1. / (10_f64.powf(attenuation_db as f64 / 10.) - 1.).sqrt();
+                let (re, im) = chebyshev_i_pole(eps);
+                // the inverse Chebyshev poles are the reciprocals, the zeros sit on the axis
+                let mag_sq = re * re + im * im;
+                let pole_mag_sq = 1. / mag_sq;
+                let zero_sq = 1. / (theta.cos() * theta.cos());
+                let gain = pole_mag_sq / zero_sq;
+                (
+                    [gain, 0., gain * zero_sq],
+                    [1., -2. * re / mag_sq, pole_mag_sq],
+                )
+            }
+            FilterDesign::Butterworth => unreachable!(),
+        };
+        let (num, den) = if high {
+            ([num[2], num[1], num[0]], [den[2], den[1], den[0]])
+        } else {
+            (num, den)
+        };
+
+        let k_warp =
+            (std::f64::consts::PI * self.cutoff_freq.to_f64() / self.sample_rate.to_f64()).tan();
+        let bilinear = |c: [f64; 3]| {
+            let (c2, c1, c0) = (c[0], c[1] * k_warp, c[2] * k_warp * k_warp);
+            [c2 + c1 + c0, 2. * (c0 - c2), c2 - c1 + c0]
+        };
+        let (b, a) = (bilinear(num), bilinear(den));
+        [
+            b[0] / a[0],
+            b[1] / a[0],
+            b[2] / a[0],
+            a[1] / a[0],
+            a[2] / a[0],
+        ]
+        .map(T::from_f64)
+    }
+
+    /// Vicanek, "Matched Second Order Digital Filters" (2016). The poles are the analog ones
+    /// mapped with z = e^(sT), the numerator is solved for from its squared magnitude
+    /// B0 phi0 + B1 phi1 + B2 phi2, with phi0 = cos^2(w/2), phi1 = sin^2(w/2), phi2 = 4 phi0 phi1.
+    fn calculate_matched_section(&self, q: f64, gain_db: f64) -> [T; 5] {
+        let w0 = 2. * std::f64::consts::PI * self.cutoff_freq.to_f64() / self.sample_rate.to_f64();
+        let peak_gain = 10_f64.powf(gain_db / 20.);
+        // the RBJ peak has its poles at Q sqrt(G)
+        let pole_q = match self.filter_type {
+            FilterType::Peak { .. } => q * peak_gain.sqrt(),
+            _ => q,
+        };
+        let zeta = 1. / (2. * pole_q);
+        let decay = (-zeta * w0).exp();
+        let a1 = if zeta <= 1. {
+            -2. * decay * ((1. - zeta * zeta).sqrt() * w0).cos()
+        } else {
+            -2. * decay * ((zeta * zeta - 1.).sqrt() * w0).cosh()
+        };
+        let a2 = decay * decay;
+
+        let (big_a0, big_a1, big_a2) = ((1. + a1 + a2).powi(2), (1. - a1 + a2).powi(2), -4. * a2);
+        let phi1 = (w0 / 2.).sin().powi(2);
+        let phi0 = 1. - phi1;
+        let phi2 = 4. * phi0 * phi1;
+        // squared magnitude of the denominator at the cutoff, and its slope there
+        let r1 = big_a0 * phi0 + big_a1 * phi1 + big_a2 * phi2;
+        let r2 = -big_a0 + big_a1 + 4. * (phi0 - phi1) * big_a2;
+
+        let (b0, b1, b2) = match self.filter_type {
+            FilterType::Lowpass => {
+                let big_b1 = (r1 * q * q - big_a0 * phi0) / phi1;
+                let b0 = 0.5 * (big_a0.sqrt() + big_b1.sqrt());
+                (b0, big_a0.sqrt() - b0, 0.)
+            }
+            FilterType::Highpass => {
+                let b0 = r1.sqrt() * q / (4. * phi1);
+                (b0, -2. * b0, b0)
+            }
+            FilterType::Bandpass => {
+                let big_b2 = (r1 - r2 * phi1) / (4. * phi1 * phi1);
+                let big_b1 = r2 + 4. * (phi1 - phi0) * big_b2;
+                let b1 = -0.5 * big_b1.sqrt();
+                let b0 = 0.5 * ((big_b2 + b1 * b1).sqrt() - b1);
+                (b0, b1, -b0 - b1)
+            }
+            _ => {
+                let g_sq = peak_gain * peak_gain;
+                let (r1, r2) = (r1 * g_sq, r2 * g_sq);
+                let big_b0 = big_a0;
+                let big_b2 = (r1 - r2 * phi1 - big_b0) / (4. * phi1 * phi1);
+                let big_b1 = r2 + big_b0 + 4. * (phi1 - phi0) * big_b2;
+                let w = 0.5 * (big_b0.sqrt() + big_b1.sqrt());
+                let b0 = 0.5 * (w + (w * w + big_b2).sqrt());
+                (
+                    b0,
+                    0.5 * (big_b0.sqrt() - big_b1.sqrt()),
+                    -big_b2 / (4. * b0),
+                )
+            }
+        };
+        [b0, b1, b2, a1, a2].map(T::from_f64)
+    }
+
+    #[inline]
+    fn calculate_peak_sections(fc: &T, fs: &T, gain_db: &T, q_value: &T) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let a = T::from_f32(10.).powf(*gain_db / T::from_f32(40.));
+        let omega_0: T = two * T::PI * (*fc / *fs);
+        let alpha: T = omega_0.sin() / (two * *q_value);
+        let cos_omega: T = omega_0.cos();
+        let a0: T = T::ONE + alpha / a;
+        let b0: T = (T::ONE + alpha * a) / a0;
+        let b1: T = (-two * cos_omega) / a0;
+        let b2: T = (T::ONE - alpha * a) / a0;
+        let a1: T = (-two * cos_omega) / a0;
+        let a2: T = (T::ONE - alpha / a) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    #[inline]
+    fn calculate_allpass_sections(fc: &T, fs: &T, q_value: &T) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (*fc / *fs);
+        let alpha: T = omega_0.sin() / (two * *q_value);
+        let cos_omega: T = omega_0.cos();
+        let a0: T = T::ONE + alpha;
+        let b0: T = (T::ONE - alpha) / a0;
+        let b1: T = (-two * cos_omega) / a0;
+        let b2: T = T::ONE;
+        let a1: T = (-two * cos_omega) / a0;
+        let a2: T = (T::ONE - alpha) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    /// The shelves ignore the cascade Q values, each section takes its share of the gain
+    #[inline]
+    fn calculate_shelf_sections(&self, high: bool) -> [T; 5] {
+        let two = T::from_f32(2.);
+        let section_gain_db = self.shelf_gain_db / T::from_f64(self.num_sections() as f64);
+        let a = T::from_f32(10.).powf(section_gain_db / T::from_f32(40.));
+        let omega_0: T = two * T::PI * (self.cutoff_freq / self.sample_rate);
+        let cos_omega: T = omega_0.cos();
+        let alpha: T = omega_0.sin() / two
+            * ((a + T::ONE / a) * (T::ONE / self.shelf_slope - T::ONE) + two).sqrt();
+        let two_sqrt_a_alpha = two * a.sqrt() * alpha;
+        // the high shelf is the low shelf with the sign of cos flipped
+        let (cos_omega, sign) = if high {
+            (-cos_omega, -T::ONE)
+        } else {
+            (cos_omega, T::ONE)
+        };
+        let a0: T = (a + T::ONE) + (a - T::ONE) * cos_omega + two_sqrt_a_alpha;
+        let b0: T = a * ((a + T::ONE) - (a - T::ONE) * cos_omega + two_sqrt_a_alpha) / a0;
+        let b1: T = sign * two * a * ((a - T::ONE) - (a + T::ONE) * cos_omega) / a0;
+        let b2: T = a * ((a + T::ONE) - (a - T::ONE) * cos_omega - two_sqrt_a_alpha) / a0;
+        let a1: T = sign * -two * ((a - T::ONE) + (a + T::ONE) * cos_omega) / a0;
+        let a2: T = ((a + T::ONE) + (a - T::ONE) * cos_omega - two_sqrt_a_alpha) / a0;
+        [b0, b1, b2, a1, a2]
+    }
+
+    fn start_ramp(&mut self, from: [[T; 5]; MAX_SECTIONS], from_coupled: [[T; 4]; MAX_SECTIONS]) {
+        let steps = T::from_f64(self.ramp_samples as f64);
+        self.ramp_target = self.coefs;
+        self.ramp_step
+            .iter_mut()
+            .zip(self.ramp_target.iter().zip(from.iter()))
+            .for_each(|(step, (to, from))| {
+                (0..5).for_each(|j| step[j] = (to[j] - from[j]) / steps);
+            });
+        self.coefs = from;
+        self.coupled_step
+            .iter_mut()
+            .zip(self.coupled_target.iter().zip(from_coupled.iter()))
+            .for_each(|(step, (to, from))| {
+                (0..4).for_each(|j| step[j] = (to[j] - from[j]) / steps);
+            });
+        self.coupled = from_coupled;
+        self.ramp_remaining = self.ramp_samples;
+    }
+
+    pub(crate) fn is_ramping(&self) -> bool {
+        self.ramp_remaining > 0
+    }
+
+    pub(crate) fn is_fading(&self) -> bool {
+        self.type_fade.is_some()
+    }
+
+    #[inline]
+    pub(crate) fn advance_ramp(&mut self) {
+        if self.ramp_remaining == 0 {
+            return;
+        }
+        self.ramp_remaining -= 1;
+        if self.ramp_remaining == 0 {
+            // land exactly on the target instead of on the summed steps
+            self.coefs = self.ramp_target;
+            self.coupled = self.coupled_target;
+            return;
+        }
+        self.coefs
+            .iter_mut()
+            .zip(self.ramp_step.iter())
+            .for_each(|(c, step)| (0..5).for_each(|j| c[j] += step[j]));
+        if self.topology == Topology::Coupled {
+            self.coupled
+                .iter_mut()
+                .zip(self.coupled_step.iter())
+                .for_each(|(p, step)| (0..4).for_each(|j| p[j] += step[j]));
+        }
+    }
+
+    pub fn process_sample(&mut self, sample: &mut T) {
+        *sample = self.tick(*sample);
+    }
+
+    #[inline]
+    fn tick(&mut self, x: T) -> T {
+        let y = self.tick_sections(x);
+        let gain = if self.output_gain.target_reached() {
+            self.output_gain.current_value()
+        } else {
+            self.output_gain.consume()
+        };
+        y * T::from_f32(gain)
+    }
+
+    #[inline]
+    fn tick_sections(&mut self, x: T) -> T {
+        self.advance_ramp();
+        let sections = self.num_sections();
+        let y = self.topology.run(
+            &self.coefs,
+            &self.coupled,
+            sections,
+            &mut self.states,
+            &mut self.outputs,
+            x,
+        );
+        let Some(fade) = &mut self.type_fade else {
+            return y;
+        };
+        let old = self.topology.run(
+            &fade.coefs,
+            &fade.coupled,
+            fade.sections,
+            &mut fade.states,
+            &mut fade.outputs,
+            x,
+        );
+        let gain = T::from_f32(fade.fade_in.consume());
+        if fade.fade_in.target_reached() {
+            self.type_fade = None;
+        }
+        old + gain * (y - old)
+    }
+
+    /// Runs `x` through the first `sections` sections, transposed direct form II
+    #[inline]
+    pub(crate) fn run_sections(
+        coefs: &[[T; 5]; MAX_SECTIONS],
+        sections: usize,
+        states: &mut [[T; 2]; MAX_SECTIONS],
+        x: T,
+    ) -> T {
+        coefs
+            .iter()
+            .zip(states.iter_mut())
+            .take(sections)
+            .fold(x, |x, (c, state)| tdf2_step(c, state, x))
+    }
+
+    pub(crate) fn coefs(&self) -> &[[T; 5]; MAX_SECTIONS] {
+        &self.coefs
+    }
+
+    /// Sweeps the cutoff at audio rate, `cutoff` holds the cutoff for each sample and the filter
+    /// is left at the last one. The Butterworth lowpass, highpass, bandpass and notch share
+    /// one sin and cos between the sections, the other types regenerate every section.
+    pub fn process_block_modulated(&mut self, input_signal: &mut [T], cutoff: &[T]) {
+        assert_eq!(input_signal.len(), cutoff.len());
+        let fast = self.design == FilterDesign::Butterworth
+            && self.discretization == Discretization::Bilinear
+            && matches!(
+                self.filter_type,
+                FilterType::Lowpass
+                    | FilterType::Highpass
+                    | FilterType::Bandpass
+                    | FilterType::Bandreject
+            );
+        input_signal
+            .iter_mut()
+            .zip(cutoff.iter())
+            .for_each(|(s, fc)| {
+                self.cutoff_freq = *fc;
+                if fast {
+                    self.retune_sections();
+                } else {
+                    self.gen_coefficients();
+                }
+                self.process_sample(s);
+            });
+    }
+
+    /// The lowpass, highpass, bandpass and notch sections, with the same arithmetic as their
+    /// `calculate_*_sections`
+    #[inline]
+    fn retune_sections(&mut self) {
+        self.ramp_remaining = 0;
+        let two = T::from_f32(2.);
+        let omega_0: T = two * T::PI * (self.cutoff_freq / self.sample_rate);
+        let (sin_omega, cos_omega) = (omega_0.sin(), omega_0.cos());
+        let q_vals = Self::q_vals(self.order);
+        let (filter_type, q) = (self.filter_type, self.q_override());
+        self.coefs
+            .iter_mut()
+            .zip(q_vals.iter())
+            .for_each(|(c, q_val)| {
+                let alpha: T = sin_omega / (two * q.unwrap_or(T::from_f32(*q_val)));
+                let a0: T = T::ONE + alpha;
+                let (b0, b1, b2) = match filter_type {
+                    FilterType::Lowpass => (
+                        ((T::ONE - cos_omega) / two) / a0,
+                        (T::ONE - cos_omega) / a0,
+                        ((T::ONE - cos_omega) / two) / a0,
+                    ),
+                    FilterType::Highpass => (
+                        ((T::ONE + cos_omega) / two) / a0,
+                        -(T::ONE + cos_omega) / a0,
+                        ((T::ONE + cos_omega) / two) / a0,
+                    ),
+                    FilterType::Bandpass => (alpha / a0, T::ZERO, -alpha / a0),
+                    _ => (T::ONE / a0, (-two * cos_omega) / a0, T::ONE / a0),
+                };
+                *c = [b0, b1, b2, (-two * cos_omega) / a0, (T::ONE - alpha) / a0];
+            });
+        self.update_coupled();
+    }
+
+    pub fn process_block(&mut self, input_signal: &mut [T]) {
+        input_signal.iter_mut().for_each(|s| *s = self.tick(*s));
+    }
+
+    /// `process_block` that leaves `input` alone, for parallel paths and dry/wet mixing
+    pub fn process_block_to(&mut self, input: &[T], output: &mut [T]) {
+        assert_eq!(input.len(), output.len());
+        output
+            .iter_mut()
+            .zip(input.iter())
+            .for_each(|(y, x)| *y = self.tick(*x));
+    }
+
+    /// Runs an f32 buffer with the state and coefficients kept at `T`, so an
+    /// `IIRBiquadFilter64` can sit in an f32 signal path. Low cutoffs at high sample rates
+    /// put the poles so close to 1 that f32 state audibly moves the response.
+    pub fn process_block_f32(&mut self, input_signal: &mut [f32]) {
+        input_signal
+            .iter_mut()
+            .for_each(|s| *s = self.tick(T::from_f32(*s)).to_f32());
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum FilterOrder {
-    First,
-    Second,
-}
+impl<T: Sample> MemoryFootprint for GenericIIRBiquadFilter<T> {}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::iir_biquad_filter;
+
+    use super::*;
+
+    const FIRST_ORDER_1000_441_LPF_COEFS: [f32; 5] = [
+        0.004604009,
+        0.009208018,
+        0.004604009,
+        -1.7990962,
+        0.81751233,
+    ];
+
+    #[test]
+    fn test_calculate_lowpass() {
+        assert_eq!(
+            iir_biquad_filter::IIRBiquadFilter::calculate_lowpass_sections(
+                &1000.0,
+                &44100.0,
+                &FIRST_ORDER_Q_VALS[0]
+            ),
+            FIRST_ORDER_1000_441_LPF_COEFS
+        )
+    }
+
+    #[test]
+    fn test_calculate_lowpass_order_2() {
+        assert_eq!(
+            iir_biquad_filter::IIRBiquadFilter::calculate_lowpass_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[0]
+            ),
+            [
+                0.020448789,
+                0.040897578,
+                0.020448789,
+                -1.4594773,
+                0.54127246
+            ]
+        );
+        assert_eq!(
+            iir_biquad_filter::IIRBiquadFilter::calculate_lowpass_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[1]
+            ),
+            [0.023635214, 0.04727043, 0.023635214, -1.6868998, 0.7814407]
+        )
+    }
+
+    fn check_coefs(coefs: [f32; 5], expected: [f32; 5]) {
+        coefs
+            .iter()
+            .zip(expected.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "{:?} {:?}", coefs, expected));
+    }
+
+    #[test]
+    fn test_calculate_highpass() {
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_highpass_sections(
+                &1000.0,
+                &44100.0,
+                &FIRST_ORDER_Q_VALS[0],
+            ),
+            [0.9041522, -1.8083044, 0.9041522, -1.7990964, 0.8175124],
+        )
+    }
+
+    #[test]
+    fn test_calculate_highpass_order_2() {
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_highpass_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[0],
+            ),
+            [0.7501874, -1.5003749, 0.7501874, -1.4594773, 0.5412724],
+        );
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_highpass_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[1],
+            ),
+            [0.8670851, -1.7341703, 0.8670851, -1.6868999, 0.7814407],
+        )
+    }
+
+    #[test]
+    fn test_proc_highpass() {
+        let mut input_signal = [1., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.];
+        let mut f = IIRBiquadFilter::new(FilterType::Highpass);
+        f.init(&44100.0, &1000.0, FilterOrder::First);
+
+        f.process_block(&mut input_signal);
+
+        let expected_result: [f32; 11] = [
+            0.9041522,
+            -0.18164743,
+            -0.16180467,
+            -0.14260317,
+            -0.12427953,
+            -0.107011,
+            -0.09092304,
+            -0.0760965,
+            -0.06257423,
+            -0.05036724,
+            -0.03946031,
+        ];
+
+        input_signal
+            .iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-5, "{} {}", a, b));
+
+        // DC is blocked, both sections' zeros sit at z = 1
+        let mut f = IIRBiquadFilter64::new(FilterType::Highpass);
+        f.init(&48000.0, &2500.0, FilterOrder::Second);
+        let mut dc = vec![1.0_f64; 4800];
+        f.process_block(&mut dc);
+        assert!(dc[4799].abs() < 1e-9, "{}", dc[4799]);
+        f.poles_zeros()
+            .zeros
+            .iter()
+            .for_each(|(re, im)| assert!((re - 1.).abs() < 1e-6 && im.abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_calculate_bandreject() {
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_bandreject_sections(
+                &1000.0,
+                &44100.0,
+                &FIRST_ORDER_Q_VALS[0],
+            ),
+            [0.9087562, -1.7990964, 0.9087562, -1.7990964, 0.8175124],
+        );
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_bandreject_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[0],
+            ),
+            [0.7706362, -1.4594773, 0.7706362, -1.4594773, 0.5412724],
+        );
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_bandreject_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[1],
+            ),
+            [0.8907204, -1.6868999, 0.8907204, -1.6868999, 0.7814407],
+        )
+    }
+
+    #[test]
+    fn test_calculate_bandpass() {
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_bandpass_sections(
+                &1000.0,
+                &44100.0,
+                &FIRST_ORDER_Q_VALS[0],
+            ),
+            [0.0912438, 0.0, -0.0912438, -1.7990964, 0.8175124],
+        );
+        check_coefs(
+            iir_biquad_filter::IIRBiquadFilter::calculate_bandpass_sections(
+                &2500.0,
+                &48000.0,
+                &SECOND_ORDER_Q_VALS[1],
+            ),
+            [0.1092796, 0.0, -0.1092796, -1.6868999, 0.7814407],
+        )
+    }
+
+    /// Steady state peak of a sine at `freq` after running through `f`
+    fn sine_gain(f: &mut IIRBiquadFilter64, freq: f64) -> f64 {
+        let mut sig: Vec<f64> = (0..48000)
+            .map(|n| (2. * std::f64::consts::PI * freq * n as f64 / 48000.).sin())
+            .collect();
+        f.process_block(&mut sig);
+        sig[24000..].iter().fold(0., |m, v| v.abs().max(m))
+    }
 
-const W1: usize = 0;
-const W2: usize = 1;
+    #[test]
+    fn test_proc_bandreject() {
+        let mut input_signal = [1., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.];
+        let mut f = IIRBiquadFilter::new(FilterType::Bandreject);
+        f.init(&44100.0, &1000.0, FilterOrder::First);
 
-const B0: usize = 0;
-const B1: usize = 1;
-const B2: usize = 2;
-const A1: usize = 3;
-const A2: usize = 4;
+        f.process_block(&mut input_signal);
 
-const FIRST_ORDER_Q_VALS: [f32; 1] = [0.70710677];
-const SECOND_ORDER_Q_VALS: [f32; 2] = [0.54, 1.31];
+        let expected_result: [f32; 11] = [
+            0.9087562,
+            -0.16415639,
+            -0.12949644,
+            -0.09877669,
+            -0.07184384,
+            -0.04850283,
+            -0.02852804,
+            -0.01167302,
+            0.00232113,
+            0.01371878,
+            0.02278385,
+        ];
 
-#[derive(Debug)]
-pub struct IIRBiquadFilter {
-    coefs: [[f32; 5]; 2],
-    filter_type: FilterType,
-    states: [[f32; 2]; 2],
-    order: FilterOrder,
-    cutoff_freq: f32,
-    sample_rate: f32,
-}
+        input_signal
+            .iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-5, "{} {}", a, b));
 
-impl Default for IIRBiquadFilter {
-    fn default() -> Self {
-        IIRBiquadFilter {
-            coefs: [[0.0_f32; 5]; 2],
-            filter_type: FilterType::Lowpass,
-            states: [[0.0_f32; 2]; 2],
-            order: FilterOrder::First,
-            cutoff_freq: 1000.0,
-            sample_rate: 44100.0,
-        }
-    }
-}
+        // the notch swallows the cutoff and leaves the rest of the band alone
+        let mut f = IIRBiquadFilter64::new(FilterType::Bandreject);
+        f.init(&48000.0, &2500.0, FilterOrder::Second);
+        assert!(sine_gain(&mut f, 2500.) < 1e-3);
+        f.reset();
+        assert!((sine_gain(&mut f, 50.) - 1.).abs() < 1e-2);
+        f.poles_zeros()
+            .zeros
+            .iter()
+            .for_each(|(re, im)| assert!((re * re + im * im - 1.).abs() < 1e-9));
 
-impl IIRBiquadFilter {
-    pub fn new(ft: FilterType) -> Self {
-        let mut new_biquad = IIRBiquadFilter::default();
-        new_biquad.set_filter_type(ft);
-        new_biquad
+        let mut f = IIRBiquadFilter64::new(FilterType::Bandpass);
+        f.init(&48000.0, &2500.0, FilterOrder::First);
+        assert!((sine_gain(&mut f, 2500.) - 1.).abs() < 1e-3);
+        f.reset();
+        assert!(sine_gain(&mut f, 50.) < 5e-2);
     }
 
-    pub fn init(&mut self, sample_rate: &f32, cutoff_freq: &f32, order: FilterOrder) {
-        self.sample_rate = *sample_rate;
-        self.gen_coefficients(cutoff_freq, order);
-        self.order = order;
-        self.cutoff_freq = *cutoff_freq;
+    /// Magnitude of the running sections at DC and at nyquist
+    fn dc_and_nyquist_gain(f: &IIRBiquadFilter64) -> (f64, f64) {
+        f.coefs
+            .iter()
+            .take(f.num_sections())
+            .fold((1., 1.), |(dc, ny), c| {
+                (
+                    dc * (c[B0] + c[B1] + c[B2]) / (1. + c[A1] + c[A2]),
+                    ny * (c[B0] - c[B1] + c[B2]) / (1. - c[A1] + c[A2]),
+                )
+            })
     }
 
-    pub fn set_filter_type(&mut self, new_filter_type: FilterType) {
-        self.filter_type = new_filter_type;
-        self.gen_coefficients(&self.cutoff_freq.clone(), self.order);
-    }
+    #[test]
+    fn test_calculate_shelves() {
+        let mut f = IIRBiquadFilter::new(FilterType::LowShelf);
+        f.init(&44100.0, &1000.0, FilterOrder::First);
+        f.set_shelf_gain_db(6.0);
+        check_coefs(
+            f.coefs[0],
+            [1.0354764, -1.8241491, 0.815104, -1.8307412, 0.8439883],
+        );
+        f.set_filter_type(FilterType::HighShelf);
+        check_coefs(
+            f.coefs[0],
+            [1.9269027, -3.5276603, 1.6262833, -1.761652, 0.7871778],
+        );
 
-    pub fn reset(&mut self) {
-        self.states = [[0.0_f32; 2]; 2];
+        let mut f = IIRBiquadFilter::new(FilterType::HighShelf);
+        f.set_shelf_slope(0.5);
+        f.set_shelf_gain_db(-9.0);
+        f.init(&48000.0, &4000.0, FilterOrder::First);
+        check_coefs(
+            f.coefs[0],
+            [0.44361624, -0.42446016, 0.09719011, -1.3020722, 0.41841843],
+        );
+        assert_eq!(f.get_shelf_gain_db(), -9.0);
+        assert_eq!(f.get_shelf_slope(), 0.5);
     }
 
-    pub fn get_current_cutoff(&self) -> f32 {
-        self.cutoff_freq
-    }
+    #[test]
+    fn test_shelf_gains() {
+        let boost = 10_f64.powf(6.0 / 20.);
+        let cut = 10_f64.powf(-12.0 / 20.);
+        [FilterOrder::First, FilterOrder::Second]
+            .iter()
+            .for_each(|order| {
+                let mut f = IIRBiquadFilter64::new(FilterType::LowShelf);
+                f.init(&48000.0, &500.0, *order);
+                f.set_shelf_gain_db(6.0);
+                let (dc, ny) = dc_and_nyquist_gain(&f);
+                assert!(
+                    (dc - boost).abs() < 1e-9 && (ny - 1.).abs() < 1e-9,
+                    "{} {}",
+                    dc,
+                    ny
+                );
 
-    pub fn set_cutoff(&mut self, new_cutoff_freq: f32) {
-        self.cutoff_freq = new_cutoff_freq;
-        self.gen_coefficients(&new_cutoff_freq, self.order);
+                f.set_filter_type(FilterType::HighShelf);
+                f.set_shelf_gain_db(-12.0);
+                let (dc, ny) = dc_and_nyquist_gain(&f);
+                assert!(
+                    (dc - 1.).abs() < 1e-9 && (ny - cut).abs() < 1e-9,
+                    "{} {}",
+                    dc,
+                    ny
+                );
+                assert!(f.poles_zeros().stability_margin() > 0.0);
+            });
+
+        // flat at 0 dB
+        let mut f = IIRBiquadFilter64::new(FilterType::LowShelf);
+        f.init(&48000.0, &500.0, FilterOrder::Second);
+        let mut sig: Vec<f64> = RANDOM_NORMAL_480.iter().map(|v| *v as f64).collect();
+        let expected = sig.clone();
+        f.process_block(&mut sig);
+        sig.iter()
+            .zip(expected.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-12));
     }
 
-    #[inline]
-    fn gen_coefficients(&mut self, cutoff_freq: &f32, order: FilterOrder) {
-        match order {
-            FilterOrder::First => {
-                self.coefs = [
-                    match self.filter_type {
-                        FilterType::Lowpass => Self::calculate_lowpass_sections(
-                            &cutoff_freq,
-                            &self.sample_rate,
-                            &FIRST_ORDER_Q_VALS[0],
-                        ),
-                        FilterType::Highpass => Self::calculate_highpass_sections(
-                            &cutoff_freq,
-                            &self.sample_rate,
-                            &FIRST_ORDER_Q_VALS[0],
-                        ),
-                        FilterType::Bandpass => Self::calculate_bandpass_sections(
-                            &cutoff_freq,
-                            &self.sample_rate,
-                            &FIRST_ORDER_Q_VALS[0],
-                        ),
-                        FilterType::Bandreject => Self::calculate_bandreject_sections(
-                            &cutoff_freq,
-                            &self.sample_rate,
-                            &FIRST_ORDER_Q_VALS[0],
-                        ),
-                    },
-                    [0.0, 0.0, 0.0, 0.0, 0.0],
-                ]
-            }
-            FilterOrder::Second => {
-                self.coefs = [
-                    Self::calculate_lowpass_sections(
-                        &cutoff_freq,
-                        &self.sample_rate,
-                        &SECOND_ORDER_Q_VALS[0],
-                    ),
-                    Self::calculate_lowpass_sections(
-                        &cutoff_freq,
-                        &self.sample_rate,
-                        &SECOND_ORDER_Q_VALS[1],
-                    ),
-                ]
-            }
+    #[test]
+    fn test_proc_peak() {
+        let peak = FilterType::Peak {
+            gain_db: 6.0,
+            q: 2.0,
         };
+        let mut f = IIRBiquadFilter::new(peak);
+        f.init(&44100.0, &1000.0, FilterOrder::First);
+        check_coefs(
+            f.coefs[0],
+            [1.0243988, -1.9312018, 0.9265712, -1.9312018, 0.95097005],
+        );
+
+        let mut input_signal = [1., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.];
+        f.process_block(&mut input_signal);
+        let expected_result: [f32; 11] = [
+            1.0243988,
+            0.04711908,
+            0.04339505,
+            0.03899576,
+            0.03404129,
+            0.02865681,
+            0.02296982,
+            0.0171076,
+            0.01119462,
+            0.00535025,
+            -0.00031334,
+        ];
+        input_signal
+            .iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-5, "{} {}", a, b));
+
+        // the full gain at the center, flat at either end, for either order
+        [FilterOrder::First, FilterOrder::Second]
+            .iter()
+            .for_each(|order| {
+                let mut f = IIRBiquadFilter64::new(FilterType::Peak {
+                    gain_db: -9.0,
+                    q: 1.5,
+                });
+                f.init(&48000.0, &2500.0, *order);
+                let center = sine_gain(&mut f, 2500.);
+                assert!(
+                    (center - 10_f64.powf(-9.0 / 20.)).abs() < 2e-3,
+                    "{}",
+                    center
+                );
+                let (dc, ny) = dc_and_nyquist_gain(&f);
+                assert!((dc - 1.).abs() < 1e-9 && (ny - 1.).abs() < 1e-9);
+            });
     }
 
-    #[inline]
-    fn calculate_lowpass_sections(fc: &f32, fs: &f32, q_value: &f32) -> [f32; 5] {
-        let omega_0: f32 = 2. * PI * (*fc / *fs);
-        let alpha: f32 = omega_0.sin() / (2. * q_value);
-        let cos_omega: f32 = omega_0.cos();
-        let a0: f32 = 1. + alpha;
-        let b0: f32 = ((1. - cos_omega) / 2.) / a0;
-        let b1: f32 = (1. - cos_omega) / a0;
-        let b2: f32 = ((1. - cos_omega) / 2.) / a0;
-        let a1: f32 = (-2. * cos_omega) / a0;
-        let a2: f32 = (1. - alpha) / a0;
-        [b0, b1, b2, a1, a2]
+    #[test]
+    fn test_allpass() {
+        [FilterOrder::First, FilterOrder::Second]
+            .iter()
+            .for_each(|order| {
+                let mut f = IIRBiquadFilter64::new(FilterType::Allpass { q: 0.8 });
+                f.init(&48000.0, &2500.0, *order);
+                // numerator is the denominator reversed
+                f.coefs.iter().take(f.num_sections()).for_each(|c| {
+                    assert_eq!([c[B0], c[B1], c[B2]], [c[A2], c[A1], 1.]);
+                });
+
+                // all the energy of the impulse comes back out
+                let mut impulse = vec![0.0_f64; 4800];
+                impulse[0] = 1.;
+                f.process_block(&mut impulse);
+                let energy: f64 = impulse.iter().map(|v| v * v).sum();
+                assert!((energy - 1.).abs() < 1e-9, "{}", energy);
+
+                // compared by power, a sampled sine near nyquist misses its peaks
+                [100., 2500., 15000.].iter().for_each(|freq| {
+                    f.reset();
+                    let mut sig: Vec<f64> = (0..48000)
+                        .map(|n| (2. * std::f64::consts::PI * freq * n as f64 / 48000.).sin())
+                        .collect();
+                    let power_in: f64 = sig[24000..].iter().map(|v| v * v).sum();
+                    f.process_block(&mut sig);
+                    let power_out: f64 = sig[24000..].iter().map(|v| v * v).sum();
+                    let gain = (power_out / power_in).sqrt();
+                    assert!((gain - 1.).abs() < 1e-3, "{} at {}", gain, freq);
+                });
+            });
+
+        // a single section inverts a sine at the cutoff
+        let mut f = IIRBiquadFilter64::new(FilterType::Allpass { q: 0.8 });
+        f.init(&48000.0, &2400.0, FilterOrder::First);
+        let mut sig: Vec<f64> = (0..4800)
+            .map(|n| (2. * std::f64::consts::PI * n as f64 / 20.).sin())
+            .collect();
+        let input = sig.clone();
+        f.process_block(&mut sig);
+        sig.iter()
+            .zip(input.iter())
+            .skip(2400)
+            .for_each(|(y, x)| assert!((y + x).abs() < 1e-6, "{} {}", y, x));
     }
 
-    fn calculate_highpass_sections(_fc: &f32, _fs: &f32, _q_value: &f32) -> [f32; 5] {
-        unimplemented!()
+    #[test]
+    fn test_set_q() {
+        let mut f = IIRBiquadFilter::default();
+        f.init(&44100.0, &1000.0, FilterOrder::First);
+        f.set_q(FIRST_ORDER_Q_VALS[0]);
+        assert_eq!(f.coefs[0], FIRST_ORDER_1000_441_LPF_COEFS);
+        assert_eq!(f.get_q(), Some(FIRST_ORDER_Q_VALS[0]));
+
+        let mut f = IIRBiquadFilter64::default();
+        f.init(&48000.0, &2500.0, FilterOrder::First);
+        f.set_q(4.0);
+        let resonance = sine_gain(&mut f, 2500.);
+        assert!((resonance - 4.).abs() < 0.05, "{}", resonance);
+
+        // both sections resonate at second order
+        f.set_order(FilterOrder::Second);
+        f.reset();
+        let resonance = sine_gain(&mut f, 2500.);
+        assert!((resonance - 16.).abs() < 0.3, "{}", resonance);
+
+        let mut expected = IIRBiquadFilter64::default();
+        expected.init(&48000.0, &2500.0, FilterOrder::Second);
+        f.clear_q();
+        assert_eq!(f.get_q(), None);
+        assert_eq!(f.coefs, expected.coefs);
     }
 
-    fn calculate_bandpass_sections(_fc: &f32, _fs: &f32, _q_value: &f32) -> [f32; 5] {
-        unimplemented!()
+    #[test]
+    fn test_process_block_f32() {
+        // 10 Hz at 192 kHz, step response against an all f64 reference
+        let step = vec![0.25_f32; 192000];
+        let mut reference = IIRBiquadFilter64::new(FilterType::Lowpass);
+        reference.init(&192000.0, &10.0, FilterOrder::Second);
+        let mut expected: Vec<f64> = step.iter().map(|x| *x as f64).collect();
+        reference.process_block(&mut expected);
+
+        let mut f64_state = IIRBiquadFilter64::new(FilterType::Lowpass);
+        f64_state.init(&192000.0, &10.0, FilterOrder::Second);
+        let mut wide = step.clone();
+        f64_state.process_block_f32(&mut wide);
+
+        let mut f32_state = IIRBiquadFilter::new(FilterType::Lowpass);
+        f32_state.init(&192000.0, &10.0, FilterOrder::Second);
+        let mut narrow = step.clone();
+        f32_state.process_block_f32(&mut narrow);
+        let mut narrow_block = step.clone();
+        f32_state.reset();
+        f32_state.process_block(&mut narrow_block);
+        assert_eq!(narrow, narrow_block);
+
+        let max_err = |out: &[f32]| {
+            out.iter()
+                .zip(expected.iter())
+                .fold(0., |m: f64, (y, e)| m.max((*y as f64 - e).abs()))
+        };
+        assert!(max_err(&wide) < 1e-7, "{}", max_err(&wide));
+        assert!(max_err(&narrow) > 100. * max_err(&wide));
     }
 
-    fn calculate_bandreject_sections(_fc: &f32, _fs: &f32, _q_value: &f32) -> [f32; 5] {
-        unimplemented!()
+    fn preset_filter() -> IIRBiquadFilter {
+        let mut f = IIRBiquadFilter::new(FilterType::HighShelf);
+        f.init(&96000.0, &4000.0, FilterOrder::Third);
+        f.set_shelf_gain_db(4.5);
+        f.set_shelf_slope(0.8);
+        f.set_bandwidth_octaves(1.5);
+        f
     }
 
-    pub fn process_sample(&mut self, sample: &mut f32) {
-        let mut y: f32 = 0.0;
-        let num_sections: usize = match &self.order {
-            FilterOrder::First => 1,
-            FilterOrder::Second => 2,
+    #[test]
+    fn test_topologies() {
+        let input: Vec<f64> = (0..1024)
+            .map(|n| (n as f64 * 0.01).sin() + 0.3 * (n as f64 * 2.1).cos())
+            .collect();
+        let run = |topology, ft, cutoff| {
+            let mut f = IIRBiquadFilter64::new(ft);
+            f.init(&48000.0, &cutoff, FilterOrder::Second);
+            f.set_topology(topology);
+            assert_eq!(f.get_topology(), topology);
+            let mut out = input.clone();
+            f.process_block(&mut out);
+            out
         };
-        for i in 0..num_sections {
-            let state = self.states[i];
-            let coefs = self.coefs[i];
+        [
+            (FilterType::Lowpass, 30.0),
+            (FilterType::Highpass, 5000.0),
+            (FilterType::Bandreject, 1000.0),
+            (
+                FilterType::Peak {
+                    gain_db: 9.0,
+                    q: 4.0,
+                },
+                200.0,
+            ),
+        ]
+        .iter()
+        .for_each(|(ft, cutoff)| {
+            let expected = run(Topology::TransposedDirectForm2, *ft, *cutoff);
+            [Topology::DirectForm1, Topology::Coupled]
+                .iter()
+                .for_each(|topology| {
+                    run(*topology, *ft, *cutoff)
+                        .iter()
+                        .zip(expected.iter())
+                        .for_each(|(a, b)| {
+                            assert!((a - b).abs() < 1e-9, "{:?} {} {}", topology, a, b)
+                        });
+                });
+        });
 
-            let x = if i == 0 { *sample } else { y };
+        // real poles fall back to TDF2
+        let mut f = IIRBiquadFilter64::new(FilterType::Lowpass);
+        f.init(&48000.0, &1000.0, FilterOrder::First);
+        f.set_q(0.3);
+        let mut expected = input.clone();
+        f.process_block(&mut expected);
+        f.set_topology(Topology::Coupled);
+        let mut out = input.clone();
+        f.process_block(&mut out);
+        assert_eq!(out, expected);
+    }
 
-            y = (coefs[B0] * x) + state[W1];
-            self.states[i][W1] = (coefs[B1] * x) - (coefs[A1] * y) + state[W2];
-            self.states[i][W2] = (coefs[B2] * x) - (coefs[A2] * y);
-        }
-        *sample = y;
+    #[test]
+    fn test_coupled_low_cutoff_f32() {
+        // 5 Hz at 192 kHz, where the rounded f32 a1 and a2 no longer place the poles
+        let input: Vec<f32> = (0..192000)
+            .map(|n| 0.25 + 0.1 * (n as f32 * 0.0003).sin())
+            .collect();
+        let mut reference = IIRBiquadFilter64::new(FilterType::Lowpass);
+        reference.init(&192000.0, &5.0, FilterOrder::Second);
+        let mut expected: Vec<f64> = input.iter().map(|x| *x as f64).collect();
+        reference.process_block(&mut expected);
+
+        let max_err = |topology| {
+            let mut f = IIRBiquadFilter::new(FilterType::Lowpass);
+            f.init(&192000.0, &5.0, FilterOrder::Second);
+            f.set_topology(topology);
+            let mut out = input.clone();
+            f.process_block(&mut out);
+            out.iter()
+                .zip(expected.iter())
+                .fold(0., |m: f64, (y, e)| m.max((*y as f64 - e).abs()))
+        };
+        let (tdf2, coupled) = (
+            max_err(Topology::TransposedDirectForm2),
+            max_err(Topology::Coupled),
+        );
+        assert!(coupled < 1e-4, "{}", coupled);
+        assert!(coupled * 20. < tdf2, "coupled {} tdf2 {}", coupled, tdf2);
     }
 
-    pub fn process_block(&mut self, input_signal: &mut [f32]) {
-        input_signal.iter_mut().for_each(|s| {
-            let mut y: f32 = 0.0;
-            let num_sections: usize = match &self.order {
-                FilterOrder::First => 1,
-                FilterOrder::Second => 2,
-            };
-            for i in 0..num_sections {
-                let state = self.states[i];
-                let coefs = self.coefs[i];
+    #[test]
+    fn test_settings() {
+        let mut f = preset_filter();
+        f.set_topology(Topology::Coupled);
+        let settings = f.settings();
+        assert_eq!(settings.order, FilterOrder::Third);
+        assert_eq!(settings.bandwidth_octaves, Some(1.5));
+        assert_eq!(settings.topology, Topology::Coupled);
+        let restored = IIRBiquadFilter::from_settings(&settings);
+        assert_eq!(restored.coefs, f.coefs);
+        assert_eq!(restored.get_topology(), Topology::Coupled);
+        assert_eq!(restored.settings(), settings);
 
-                let x = if i == 0 { *s } else { y };
+        let mut lowpass = IIRBiquadFilter::new(FilterType::Lowpass);
+        lowpass.set_type_fade(64);
+        lowpass.apply_settings(&settings);
+        assert!(lowpass.type_fade.is_none());
+        assert_eq!(lowpass.coefs, f.coefs);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_settings_serde() {
+        let settings = preset_filter().settings();
+        let json = serde_json::to_string(&settings).unwrap();
+        let restored: BiquadSettings<f32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, settings);
 
-                y = (coefs[B0] * x) + state[W1];
-                self.states[i][W1] = (coefs[B1] * x) - (coefs[A1] * y) + state[W2];
-                self.states[i][W2] = (coefs[B2] * x) - (coefs[A2] * y);
+        let peak: BiquadSettings<f64> = serde_json::from_str(
+            r#"{
+                "filter_type": { "Peak": { "gain_db": -3.0, "q": 2.0 } },
+                "order": "First",
+                "design": { "ChebyshevI": { "ripple_db": 0.5 } },
+                "sample_rate": 48000.0,
+                "cutoff_freq": 1000.0,
+                "q": null,
+                "bandwidth_octaves": null,
+                "shelf_gain_db": 0.0,
+                "shelf_slope": 1.0
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            peak.filter_type,
+            FilterType::Peak {
+                gain_db: -3.0,
+                q: 2.0
             }
-            *s = y;
+        );
+        assert_eq!(peak.topology, Topology::TransposedDirectForm2);
+    }
+
+    #[test]
+    fn test_bandwidth_octaves() {
+        let mut f = IIRBiquadFilter64::new(FilterType::Bandreject);
+        f.init(&48000.0, &1000.0, FilterOrder::First);
+        f.set_bandwidth_octaves(1.0);
+        assert_eq!(f.get_bandwidth_octaves(), Some(1.0));
+        assert_eq!(f.get_q(), None);
+        // about sqrt(2) for one octave well below Nyquist
+        let q = f.q_override().unwrap();
+        assert!((q - std::f64::consts::SQRT_2).abs() < 0.01, "{}", q);
+        [
+            1000. / std::f64::consts::SQRT_2,
+            1000. * std::f64::consts::SQRT_2,
+        ]
+        .iter()
+        .for_each(|edge| {
+            let mag = f.magnitude_at(*edge);
+            assert!(
+                (mag - std::f64::consts::FRAC_1_SQRT_2).abs() < 0.01,
+                "{}",
+                mag
+            );
         });
+
+        // the -3 dB points stay an octave apart up high, where a fixed Q narrows
+        f.set_filter_type(FilterType::Bandpass);
+        f.set_cutoff(12000.0);
+        let width = |f: &IIRBiquadFilter64| {
+            let above = (1..2400)
+                .map(|n| n as f64 * 10.)
+                .filter(|freq| f.magnitude_at(*freq) >= std::f64::consts::FRAC_1_SQRT_2)
+                .collect::<Vec<_>>();
+            (above[above.len() - 1] / above[0]).log2()
+        };
+        assert!((width(&f) - 1.).abs() < 0.02, "{}", width(&f));
+        let mut fixed_q = IIRBiquadFilter64::new(FilterType::Bandpass);
+        fixed_q.init(&48000.0, &12000.0, FilterOrder::First);
+        fixed_q.set_q(std::f64::consts::SQRT_2);
+        assert!(width(&fixed_q) < 0.9, "{}", width(&fixed_q));
+        let mut retuned = IIRBiquadFilter64::new(FilterType::Bandpass);
+        retuned.init(&48000.0, &1000.0, FilterOrder::First);
+        retuned.set_bandwidth_octaves(1.0);
+        let mut block = [0.0; 1];
+        retuned.process_block_modulated(&mut block, &[12000.0]);
+        assert_eq!(retuned.coefs, f.coefs);
+
+        f.set_q(2.0);
+        assert_eq!(f.get_bandwidth_octaves(), None);
+        f.set_bandwidth_octaves(0.5);
+        f.clear_q();
+        assert_eq!((f.get_q(), f.get_bandwidth_octaves()), (None, None));
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn test_process_block_to() {
+        let input: Vec<f32> = (0..64).map(|n| (n as f32 * 0.7).sin()).collect();
+        let mut in_place = IIRBiquadFilter::new(FilterType::Bandpass);
+        in_place.init(&48000.0, &3000.0, FilterOrder::Second);
+        let mut out_of_place = IIRBiquadFilter::new(FilterType::Bandpass);
+        out_of_place.init(&48000.0, &3000.0, FilterOrder::Second);
 
-    use crate::iir_biquad_filter;
+        let mut expected = input.clone();
+        in_place.process_block(&mut expected);
+        let mut output = vec![0.0; 64];
+        out_of_place.process_block_to(&input, &mut output);
+        assert_eq!(output, expected);
+        assert_eq!(input[1], 0.7_f32.sin());
+    }
 
-    use super::*;
+    #[test]
+    fn test_type_fade() {
+        let max_jump = |fade: usize| {
+            let mut f = IIRBiquadFilter64::new(FilterType::Lowpass);
+            f.init(&48000.0, &1000.0, FilterOrder::Second);
+            f.set_type_fade(fade);
+            let mut settle = [1.0; 4800];
+            f.process_block(&mut settle);
+            f.set_filter_type(FilterType::Highpass);
+            let mut block = [1.0; 512];
+            f.process_block(&mut block);
+            assert!(f.type_fade.is_none());
+            // settled lowpass output, then the switch
+            [settle[4799]]
+                .iter()
+                .chain(block.iter())
+                .collect::<Vec<_>>()
+                .windows(2)
+                .fold(0., |m: f64, w| m.max((w[1] - w[0]).abs()))
+        };
+        assert!(max_jump(0) > 0.5, "{}", max_jump(0));
+        assert!(max_jump(256) < 0.01, "{}", max_jump(256));
 
-    const FIRST_ORDER_1000_441_LPF_COEFS: [f32; 5] = [
-        0.004604009,
-        0.009208018,
-        0.004604009,
-        -1.7990962,
-        0.81751233,
-    ];
+        // past the fade it's a highpass started from silence
+        let mut f = IIRBiquadFilter64::new(FilterType::Lowpass);
+        f.init(&48000.0, &1000.0, FilterOrder::First);
+        f.set_type_fade(64);
+        assert_eq!(f.get_type_fade(), 64);
+        let mut block = [0.3; 100];
+        f.process_block(&mut block);
+        f.set_filter_type(FilterType::Highpass);
+        let mut faded: Vec<f64> = (0..200).map(|n| (n as f64 * 0.4).sin()).collect();
+        let mut expected = faded.clone();
+        f.process_block(&mut faded);
+        let mut highpass = IIRBiquadFilter64::new(FilterType::Highpass);
+        highpass.init(&48000.0, &1000.0, FilterOrder::First);
+        highpass.process_block(&mut expected);
+        assert_eq!(faded[64..], expected[64..]);
+    }
 
     #[test]
-    fn test_calculate_lowpass() {
-        assert_eq!(
-            iir_biquad_filter::IIRBiquadFilter::calculate_lowpass_sections(
-                &1000.0,
-                &44100.0,
-                &FIRST_ORDER_Q_VALS[0]
-            ),
-            FIRST_ORDER_1000_441_LPF_COEFS
-        )
+    fn test_set_sample_rate() {
+        let mut f = IIRBiquadFilter64::new(FilterType::LowShelf);
+        f.init(&44100.0, &300.0, FilterOrder::Second);
+        f.set_shelf_gain_db(-6.0);
+        f.set_q(0.9);
+        let mut block = [0.5; 16];
+        f.process_block(&mut block);
+        let states = f.states;
+
+        f.set_sample_rate(96000.0);
+        assert_eq!(f.get_sample_rate(), 96000.0);
+        assert_eq!(f.states, states);
+        let mut expected = IIRBiquadFilter64::new(FilterType::LowShelf);
+        expected.set_shelf_gain_db(-6.0);
+        expected.set_q(0.9);
+        expected.init(&96000.0, &300.0, FilterOrder::Second);
+        assert_eq!(f.coefs, expected.coefs);
+        assert_eq!(f.get_current_cutoff(), 300.0);
     }
 
     #[test]
-    fn test_calculate_lowpass_order_2() {
+    fn test_validate() {
+        let f = IIRBiquadFilter::try_new(FilterType::Lowpass, 48000.0, 1000.0, FilterOrder::Third)
+            .unwrap();
+        assert!(f.is_stable());
+
+        let err = |ft, sr, fc| IIRBiquadFilter64::try_new(ft, sr, fc, FilterOrder::First).err();
         assert_eq!(
-            iir_biquad_filter::IIRBiquadFilter::calculate_lowpass_sections(
-                &2500.0,
-                &48000.0,
-                &SECOND_ORDER_Q_VALS[0]
-            ),
-            [
-                0.020448789,
-                0.040897578,
-                0.020448789,
-                -1.4594773,
-                0.54127246
-            ]
+            err(FilterType::Lowpass, 0.0, 1000.0),
+            Some(BiquadError::InvalidSampleRate(0.0))
         );
         assert_eq!(
-            iir_biquad_filter::IIRBiquadFilter::calculate_lowpass_sections(
-                &2500.0,
-                &48000.0,
-                &SECOND_ORDER_Q_VALS[1]
+            err(FilterType::Highpass, 48000.0, 30000.0),
+            Some(BiquadError::CutoffOutOfRange {
+                cutoff_hz: 30000.0,
+                nyquist_hz: 24000.0
+            })
+        );
+        assert!(matches!(
+            err(FilterType::Bandpass, 48000.0, f64::NAN),
+            Some(BiquadError::CutoffOutOfRange { .. })
+        ));
+        assert_eq!(
+            err(
+                FilterType::Peak {
+                    gain_db: f32::INFINITY,
+                    q: 1.0
+                },
+                48000.0,
+                1000.0
             ),
-            [0.023635214, 0.04727043, 0.023635214, -1.6868998, 0.7814407]
-        )
+            Some(BiquadError::NonFiniteCoefficients)
+        );
+
+        let mut f = IIRBiquadFilter64::default();
+        f.init(&48000.0, &1000.0, FilterOrder::First);
+        f.set_q(-1.0);
+        assert!(!f.is_stable());
+        assert_eq!(f.validate(), Err(BiquadError::Unstable));
+        assert_eq!(f.validate().unwrap_err().to_string(), "filter is unstable");
+        f.set_q(0.0);
+        assert_eq!(f.validate(), Err(BiquadError::NonFiniteCoefficients));
+        assert!(!f.is_stable());
+        f.clear_q();
+        assert_eq!(f.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_frequency_response() {
+        let mut f = IIRBiquadFilter64::default();
+        f.init(&48000.0, &1000.0, FilterOrder::First);
+        assert!((f.magnitude_at(1000.) - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-7);
+        assert!((f.phase_at(1000.) + std::f64::consts::FRAC_PI_2).abs() < 1e-7);
+        assert!(f.phase_at(1e-3).abs() < 1e-5);
+        assert!((f.magnitude_at(1e-3) - 1.).abs() < 1e-9);
+
+        let freqs = [20., 440., 1000., 5000., 20000.];
+        let response = f.response(&freqs);
+        assert_eq!(response.len(), 5);
+        response
+            .iter()
+            .zip(freqs.iter())
+            .for_each(|((re, im), freq)| {
+                assert_eq!((*re, *im), f.response_at(*freq));
+                assert!((re.hypot(*im) - f.magnitude_at(*freq)).abs() < 1e-12);
+            });
+
+        // cascades multiply, with f32 coefficients too
+        let mut f = IIRBiquadFilter::new(FilterType::Highpass);
+        f.init(&48000.0, &1000.0, FilterOrder::Second);
+        assert!((f.magnitude_at(1000.) - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-2);
+        assert!(f.magnitude_at(100.) < 1e-3);
+        let mag_db = 20. * f.magnitude_at(8000.).log10();
+        assert!(mag_db.abs() < 0.01, "{}", mag_db);
+    }
+
+    #[test]
+    fn test_higher_order_butterworth() {
+        [
+            (FilterOrder::Second, 4),
+            (FilterOrder::Third, 6),
+            (FilterOrder::Fourth, 8),
+        ]
+        .iter()
+        .for_each(|(order, poles)| {
+            let mut f = IIRBiquadFilter64::default();
+            f.init(&48000.0, &1000.0, *order);
+            assert_eq!(f.poles_zeros().poles.len(), *poles);
+
+            // bilinear Butterworth, 1 / sqrt(1 + (tan(w / 2) / tan(wc / 2))^2n)
+            let warp = |freq: f64| (std::f64::consts::PI * freq / 48000.).tan();
+            [250., 1000., 2000., 4000.].iter().for_each(|freq| {
+                let expected =
+                    1. / (1. + (warp(*freq) / warp(1000.)).powi(*poles as i32 * 2)).sqrt();
+                let mag = f.magnitude_at(*freq);
+                assert!(
+                    (mag / expected - 1.).abs() < 2e-2,
+                    "order {}: {} vs {} at {}",
+                    poles,
+                    mag,
+                    expected,
+                    freq
+                );
+            });
+        });
+
+        // going up in order starts the new sections from rest
+        let mut f = IIRBiquadFilter::default();
+        f.init(&48000.0, &1000.0, FilterOrder::Second);
+        let mut sig = [1.0_f32; 16];
+        f.process_block(&mut sig);
+        f.states[2] = [0.5, 0.5];
+        f.set_order(FilterOrder::Fourth);
+        assert_eq!(f.states[2], [0.0_f32; 2]);
+        assert_eq!(f.states[3], [0.0_f32; 2]);
+        assert_ne!(f.states[1], [0.0_f32; 2]);
+        f.set_order(FilterOrder::First);
+        assert_eq!(&f.coefs[1..], &[[0.0_f32; 5]; 3]);
+    }
+
+    #[test]
+    fn test_chebyshev_designs() {
+        let ripple = 10_f64.powf(-1. / 20.);
+        let mut f = IIRBiquadFilter64::default();
+        f.init(&48000.0, &2000.0, FilterOrder::Second);
+        f.set_design(FilterDesign::ChebyshevI { ripple_db: 1.0 });
+        assert_eq!(f.get_design(), FilterDesign::ChebyshevI { ripple_db: 1.0 });
+        assert!((f.magnitude_at(1e-3) - ripple).abs() < 1e-9);
+        assert!((f.magnitude_at(2000.) - ripple).abs() < 1e-9);
+        (1..200).for_each(|n| {
+            let mag = f.magnitude_at(n as f64 * 10.);
+            assert!(
+                mag < 1. + 1e-9 && mag > ripple - 1e-9,
+                "{} at {}",
+                mag,
+                n * 10
+            );
+        });
+        // steeper than the Butterworth of the same order
+        let mut butterworth = IIRBiquadFilter64::default();
+        butterworth.init(&48000.0, &2000.0, FilterOrder::Second);
+        assert!(f.magnitude_at(4000.) < butterworth.magnitude_at(4000.) / 2.);
+
+        f.set_filter_type(FilterType::Highpass);
+        assert!((f.magnitude_at(23999.) - ripple).abs() < 1e-6);
+        assert!((f.magnitude_at(2000.) - ripple).abs() < 1e-9);
+
+        let floor = 10_f64.powf(-40. / 20.);
+        let mut f = IIRBiquadFilter64::new(FilterType::Lowpass);
+        f.set_design(FilterDesign::ChebyshevII {
+            attenuation_db: 40.0,
+        });
+        f.init(&48000.0, &4000.0, FilterOrder::Third);
+        assert!((f.magnitude_at(1e-3) - 1.).abs() < 1e-9);
+        assert!((f.magnitude_at(4000.) - floor).abs() < 1e-9);
+        (400..2400).for_each(|n| {
+            let mag = f.magnitude_at(n as f64 * 10.);
+            assert!(mag < floor + 1e-9, "{} at {}", mag, n * 10);
+        });
+        assert!(f.poles_zeros().stability_margin() > 0.0);
+
+        f.set_filter_type(FilterType::Highpass);
+        assert!((f.magnitude_at(23999.) - 1.).abs() < 1e-6);
+        assert!(f.magnitude_at(3000.) < floor + 1e-9);
+
+        // only the lowpass and highpass change
+        f.set_filter_type(FilterType::Bandpass);
+        let mut bandpass = IIRBiquadFilter64::new(FilterType::Bandpass);
+        bandpass.init(&48000.0, &4000.0, FilterOrder::Third);
+        assert_eq!(f.coefs, bandpass.coefs);
+    }
+
+    #[test]
+    fn test_cutoff_smoothing() {
+        let mut f = IIRBiquadFilter64::default();
+        f.init(&48000.0, &1000.0, FilterOrder::Second);
+        f.set_cutoff_smoothing(64);
+        assert_eq!(f.get_cutoff_smoothing(), 64);
+        let from = f.coefs;
+        f.set_cutoff(4000.0);
+        assert_eq!(f.coefs, from);
+
+        let mut target = IIRBiquadFilter64::default();
+        target.init(&48000.0, &4000.0, FilterOrder::Second);
+
+        let mut block = [0.5; 32];
+        f.process_block(&mut block);
+        (0..2).for_each(|i| {
+            (0..5).for_each(|j| {
+                let halfway = (from[i][j] + target.coefs[i][j]) / 2.;
+                assert!((f.coefs[i][j] - halfway).abs() < 1e-12);
+            })
+        });
+        assert!(f.poles_zeros().stability_margin() > 0.0);
+        let mut sample = 0.5;
+        (0..32).for_each(|_| f.process_sample(&mut sample));
+        assert_eq!(f.coefs, target.coefs);
+
+        // the other setters and reset don't ramp
+        f.set_cutoff(1000.0);
+        f.set_order(FilterOrder::Second);
+        target.set_cutoff(1000.0);
+        assert_eq!(f.coefs, target.coefs);
+        f.set_cutoff(4000.0);
+        f.reset();
+        target.set_cutoff(4000.0);
+        assert_eq!(f.coefs, target.coefs);
+    }
+
+    #[test]
+    fn test_process_block_modulated() {
+        let input: Vec<f64> = (0..256).map(|n| (n as f64 * 0.3).sin()).collect();
+        let sweep: Vec<f64> = (0..256).map(|n| 200. + 80. * n as f64).collect();
+        [
+            FilterType::Lowpass,
+            FilterType::Highpass,
+            FilterType::Bandpass,
+            FilterType::Bandreject,
+            FilterType::Peak {
+                gain_db: 6.0,
+                q: 2.0,
+            },
+        ]
+        .iter()
+        .for_each(|ft| {
+            let mut modulated = IIRBiquadFilter64::new(*ft);
+            modulated.init(&48000.0, &1000.0, FilterOrder::Third);
+            let mut stepped = IIRBiquadFilter64::new(*ft);
+            stepped.init(&48000.0, &1000.0, FilterOrder::Third);
+
+            let mut out = input.clone();
+            modulated.process_block_modulated(&mut out, &sweep);
+            let expected: Vec<f64> = input
+                .iter()
+                .zip(sweep.iter())
+                .map(|(x, fc)| {
+                    let mut y = *x;
+                    stepped.set_cutoff(*fc);
+                    stepped.process_sample(&mut y);
+                    y
+                })
+                .collect();
+            assert_eq!(out, expected, "{:?}", ft);
+            assert_eq!(modulated.get_current_cutoff(), 200. + 80. * 255.);
+            assert_eq!(modulated.coefs, stepped.coefs);
+        });
     }
 
     #[test]
@@ -304,6 +2352,111 @@ mod tests {
             .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "{} {}", a, b));
     }
 
+    #[test]
+    fn test_settings_before_init() {
+        let mut expected = IIRBiquadFilter::default();
+        expected.init(&48000.0, &2500.0, FilterOrder::Second);
+
+        let mut f = IIRBiquadFilter::new(FilterType::Lowpass);
+        f.set_cutoff(100.0);
+        f.set_order(FilterOrder::First);
+        f.init(&48000.0, &2500.0, FilterOrder::Second);
+
+        assert_eq!(f.coefs, expected.coefs);
+        assert_eq!(f.get_order(), FilterOrder::Second);
+        assert_eq!(f.get_filter_type(), FilterType::Lowpass);
+    }
+
+    #[test]
+    fn test_settings_after_init() {
+        let mut expected = IIRBiquadFilter::default();
+        expected.init(&48000.0, &2500.0, FilterOrder::Second);
+
+        let mut f = IIRBiquadFilter::default();
+        f.init(&48000.0, &1000.0, FilterOrder::First);
+        f.set_order(FilterOrder::Second);
+        f.set_cutoff(2500.0);
+        f.set_filter_type(FilterType::Lowpass);
+
+        assert_eq!(f.coefs, expected.coefs);
+
+        let mut g = IIRBiquadFilter::default();
+        g.init(&48000.0, &1000.0, FilterOrder::First);
+        g.set_cutoff(2500.0);
+        g.set_order(FilterOrder::Second);
+
+        assert_eq!(g.coefs, expected.coefs);
+        assert_eq!(g.get_current_cutoff(), 2500.0);
+    }
+
+    #[test]
+    fn test_order_changes() {
+        let mut f = IIRBiquadFilter::default();
+        f.init(&48000.0, &2500.0, FilterOrder::Second);
+        f.set_order(FilterOrder::First);
+
+        let mut expected = IIRBiquadFilter::default();
+        expected.init(&48000.0, &2500.0, FilterOrder::First);
+        assert_eq!(f.coefs, expected.coefs);
+        assert_eq!(f.coefs[1], [0.0_f32; 5]);
+
+        // coming back to second order starts the second section from rest
+        let mut sig = [1.0_f32; 16];
+        f.process_block(&mut sig);
+        f.states[1] = [0.5, 0.5];
+        f.set_order(FilterOrder::Second);
+        assert_eq!(f.states[1], [0.0_f32; 2]);
+        assert_ne!(f.states[0], [0.0_f32; 2]);
+    }
+
+    #[test]
+    fn test_f64_matches_f32() {
+        let mut f = IIRBiquadFilter::default();
+        f.init(&48000.0, &2500.0, FilterOrder::Second);
+        let mut g = IIRBiquadFilter64::default();
+        g.init(&48000.0, &2500.0, FilterOrder::Second);
+
+        let mut sig: Vec<f32> = RANDOM_NORMAL_480.to_vec();
+        let mut sig_64: Vec<f64> = RANDOM_NORMAL_480.iter().map(|v| *v as f64).collect();
+        f.process_block(&mut sig);
+        g.process_block(&mut sig_64);
+
+        sig.iter()
+            .zip(sig_64.iter())
+            .for_each(|(a, b)| assert!((*a as f64 - b).abs() < 1e-4, "{} {}", a, b));
+    }
+
+    #[test]
+    fn test_poles_zeros() {
+        let mut f = IIRBiquadFilter64::default();
+        f.init(&48000.0, &2500.0, FilterOrder::Second);
+        let pz = f.poles_zeros();
+        assert_eq!(pz.poles.len(), 4);
+        assert_eq!(pz.zeros.len(), 4);
+
+        // lowpass zeros all sit at nyquist, each conjugate pair multiplies out to a2
+        pz.zeros
+            .iter()
+            .for_each(|(re, im)| assert!((re + 1.).abs() < 1e-6 && im.abs() < 1e-6));
+        pz.poles
+            .chunks(2)
+            .zip(f.coefs.iter())
+            .for_each(|(pair, c)| {
+                let (re, im) = pair[0];
+                assert_eq!(pair[1], (re, -im));
+                assert!((re * re + im * im - c[A2]).abs() < 1e-12);
+                assert!((-2. * re - c[A1]).abs() < 1e-12);
+            });
+        assert!(pz.stability_margin() > 0.0 && pz.stability_margin() < 0.5);
+
+        // the higher Q section rings longer, its poles lie closer to the unit circle
+        let radius = |(re, im): (f64, f64)| (re * re + im * im).sqrt();
+        assert!(radius(pz.poles[2]) > radius(pz.poles[0]));
+
+        f.set_order(FilterOrder::First);
+        assert_eq!(f.poles_zeros().poles.len(), 2);
+    }
+
     const RANDOM_NORMAL_480: [f32; 480] = [
         8.39230795e-01,
         -9.16539688e-01,
@@ -1295,4 +3448,137 @@ mod tests {
             .zip(expected_result.into_iter())
             .for_each(|(a, b)| assert!((a - b).abs() < 1e-4, "{} {}", a, b));
     }
+
+    #[test]
+    fn test_matched_discretization() {
+        let fs = 44100.0;
+        // analog prototypes at x = f / fc
+        let resonance = |x: f64, q: f64| ((1. - x * x).powi(2) + (x / q).powi(2)).sqrt();
+        let analog = |ft: FilterType, x: f64| match ft {
+            FilterType::Lowpass => 1. / resonance(x, 2.),
+            FilterType::Highpass => x * x / resonance(x, 2.),
+            FilterType::Bandpass => x / 2. / resonance(x, 2.),
+            _ => {
+                let a = 10_f64.powf(12. / 40.);
+                resonance(x, 1. / a) / resonance(x, a)
+            }
+        };
+        [
+            FilterType::Lowpass,
+            FilterType::Highpass,
+            FilterType::Bandpass,
+            FilterType::Peak {
+                gain_db: 12.0,
+                q: 1.0,
+            },
+        ]
+        .iter()
+        .for_each(|ft| {
+            let mut bilinear = IIRBiquadFilter64::new(*ft);
+            bilinear.set_q(2.0);
+            bilinear.init(&fs, &15000.0, FilterOrder::First);
+            let mut matched = IIRBiquadFilter64::new(*ft);
+            matched.set_q(2.0);
+            matched.set_discretization(Discretization::Matched);
+            assert_eq!(matched.get_discretization(), Discretization::Matched);
+            matched.init(&fs, &15000.0, FilterOrder::First);
+
+            let at_cutoff = analog(*ft, 1.);
+            assert!(
+                (matched.magnitude_at(15000.) - at_cutoff).abs() < 1e-9,
+                "{:?}",
+                ft
+            );
+            assert!((matched.magnitude_at(1.) - analog(*ft, 1. / 15000.)).abs() < 1e-3);
+            // cramping shows most near Nyquist
+            [18000., 21000.].iter().for_each(|f| {
+                let expected = analog(*ft, f / 15000.);
+                let matched_error = (matched.magnitude_at(*f) - expected).abs();
+                let bilinear_error = (bilinear.magnitude_at(*f) - expected).abs();
+                assert!(
+                    matched_error < bilinear_error / 2.,
+                    "{:?} {} {} {}",
+                    ft,
+                    f,
+                    matched_error,
+                    bilinear_error
+                );
+            });
+            assert!(matched.is_stable());
+        });
+
+        // the other types ignore it, a cascade splits the peak gain
+        let mut f = IIRBiquadFilter64::new(FilterType::Peak {
+            gain_db: 12.0,
+            q: 1.0,
+        });
+        f.set_discretization(Discretization::Matched);
+        f.init(&fs, &1000.0, FilterOrder::Second);
+        assert!((f.magnitude_at(1000.) - 10_f64.powf(12. / 20.)).abs() < 1e-9);
+        let mut shelf = IIRBiquadFilter64::new(FilterType::LowShelf);
+        let bilinear_coefs = shelf.coefs;
+        shelf.set_discretization(Discretization::Matched);
+        assert_eq!(shelf.coefs, bilinear_coefs);
+
+        // and the modulated path keeps the matched sections
+        let mut f = IIRBiquadFilter64::new(FilterType::Lowpass);
+        f.set_discretization(Discretization::Matched);
+        f.init(&fs, &1000.0, FilterOrder::Second);
+        f.process_block_modulated(&mut [0.0; 4], &[12000.0; 4]);
+        let mut reference = IIRBiquadFilter64::new(FilterType::Lowpass);
+        reference.set_discretization(Discretization::Matched);
+        reference.init(&fs, &12000.0, FilterOrder::Second);
+        assert_eq!(f.coefs, reference.coefs);
+    }
+
+    #[test]
+    fn test_output_gain() {
+        let input: Vec<f64> = (0..2048).map(|n| (n as f64 * 0.11).sin()).collect();
+        let mut unity = IIRBiquadFilter64::new(FilterType::Peak {
+            gain_db: 6.0,
+            q: 1.0,
+        });
+        unity.init(&48000.0, &1000.0, FilterOrder::Second);
+        let mut expected = input.clone();
+        unity.process_block(&mut expected);
+
+        let mut f = IIRBiquadFilter64::new(FilterType::Peak {
+            gain_db: 6.0,
+            q: 1.0,
+        });
+        f.init(&48000.0, &1000.0, FilterOrder::Second);
+        f.set_output_gain_smoothing_ms(0.0);
+        f.set_output_gain_db(-6.0);
+        assert_eq!(f.get_output_gain_db(), -6.0);
+        let mut out = input.clone();
+        f.process_block(&mut out);
+        let half = db_to_gain(-6.0) as f64;
+        out.iter()
+            .zip(expected.iter())
+            .for_each(|(y, e)| assert!((y - e * half).abs() < 1e-12));
+
+        // glides from the old gain, a reset lands on the new one
+        f.reset();
+        f.set_output_gain_smoothing_ms(DEFAULT_OUTPUT_GAIN_SMOOTHING_MS);
+        f.set_output_gain_db(0.0);
+        let mut impulse = [1.0, 0.0, 0.0];
+        f.process_block(&mut impulse);
+        let mut reference = [1.0, 0.0, 0.0];
+        unity.reset();
+        unity.process_block(&mut reference);
+        assert!(impulse[0] > reference[0] * half && impulse[0] < reference[0]);
+        f.reset();
+        let mut impulse = [1.0];
+        f.process_block(&mut impulse);
+        assert_eq!(impulse[0], reference[0]);
+
+        // part of the preset, restored without a glide
+        f.set_output_gain_db(-12.0);
+        let restored = IIRBiquadFilter64::from_settings(&f.settings());
+        assert_eq!(restored.get_output_gain_db(), -12.0);
+        let mut sample = 1.0;
+        let mut restored = restored;
+        restored.process_sample(&mut sample);
+        assert!((sample - reference[0] * db_to_gain(-12.0) as f64).abs() < 1e-7);
+    }
 }
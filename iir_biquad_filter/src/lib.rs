@@ -1,4 +1,12 @@
+#![feature(portable_simd)]
+
+pub mod bank;
+pub mod cascade;
+pub mod crossover;
+pub mod graphic_eq;
 pub mod iir_biquad_filter;
+pub mod multichannel;
+pub mod svf;
 
 // pub use iir_biquad_filter::FilterOrder;
 // pub use iir_biquad_filter::IIRBiquadFilter;
@@ -0,0 +1,163 @@
+use sample::{MemoryFootprint, Sample};
+
+use crate::iir_biquad_filter::{FilterType, GenericIIRBiquadFilter, MAX_SECTIONS};
+
+pub type MultiChannelBiquad<const N: usize> = GenericMultiChannelBiquad<f32, N>;
+pub type MultiChannelBiquad64<const N: usize> = GenericMultiChannelBiquad<f64, N>;
+
+/// `N` channels of one filter, every channel has its own state but they all run the same
+/// coefficients, so a cutoff change reaches all of them on the same sample
+#[derive(Debug)]
+pub struct GenericMultiChannelBiquad<T: Sample, const N: usize> {
+    filter: GenericIIRBiquadFilter<T>,
+    states: [[[T; 2]; MAX_SECTIONS]; N],
+    running: usize,
+}
+
+impl<T: Sample, const N: usize> GenericMultiChannelBiquad<T, N> {
+    pub fn new(ft: FilterType) -> Self {
+        let filter = GenericIIRBiquadFilter::new(ft);
+        let running = filter.num_sections();
+        GenericMultiChannelBiquad {
+            filter,
+            states: [[[T::ZERO; 2]; MAX_SECTIONS]; N],
+            running,
+        }
+    }
+
+    /// The shared settings, configure the filter through this. Its own state isn't used.
+    pub fn filter(&self) -> &GenericIIRBiquadFilter<T> {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut GenericIIRBiquadFilter<T> {
+        &mut self.filter
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+        self.states = [[[T::ZERO; 2]; MAX_SECTIONS]; N];
+    }
+
+    /// One sample of every channel
+    pub fn process_frame(&mut self, frame: &mut [T; N]) {
+        let sections = self.sync_sections();
+        self.run_frame(sections, frame.iter_mut());
+    }
+
+    /// One block per channel, all the same length
+    pub fn process_block(&mut self, channels: &mut [&mut [T]; N]) {
+        let len = channels.first().map_or(0, |c| c.len());
+        assert!(channels.iter().all(|c| c.len() == len));
+        let sections = self.sync_sections();
+        (0..len).for_each(|n| self.run_frame(sections, channels.iter_mut().map(|c| &mut c[n])));
+    }
+
+    /// `block` holds whole frames of `N` interleaved channels
+    pub fn process_interleaved(&mut self, block: &mut [T]) {
+        assert!(block.len().is_multiple_of(N));
+        let sections = self.sync_sections();
+        block
+            .chunks_exact_mut(N)
+            .for_each(|frame| self.run_frame(sections, frame.iter_mut()));
+    }
+
+    #[inline]
+    fn run_frame<'a, I: Iterator<Item = &'a mut T>>(&mut self, sections: usize, frame: I)
+    where
+        T: 'a,
+    {
+        self.filter.advance_ramp();
+        let coefs = self.filter.coefs();
+        frame.zip(self.states.iter_mut()).for_each(|(x, state)| {
+            *x = GenericIIRBiquadFilter::run_sections(coefs, sections, state, *x)
+        });
+    }
+
+    /// A section that was not running has no meaningful state
+    fn sync_sections(&mut self) -> usize {
+        let sections = self.filter.num_sections();
+        let running = self.running;
+        self.states.iter_mut().for_each(|state| {
+            state
+                .iter_mut()
+                .take(sections)
+                .skip(running)
+                .for_each(|s| *s = [T::ZERO; 2])
+        });
+        self.running = sections;
+        sections
+    }
+}
+
+impl<T: Sample, const N: usize> MemoryFootprint for GenericMultiChannelBiquad<T, N> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iir_biquad_filter::{FilterOrder, IIRBiquadFilter64};
+
+    fn signal(len: usize, rate: f64) -> Vec<f64> {
+        (0..len).map(|n| (n as f64 * rate).sin()).collect()
+    }
+
+    #[test]
+    fn channels_match_separate_filters() {
+        let mut stereo = MultiChannelBiquad64::<2>::new(FilterType::Lowpass);
+        stereo
+            .filter_mut()
+            .init(&48000.0, &2000.0, FilterOrder::Second);
+        let mut left = IIRBiquadFilter64::new(FilterType::Lowpass);
+        left.init(&48000.0, &2000.0, FilterOrder::Second);
+        let mut right = IIRBiquadFilter64::new(FilterType::Lowpass);
+        right.init(&48000.0, &2000.0, FilterOrder::Second);
+
+        let (mut l, mut r) = (signal(128, 0.1), signal(128, 0.7));
+        let (mut expected_l, mut expected_r) = (l.clone(), r.clone());
+        stereo.process_block(&mut [&mut l[..], &mut r[..]]);
+        left.process_block(&mut expected_l);
+        right.process_block(&mut expected_r);
+        assert_eq!(l, expected_l);
+        assert_eq!(r, expected_r);
+
+        // a change reaches both channels, and the new section starts from silence
+        stereo.filter_mut().set_order(FilterOrder::Third);
+        left.set_order(FilterOrder::Third);
+        right.set_order(FilterOrder::Third);
+        let mut interleaved: Vec<f64> = (0..64).map(|n| (n as f64 * 0.2).cos()).collect();
+        let (mut expected_l, mut expected_r): (Vec<f64>, Vec<f64>) =
+            interleaved.chunks(2).map(|f| (f[0], f[1])).unzip();
+        stereo.process_interleaved(&mut interleaved);
+        left.process_block(&mut expected_l);
+        right.process_block(&mut expected_r);
+        interleaved.chunks(2).enumerate().for_each(|(n, f)| {
+            assert_eq!(f, [expected_l[n], expected_r[n]]);
+        });
+    }
+
+    #[test]
+    fn ramp_advances_once_per_frame() {
+        let mut quad = MultiChannelBiquad64::<4>::new(FilterType::Highpass);
+        quad.filter_mut().init(&48000.0, &500.0, FilterOrder::First);
+        quad.filter_mut().set_cutoff_smoothing(8);
+        quad.filter_mut().set_cutoff(5000.0);
+        let mut mono = IIRBiquadFilter64::new(FilterType::Highpass);
+        mono.init(&48000.0, &500.0, FilterOrder::First);
+        mono.set_cutoff_smoothing(8);
+        mono.set_cutoff(5000.0);
+
+        let mut input = signal(16, 0.3);
+        let mut frame = [0.0; 4];
+        input.iter_mut().for_each(|x| {
+            frame = [*x; 4];
+            quad.process_frame(&mut frame);
+            mono.process_sample(x);
+            assert!(frame.iter().all(|y| y == x));
+        });
+
+        quad.reset();
+        let mut frame = [0.0; 4];
+        quad.process_frame(&mut frame);
+        assert_eq!(frame, [0.0; 4]);
+    }
+}
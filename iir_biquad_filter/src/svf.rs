@@ -0,0 +1,228 @@
+use sample::{MemoryFootprint, Sample};
+
+/// Which output `process_block` keeps
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SvfMode {
+    Lowpass,
+    Highpass,
+    /// Constant 0 dB peak gain at the cutoff, like the biquad bandpass
+    Bandpass,
+    Notch,
+}
+
+/// Every output of one sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvfOutputs<T: Sample> {
+    pub low: T,
+    pub high: T,
+    pub band: T,
+    pub notch: T,
+}
+
+impl<T: Sample> SvfOutputs<T> {
+    pub fn get(&self, mode: SvfMode) -> T {
+        match mode {
+            SvfMode::Lowpass => self.low,
+            SvfMode::Highpass => self.high,
+            SvfMode::Bandpass => self.band,
+            SvfMode::Notch => self.notch,
+        }
+    }
+}
+
+pub type StateVariableFilter = GenericStateVariableFilter<f32>;
+pub type StateVariableFilter64 = GenericStateVariableFilter<f64>;
+
+/// Zero delay feedback (topology preserving transform) state variable filter. With a fixed
+/// cutoff the outputs match the biquad types, but the state is the integrator outputs, so
+/// the cutoff and Q can move every sample without the bursts a biquad gives.
+#[derive(Debug)]
+pub struct GenericStateVariableFilter<T: Sample> {
+    sample_rate: T,
+    cutoff_freq: T,
+    q: T,
+    k: T,
+    a1: T,
+    a2: T,
+    a3: T,
+    ic1: T,
+    ic2: T,
+}
+
+impl<T: Sample> GenericStateVariableFilter<T> {
+    pub fn new(sample_rate: T, cutoff_freq: T, q: T) -> Self {
+        let mut svf = GenericStateVariableFilter {
+            sample_rate,
+            cutoff_freq,
+            q,
+            k: T::ZERO,
+            a1: T::ZERO,
+            a2: T::ZERO,
+            a3: T::ZERO,
+            ic1: T::ZERO,
+            ic2: T::ZERO,
+        };
+        svf.gen_coefficients();
+        svf
+    }
+
+    pub fn set_cutoff(&mut self, cutoff_freq: T) {
+        self.cutoff_freq = cutoff_freq;
+        self.gen_coefficients();
+    }
+
+    pub fn get_current_cutoff(&self) -> T {
+        self.cutoff_freq
+    }
+
+    /// The state is the integrator outputs, so it carries over to the new rate
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.gen_coefficients();
+    }
+
+    pub fn get_sample_rate(&self) -> T {
+        self.sample_rate
+    }
+
+    pub fn set_q(&mut self, q: T) {
+        self.q = q;
+        self.gen_coefficients();
+    }
+
+    pub fn get_q(&self) -> T {
+        self.q
+    }
+
+    pub fn reset(&mut self) {
+        self.ic1 = T::ZERO;
+        self.ic2 = T::ZERO;
+    }
+
+    #[inline]
+    fn gen_coefficients(&mut self) {
+        let g = (T::PI * self.cutoff_freq / self.sample_rate).tan();
+        self.k = T::ONE / self.q;
+        self.a1 = T::ONE / (T::ONE + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, x: T) -> SvfOutputs<T> {
+        let two = T::from_f32(2.);
+        let v3 = x - self.ic2;
+        let v1 = self.a1 * self.ic1 + self.a2 * v3;
+        let v2 = self.ic2 + self.a2 * self.ic1 + self.a3 * v3;
+        self.ic1 = two * v1 - self.ic1;
+        self.ic2 = two * v2 - self.ic2;
+
+        let band = self.k * v1;
+        SvfOutputs {
+            low: v2,
+            high: x - band - v2,
+            band,
+            notch: x - band,
+        }
+    }
+
+    pub fn process_block(&mut self, input_signal: &mut [T], mode: SvfMode) {
+        input_signal
+            .iter_mut()
+            .for_each(|s| *s = self.process_sample(*s).get(mode));
+    }
+
+    /// `process_block` that leaves `input` alone
+    pub fn process_block_to(&mut self, input: &[T], output: &mut [T], mode: SvfMode) {
+        assert_eq!(input.len(), output.len());
+        output
+            .iter_mut()
+            .zip(input.iter())
+            .for_each(|(y, x)| *y = self.process_sample(*x).get(mode));
+    }
+
+    /// `cutoff` holds the cutoff for each sample, the filter is left at the last one
+    pub fn process_block_modulated(&mut self, input_signal: &mut [T], cutoff: &[T], mode: SvfMode) {
+        assert_eq!(input_signal.len(), cutoff.len());
+        input_signal
+            .iter_mut()
+            .zip(cutoff.iter())
+            .for_each(|(s, fc)| {
+                self.set_cutoff(*fc);
+                *s = self.process_sample(*s).get(mode);
+            });
+    }
+}
+
+impl<T: Sample> MemoryFootprint for GenericStateVariableFilter<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iir_biquad_filter::{FilterOrder, FilterType, IIRBiquadFilter64};
+
+    #[test]
+    fn matches_biquad_types() {
+        let input: Vec<f64> = (0..512)
+            .map(|n| (n as f64 * 0.05).sin() + 0.5 * (n as f64 * 1.3).cos())
+            .collect();
+        [
+            (SvfMode::Lowpass, FilterType::Lowpass),
+            (SvfMode::Highpass, FilterType::Highpass),
+            (SvfMode::Bandpass, FilterType::Bandpass),
+            (SvfMode::Notch, FilterType::Bandreject),
+        ]
+        .iter()
+        .for_each(|(mode, ft)| {
+            let mut svf = StateVariableFilter64::new(44100.0, 3000.0, 2.5);
+            svf.set_sample_rate(48000.0);
+            assert_eq!(svf.get_sample_rate(), 48000.0);
+            let mut biquad = IIRBiquadFilter64::new(*ft);
+            biquad.set_q(2.5);
+            biquad.init(&48000.0, &3000.0, FilterOrder::First);
+
+            let mut out = vec![0.0; input.len()];
+            let mut expected = input.clone();
+            svf.process_block_to(&input, &mut out, *mode);
+            let mut in_place = input.clone();
+            svf.reset();
+            svf.process_block(&mut in_place, *mode);
+            assert_eq!(in_place, out);
+            biquad.process_block(&mut expected);
+            out.iter()
+                .zip(expected.iter())
+                .for_each(|(a, b)| assert!((a - b).abs() < 1e-12, "{:?} {} {}", mode, a, b));
+        });
+    }
+
+    #[test]
+    fn audio_rate_sweep_stays_bounded() {
+        let mut svf = StateVariableFilter64::new(48000.0, 1000.0, 8.0);
+        assert_eq!(svf.get_q(), 8.0);
+        // jumps between 100 Hz and 20 kHz every 16 samples
+        let cutoff: Vec<f64> = (0..48000)
+            .map(|n| if (n / 16) % 2 == 0 { 100. } else { 20000. })
+            .collect();
+        let mut block: Vec<f64> = (0..48000).map(|n| (n as f64 * 0.02).sin()).collect();
+        svf.process_block_modulated(&mut block, &cutoff, SvfMode::Lowpass);
+        assert_eq!(svf.get_current_cutoff(), 20000.);
+        assert!(
+            block.iter().all(|y| y.abs() < 20.),
+            "{:?}",
+            block.iter().fold(0., |m: f64, y| m.max(y.abs()))
+        );
+
+        svf.reset();
+        let out = svf.process_sample(0.0);
+        assert_eq!(
+            out,
+            SvfOutputs {
+                low: 0.0,
+                high: 0.0,
+                band: 0.0,
+                notch: 0.0
+            }
+        );
+    }
+}
@@ -1,22 +1,238 @@
 #[cfg(any(feature = "all", feature = "nl_adaa"))]
 pub use adaa_nl::adaa::AntiderivativeOrder;
 #[cfg(feature = "all")]
-pub use adaa_nl::adaa::NonlinearProcessor;
-#[cfg(feature = "all")]
 pub use adaa_nl::adaa::ProcessorState;
 #[cfg(feature = "all")]
 pub use adaa_nl::adaa::ProcessorStyle;
 #[cfg(feature = "all")]
-pub use circular_buffer::circular_buffer::{CircularDelayBuffer, TiledConv};
+pub use adaa_nl::adaa::MAX_INPUT_LEVEL;
+#[cfg(feature = "all")]
+pub use adaa_nl::adaa::{GenericNonlinearProcessor, NonlinearProcessor, NonlinearProcessor64};
+#[cfg(feature = "all")]
+pub use chain::processor::{Chain, Gain, Oversampled, Processor};
+#[cfg(feature = "all")]
+pub use chain::spec::{ChainSpec, ChainSpecError, NodeSpec, SaturatorStyle};
+#[cfg(feature = "all")]
+pub use chaos_generator::chaos_generator::ChaosGenerator;
+#[cfg(feature = "all")]
+pub use circular_buffer::circular_buffer::{
+    CircularDelayBuffer, GenericCircularDelayBuffer, TiledConv,
+};
+#[cfg(feature = "all")]
+pub use circular_buffer::static_buffer::{
+    StaticChain, StaticDelayBuffer, StaticFir, StaticProcessor,
+};
+#[cfg(feature = "all")]
+pub use control_rate::control_rate::{Aggregation, ControlRateDecimator};
+#[cfg(feature = "all")]
+pub use dc_filter::dc_filter::{DCFilter, DCFilter64, GenericDCFilter};
+#[cfg(feature = "all")]
+pub use envelope::{
+    tempo_synced_steps, velocity_depth, BreakpointEnvelope, BreakpointEnvelope64, Chained, Curve,
+    DecibelEnvelope, DecibelEnvelope64, Delayed, Env, EnvelopeScheduler, GenericBreakpointEnvelope,
+    GenericDecibelEnvelope, GenericLinearEnvelope, GenericRcEnvelope, GenericSCurveEnvelope,
+    GenericSmoother, Inverted, LinearEnvelope, LinearEnvelope64, Looping, Mapped, RcEnvelope,
+    RcEnvelope64, SCurveEnvelope, SCurveEnvelope64, Scaled, Smoother, Smoother64, DEFAULT_DB_FLOOR,
+    RC_ATTACK_OVERSHOOT, RC_DECAY_OVERSHOOT,
+};
+#[cfg(feature = "all")]
+pub use hot_swap::hot_swap::{HotSwapSender, HotSwappable};
+#[cfg(feature = "all")]
+pub use iir_biquad_filter::bank::BiquadBank;
 #[cfg(feature = "all")]
-pub use dc_filter::dc_filter::DCFilter;
+pub use iir_biquad_filter::cascade::{BiquadCascade, BiquadCascade64, GenericBiquadCascade};
+#[cfg(feature = "all")]
+pub use iir_biquad_filter::crossover::{
+    CrossoverOrder, GenericLinkwitzRileyCrossover, LinkwitzRileyCrossover, LinkwitzRileyCrossover64,
+};
+#[cfg(feature = "all")]
+pub use iir_biquad_filter::graphic_eq::{GenericGraphicEq, GraphicEq, GraphicEq64, GraphicEqBands};
 #[cfg(feature = "all")]
 pub use iir_biquad_filter::iir_biquad_filter::FilterOrder;
 #[cfg(feature = "all")]
-pub use iir_biquad_filter::iir_biquad_filter::IIRBiquadFilter;
+pub use iir_biquad_filter::iir_biquad_filter::FilterType;
+#[cfg(feature = "all")]
+pub use iir_biquad_filter::iir_biquad_filter::{
+    BiquadError, BiquadSettings, Discretization, FilterDesign, GenericIIRBiquadFilter,
+    IIRBiquadFilter, IIRBiquadFilter64, PolesZeros, Topology, DEFAULT_OUTPUT_GAIN_SMOOTHING_MS,
+};
+#[cfg(feature = "all")]
+pub use iir_biquad_filter::multichannel::{
+    GenericMultiChannelBiquad, MultiChannelBiquad, MultiChannelBiquad64,
+};
+#[cfg(feature = "all")]
+pub use iir_biquad_filter::svf::{
+    GenericStateVariableFilter, StateVariableFilter, StateVariableFilter64, SvfMode, SvfOutputs,
+};
+#[cfg(feature = "all")]
+pub use oversampler::decimator::Decimator;
 #[cfg(feature = "all")]
 pub use oversampler::oversample::OversampleFactor;
 #[cfg(feature = "all")]
 pub use oversampler::oversample::{Oversample, MAX_LATENCY_AMT};
 #[cfg(feature = "all")]
-pub use window::{hann, kaiser, sinc};
+pub use oversampler::polyphase::{decompose, PolyphaseBranch};
+#[cfg(feature = "all")]
+pub use param_range::param_range::{
+    db_to_gain, gain_to_db, ParamRange, Taper, CUTOFF_HZ, DRIVE_DB, OUTPUT_DB,
+};
+#[cfg(feature = "all")]
+pub use sample::{MemoryFootprint, Sample};
+#[cfg(feature = "all")]
+pub use silence_detector::silence_detector::SilenceDetector;
+#[cfg(feature = "all")]
+pub use window::overlap_add::OverlapAdd;
+#[cfg(feature = "all")]
+pub use window::{
+    bessel_i0, blackman, blackman_harris, blackman_harris_into, blackman_harris_iter,
+    blackman_into, blackman_iter, chebyshev, chebyshev_into, check_cola, flat_top, flat_top_into,
+    flat_top_iter, gaussian, gaussian_into, gaussian_iter, hann, hann_into, hann_iter, hilbert,
+    hilbert_into, hilbert_iter, kaiser, kaiser_beta, kaiser_into, kaiser_iter, kaiser_order,
+    normalize, nuttall, nuttall_into, nuttall_iter, recommended_hops, sinc, sinc_into, sinc_iter,
+    sinc_scaled, sinc_scaled_into, sinc_scaled_iter, tukey, tukey_into, tukey_iter, Normalization,
+    WindowInfo,
+};
+
+#[cfg(all(test, feature = "all"))]
+mod tests {
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const MEMBER_CRATES: &[&str] = &[
+        "adaa_nl",
+        "chain",
+        "chaos_generator",
+        "circular_buffer",
+        "control_rate",
+        "dc_filter",
+        "envelope",
+        "hot_swap",
+        "iir_biquad_filter",
+        "oversampler",
+        "param_range",
+        "sample",
+        "silence_detector",
+        "window",
+    ];
+
+    const ITEM_KINDS: &[&str] = &["struct", "enum", "trait", "fn", "const", "type", "static"];
+
+    fn ident_tokens(src: &str) -> impl Iterator<Item = &str> {
+        src.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|t| !t.is_empty())
+    }
+
+    /// lib.rs plus the files of every `pub mod` it declares
+    fn public_module_files(crate_dir: &Path) -> Vec<PathBuf> {
+        let lib = crate_dir.join("src").join("lib.rs");
+        let lib_src = fs::read_to_string(&lib).unwrap();
+        let mut files = vec![lib];
+        lib_src
+            .lines()
+            .filter_map(|l| l.strip_prefix("pub mod "))
+            .filter_map(|l| l.strip_suffix(';'))
+            .for_each(|m| files.push(crate_dir.join("src").join(format!("{}.rs", m.trim()))));
+        files
+    }
+
+    /// Names of the unindented (module level) `pub` items in a source file
+    fn public_items(src: &str) -> Vec<String> {
+        src.lines()
+            .filter_map(|l| l.strip_prefix("pub "))
+            .filter_map(|l| {
+                let mut tokens = ident_tokens(l);
+                let kind = tokens.next()?;
+                if ITEM_KINDS.contains(&kind) {
+                    tokens.next().map(String::from)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_public_item_is_reexported() {
+        let facade = include_str!("lib.rs");
+        let reexported: HashSet<&str> = facade
+            .lines()
+            .take_while(|l| !l.starts_with("#[cfg(all(test"))
+            .filter(|l| !l.starts_with("#["))
+            .flat_map(ident_tokens)
+            .collect();
+
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let missing: Vec<String> = MEMBER_CRATES
+            .iter()
+            .flat_map(|c| public_module_files(&workspace.join(c)))
+            .flat_map(|f| public_items(&fs::read_to_string(&f).unwrap()))
+            .filter(|item| !reexported.contains(item.as_str()))
+            .collect();
+
+        assert!(missing.is_empty(), "not re-exported by jdsp: {:?}", missing);
+    }
+
+    // these stop compiling when a variant is added without looking at the facade.
+    // ProcessorStyle, FilterType and FilterOrder are #[non_exhaustive], out here they'd need
+    // a wildcard arm that lets any new variant through, so they're left to the exhaustive
+    // matches in their own crates.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    fn enums_are_exhaustive(
+        order: crate::AntiderivativeOrder,
+        state: crate::ProcessorState,
+        design: crate::FilterDesign,
+        crossover_order: crate::CrossoverOrder,
+        svf_mode: crate::SvfMode,
+        topology: crate::Topology,
+        discretization: crate::Discretization,
+        eq_bands: crate::GraphicEqBands,
+        factor: crate::OversampleFactor,
+        curve: crate::Curve,
+        taper: crate::Taper,
+    ) {
+        match order {
+            crate::AntiderivativeOrder::FirstOrder | crate::AntiderivativeOrder::SecondOrder => (),
+        }
+        match state {
+            crate::ProcessorState::State(_, _) => (),
+        }
+        match design {
+            crate::FilterDesign::Butterworth
+            | crate::FilterDesign::ChebyshevI { .. }
+            | crate::FilterDesign::ChebyshevII { .. } => (),
+        }
+        match crossover_order {
+            crate::CrossoverOrder::LR2 | crate::CrossoverOrder::LR4 => (),
+        }
+        match svf_mode {
+            crate::SvfMode::Lowpass
+            | crate::SvfMode::Highpass
+            | crate::SvfMode::Bandpass
+            | crate::SvfMode::Notch => (),
+        }
+        match topology {
+            crate::Topology::DirectForm1
+            | crate::Topology::TransposedDirectForm2
+            | crate::Topology::Coupled => (),
+        }
+        match discretization {
+            crate::Discretization::Bilinear | crate::Discretization::Matched => (),
+        }
+        match eq_bands {
+            crate::GraphicEqBands::Octave | crate::GraphicEqBands::ThirdOctave => (),
+        }
+        match factor {
+            crate::OversampleFactor::TwoTimes
+            | crate::OversampleFactor::FourTimes
+            | crate::OversampleFactor::EightTimes
+            | crate::OversampleFactor::SixteenTimes => (),
+        }
+        match curve {
+            crate::Curve::Linear | crate::Curve::Exponential(_) => (),
+        }
+        match taper {
+            crate::Taper::Linear | crate::Taper::Logarithmic => (),
+        }
+    }
+}
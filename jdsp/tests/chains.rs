@@ -0,0 +1,223 @@
+#![cfg(feature = "all")]
+
+use jdsp::*;
+
+const BLOCK: usize = 64;
+const FACTORS: [OversampleFactor; 4] = [
+    OversampleFactor::TwoTimes,
+    OversampleFactor::FourTimes,
+    OversampleFactor::EightTimes,
+    OversampleFactor::SixteenTimes,
+];
+
+fn sine(len: usize, step: f32, amp: f32) -> Vec<f32> {
+    (0..len).map(|i| (i as f32 * step).sin() * amp).collect()
+}
+
+fn round_trip(os: &mut Oversample, block: &mut [f32]) {
+    let mut up = vec![0.0_f32; block.len() << os.get_oversample_factor() as usize];
+    os.process_up(block, &mut up);
+    os.process_down(&up, block);
+}
+
+/// oversample -> ADAA -> DC filter -> lowpass
+struct Saturator {
+    os: Oversample,
+    nl: NonlinearProcessor,
+    dc: DCFilter,
+    lowpass: IIRBiquadFilter,
+    up: Vec<f32>,
+}
+
+impl Saturator {
+    fn new(factor: OversampleFactor) -> Self {
+        let mut nl = NonlinearProcessor::new();
+        nl.compare_and_change_state(ProcessorState::State(
+            ProcessorStyle::Tanh,
+            AntiderivativeOrder::FirstOrder,
+        ));
+        // start on tanh rather than fading out of the default style
+        nl.hard_reset();
+        let mut lowpass = IIRBiquadFilter::new(FilterType::Lowpass);
+        lowpass.init(&48000.0, &8000.0, FilterOrder::Second);
+        Saturator {
+            os: Oversample::new(factor, BLOCK),
+            nl,
+            dc: DCFilter::new(),
+            lowpass,
+            up: vec![0.0_f32; BLOCK << FACTORS.len()],
+        }
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        let up = &mut self.up[..block.len() << self.os.get_oversample_factor() as usize];
+        self.os.process_up(block, up);
+        up.iter_mut().for_each(|v| *v = self.nl.process(*v));
+        self.os.process_down(up, block);
+        block.iter_mut().for_each(|v| *v = self.dc.process(*v));
+        self.lowpass.process_block(block);
+    }
+
+    /// Transport relocation, everything back to how `new` left it
+    fn hard_reset(&mut self) {
+        self.os.reset();
+        self.nl.hard_reset();
+        self.dc.reset();
+        self.lowpass.reset();
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.os.memory_footprint()
+            + self.nl.memory_footprint()
+            + self.dc.memory_footprint()
+            + self.lowpass.memory_footprint()
+            + self.up.memory_footprint()
+    }
+
+    fn run(&mut self, input: &[f32]) -> Vec<f32> {
+        input
+            .chunks(BLOCK)
+            .flat_map(|chunk| {
+                let mut block = chunk.to_vec();
+                self.process_block(&mut block);
+                block
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn reported_latency_matches_round_trip() {
+    FACTORS.iter().for_each(|factor| {
+        let mut os = Oversample::new(*factor, BLOCK);
+        let mut impulse = vec![0.0_f32; BLOCK * 16];
+        impulse[0] = 1.0;
+        let output: Vec<f32> = impulse
+            .chunks(BLOCK)
+            .flat_map(|chunk| {
+                let mut block = chunk.to_vec();
+                round_trip(&mut os, &mut block);
+                block
+            })
+            .collect();
+
+        let peak = output
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .unwrap()
+            .0;
+        assert_eq!(peak, os.get_latency_samples(), "{:?}", factor);
+        let sum = output.iter().sum::<f32>();
+        assert!((sum - 1.0).abs() < 1e-3, "{:?}: {}", factor, sum);
+    });
+}
+
+#[test]
+fn saturator_chain_sine_response() {
+    // a hot sine with a DC offset, the chain should clip it, remove the offset and stay
+    // bounded at every factor
+    let input: Vec<f32> = sine(BLOCK * 256, 0.02, 2.0)
+        .iter()
+        .map(|v| v + 0.3)
+        .collect();
+    FACTORS.iter().for_each(|factor| {
+        let output = Saturator::new(*factor).run(&input);
+        assert!(output.iter().all(|v| v.is_finite() && v.abs() < 1.5));
+
+        // the tail is past the initial ADAA fade in and the DC filter settling
+        let tail = &output[output.len() - BLOCK * 64..];
+        let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+        let peak = tail.iter().fold(0.0_f32, |m, v| m.max(v.abs()));
+        assert!(mean.abs() < 0.05, "{:?} mean {}", factor, mean);
+        assert!(peak > 0.7 && peak < 1.3, "{:?} peak {}", factor, peak);
+    });
+}
+
+#[test]
+fn filter_reset_matches_fresh_instance() {
+    let input = sine(BLOCK * 8, 0.05, 0.8);
+    let lowpass = || {
+        let mut filter = IIRBiquadFilter::new(FilterType::Lowpass);
+        filter.init(&48000.0, &2000.0, FilterOrder::Second);
+        filter
+    };
+
+    let run = |dc: &mut DCFilter, lowpass: &mut IIRBiquadFilter| -> Vec<f32> {
+        let mut block: Vec<f32> = input.iter().map(|v| dc.process(*v)).collect();
+        lowpass.process_block(&mut block);
+        block
+    };
+
+    let (mut dc, mut used) = (DCFilter::new(), lowpass());
+    run(&mut dc, &mut used);
+    dc.reset();
+    used.reset();
+    assert_eq!(
+        run(&mut dc, &mut used),
+        run(&mut DCFilter::new(), &mut lowpass())
+    );
+}
+
+#[test]
+fn chain_footprint_is_the_oversampler_and_scratch() {
+    let chain = Saturator::new(OversampleFactor::FourTimes);
+    // the filters and the ADAA state live inline
+    assert_eq!(chain.nl.memory_footprint() + chain.dc.memory_footprint(), 0);
+    assert_eq!(
+        chain.memory_footprint(),
+        Oversample::new(OversampleFactor::TwoTimes, BLOCK).memory_footprint()
+            + (BLOCK << FACTORS.len()) * 4
+    );
+}
+
+#[test]
+fn relocation_matches_fresh_instance() {
+    let input = sine(BLOCK * 128, 0.02, 1.5);
+    FACTORS.iter().for_each(|factor| {
+        let mut used = Saturator::new(*factor);
+        used.run(&input[..BLOCK * 40]);
+        // jump while a style change is fading out
+        used.nl.compare_and_change_state(ProcessorState::State(
+            ProcessorStyle::HardClip,
+            AntiderivativeOrder::SecondOrder,
+        ));
+        used.run(&input[..BLOCK * 3]);
+        used.nl.compare_and_change_state(ProcessorState::State(
+            ProcessorStyle::Tanh,
+            AntiderivativeOrder::FirstOrder,
+        ));
+        used.hard_reset();
+
+        assert_eq!(used.run(&input), Saturator::new(*factor).run(&input));
+    });
+}
+
+#[test]
+fn factor_switch_settles_to_fresh_instance() {
+    let input = sine(BLOCK * 32, 0.03, 0.9);
+    let mut switched = Oversample::new(OversampleFactor::TwoTimes, BLOCK);
+    let mut fresh = Oversample::new(OversampleFactor::EightTimes, BLOCK);
+
+    let mut out_switched = Vec::new();
+    let mut out_fresh = Vec::new();
+    input.chunks(BLOCK).enumerate().for_each(|(i, chunk)| {
+        if i == 8 {
+            switched.set_oversample_factor(OversampleFactor::EightTimes);
+        }
+        let mut block = chunk.to_vec();
+        round_trip(&mut switched, &mut block);
+        assert!(block.iter().all(|v| v.is_finite() && v.abs() < 1.5));
+        out_switched.extend(block);
+
+        let mut block = chunk.to_vec();
+        round_trip(&mut fresh, &mut block);
+        out_fresh.extend(block);
+    });
+
+    // the stages that were idle before the switch only remember a few blocks of input
+    out_switched[BLOCK * 16..]
+        .iter()
+        .zip(out_fresh[BLOCK * 16..].iter())
+        .for_each(|(s, f)| assert!((s - f).abs() < 1e-5, "{} {}", s, f));
+}
@@ -0,0 +1,187 @@
+use circular_buffer::circular_buffer::CircularDelayBuffer;
+use sample::MemoryFootprint;
+
+use crate::oversample::{Oversample, OversampleFactor};
+
+/// Lo-fi downsampler, the signal is decimated by `factor` and held back up to the input
+/// rate. `aliasing` blends between decimating through the oversampler's anti-aliasing stages
+/// (0.0) and just dropping samples (1.0).
+#[derive(Debug)]
+pub struct Decimator {
+    block_size: usize,
+    filtered: Oversample,
+    // lines the dropped samples up with the filtered path
+    raw_delay: CircularDelayBuffer,
+    filtered_buff: Vec<f32>,
+    raw_buff: Vec<f32>,
+    aliasing: f32,
+    drive: f32,
+}
+
+impl Decimator {
+    /// `block_size` must be a multiple of 16 so every factor divides it, every call to
+    /// `process_block` has to pass exactly this many samples
+    pub fn new(factor: OversampleFactor, block_size: usize) -> Self {
+        assert!(
+            block_size.is_multiple_of(16),
+            "block size must be a multiple of 16"
+        );
+        Decimator {
+            block_size,
+            filtered: Oversample::new(factor, block_size >> factor as usize),
+            raw_delay: CircularDelayBuffer::new(Decimator::filter_latency(factor) + 1),
+            filtered_buff: vec![0.0_f32; block_size],
+            raw_buff: vec![0.0_f32; block_size],
+            aliasing: 1.0,
+            drive: 1.0,
+        }
+    }
+
+    pub fn get_factor(&self) -> OversampleFactor {
+        self.filtered.get_oversample_factor()
+    }
+
+    /// Rebuilds the filter stages, this allocates so don't call it on the audio thread
+    #[cold]
+    pub fn set_factor(&mut self, new_factor: OversampleFactor) {
+        self.filtered = Oversample::new(new_factor, self.block_size >> new_factor as usize);
+        self.raw_delay = CircularDelayBuffer::new(Decimator::filter_latency(new_factor) + 1);
+    }
+
+    /// 0.0 keeps only the filtered signal, 1.0 only the aliased one
+    pub fn set_aliasing(&mut self, amount: f32) {
+        self.aliasing = amount.clamp(0.0, 1.0);
+    }
+
+    /// Input gain in front of a tanh saturator, 1.0 still saturates hot signals slightly
+    pub fn set_drive(&mut self, gain: f32) {
+        self.drive = gain.max(0.0);
+    }
+
+    /// Clears the filter and delay history, the settings are kept
+    pub fn reset(&mut self) {
+        self.filtered.reset();
+        self.raw_delay.reset();
+    }
+
+    /// Latency of the filtered path at the decimated rate
+    fn filter_latency(factor: OversampleFactor) -> usize {
+        match factor {
+            OversampleFactor::TwoTimes => 31,
+            OversampleFactor::FourTimes => 43,
+            OversampleFactor::EightTimes => 47,
+            OversampleFactor::SixteenTimes => 48,
+        }
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        assert_eq!(block.len(), self.block_size);
+        let drive = self.drive;
+        block.iter_mut().for_each(|v| *v = (*v * drive).tanh());
+
+        let ratio = 1 << self.get_factor() as usize;
+        let decimated_len = self.block_size / ratio;
+        let filtered = &mut self.filtered_buff[..decimated_len];
+        let raw = &mut self.raw_buff[..decimated_len];
+
+        self.filtered.process_down(block, filtered);
+        raw.iter_mut()
+            .zip(block.iter().step_by(ratio))
+            .for_each(|(r, b)| *r = *b);
+        self.raw_delay.delay(raw);
+
+        let aliasing = self.aliasing;
+        block
+            .chunks_exact_mut(ratio)
+            .zip(filtered.iter().zip(raw.iter()))
+            .for_each(|(held, (f, r))| held.fill(*f + aliasing * (*r - *f)));
+    }
+}
+
+impl MemoryFootprint for Decimator {
+    fn memory_footprint(&self) -> usize {
+        self.filtered.memory_footprint()
+            + self.raw_delay.memory_footprint()
+            + self.filtered_buff.memory_footprint()
+            + self.raw_buff.memory_footprint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: usize = 64;
+
+    fn run(decimator: &mut Decimator, freq: f32, blocks: usize) -> Vec<f32> {
+        let mut out = Vec::new();
+        (0..blocks).for_each(|b| {
+            let mut block: Vec<f32> = (b * BLOCK..(b + 1) * BLOCK)
+                .map(|n| (std::f32::consts::TAU * freq * n as f32).sin() * 0.1)
+                .collect();
+            decimator.process_block(&mut block);
+            out.extend_from_slice(&block);
+        });
+        out
+    }
+
+    fn rms(signal: &[f32]) -> f32 {
+        (signal.iter().map(|v| v * v).sum::<f32>() / signal.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn paths_line_up_below_nyquist() {
+        for factor in [
+            OversampleFactor::TwoTimes,
+            OversampleFactor::FourTimes,
+            OversampleFactor::EightTimes,
+            OversampleFactor::SixteenTimes,
+        ] {
+            let ratio = (1 << factor as usize) as f32;
+            let mut filtered = Decimator::new(factor, BLOCK);
+            filtered.set_aliasing(0.0);
+            let mut raw = Decimator::new(factor, BLOCK);
+            let freq = 0.02 / ratio;
+
+            let a = run(&mut filtered, freq, 64);
+            let b = run(&mut raw, freq, 64);
+            let settled = BLOCK * 32..;
+            let diff: Vec<f32> = a[settled.clone()]
+                .iter()
+                .zip(b[settled.clone()].iter())
+                .map(|(a, b)| a - b)
+                .collect();
+            assert!(rms(&a[settled]) > 0.05);
+            assert!(rms(&diff) < 0.01, "{:?}: {}", factor, rms(&diff));
+        }
+    }
+
+    #[test]
+    fn aliasing_blends_in_folded_content() {
+        // above the decimated nyquist, only the unfiltered path lets it through
+        let mut decimator = Decimator::new(OversampleFactor::FourTimes, BLOCK);
+        let freq = 0.2;
+        decimator.set_aliasing(0.0);
+        let filtered = rms(&run(&mut decimator, freq, 32)[BLOCK * 16..]);
+        decimator.set_aliasing(1.0);
+        let aliased = rms(&run(&mut decimator, freq, 32)[BLOCK * 16..]);
+        decimator.set_aliasing(0.5);
+        let blended = rms(&run(&mut decimator, freq, 32)[BLOCK * 16..]);
+
+        assert!(filtered < 1e-3, "{}", filtered);
+        assert!(aliased > 0.05, "{}", aliased);
+        assert!((blended - aliased * 0.5).abs() < 1e-2, "{}", blended);
+    }
+
+    #[test]
+    fn output_is_held() {
+        let mut decimator = Decimator::new(OversampleFactor::EightTimes, BLOCK);
+        let out = run(&mut decimator, 0.3, 4);
+        out.chunks_exact(8)
+            .for_each(|held| assert!(held.iter().all(|v| *v == held[0])));
+
+        decimator.set_drive(100.0);
+        let out = run(&mut decimator, 0.3, 4);
+        assert!(out.iter().all(|v| v.abs() <= 1.0));
+    }
+}
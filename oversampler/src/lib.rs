@@ -1,3 +1,5 @@
 // #![feature(generic_const_exprs)]
 
+pub mod decimator;
 pub mod oversample;
+pub mod polyphase;
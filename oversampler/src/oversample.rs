@@ -1,7 +1,8 @@
-mod os_filter_constants;
+pub(crate) mod os_filter_constants;
 mod oversample_stage;
 
 use nih_plug::prelude::*;
+use sample::MemoryFootprint;
 
 use self::oversample_stage::OversampleStage;
 
@@ -65,31 +66,28 @@ impl Oversample {
         self.factor = new_factor;
     }
 
+    /// Round trip (`process_up` then `process_down`) latency at the base rate, rounded to
+    /// the nearest sample
     pub fn get_latency_samples(&self) -> usize {
-        match self.factor {
-            OversampleFactor::TwoTimes => FILTER_EVEN_TAPS_OS2X,
-            OversampleFactor::FourTimes => FILTER_EVEN_TAPS_OS2X + (FILTER_EVEN_TAPS_OS4X / 2),
-            OversampleFactor::EightTimes => {
-                FILTER_EVEN_TAPS_OS2X + (FILTER_EVEN_TAPS_OS4X / 2) + (FILTER_EVEN_TAPS_OS8X / 4)
-            }
-            OversampleFactor::SixteenTimes => {
-                FILTER_EVEN_TAPS_OS2X
-                    + (FILTER_EVEN_TAPS_OS4X / 2)
-                    + (FILTER_EVEN_TAPS_OS8X / 4)
-                    + (FILTER_EVEN_TAPS_OS16X / 8)
-            }
-        }
+        // every up/down pair delays by taps - 1 samples at its own rate
+        let latency: f32 = [
+            FILTER_EVEN_TAPS_OS2X,
+            FILTER_EVEN_TAPS_OS4X,
+            FILTER_EVEN_TAPS_OS8X,
+            FILTER_EVEN_TAPS_OS16X,
+        ]
+        .iter()
+        .take(self.factor as usize)
+        .enumerate()
+        .map(|(stage, taps)| (taps - 1) as f32 / (1 << stage) as f32)
+        .sum();
+        latency.round() as usize
     }
 
+    /// Clears the filter history of every stage, including the ones the current factor skips
     #[cold]
     pub fn reset(&mut self) {
-        // self.up_stages
-        // .iter_mut()
-        // .zip(self.down_stages.iter_mut())
-        // .for_each(|(u, d)| {
-        // u.reset();
-        // d.reset();
-        // });
+        self.stages.iter_mut().for_each(|st| st.reset());
     }
 
     pub fn process_up(&mut self, input: &[f32], output: &mut [f32]) {
@@ -99,7 +97,7 @@ impl Oversample {
             .take(self.factor as usize)
             .for_each(|st| {
                 st.process_up(processed);
-                processed = &st.data;
+                processed = st.data();
             });
 
         output
@@ -118,7 +116,7 @@ impl Oversample {
             .rev()
             .for_each(|st| {
                 st.process_down(last_stage);
-                last_stage = &st.data;
+                last_stage = st.data();
             });
 
         output
@@ -130,6 +128,13 @@ impl Oversample {
     }
 }
 
+/// Counts every stage, the ones past the current factor are allocated up front too
+impl MemoryFootprint for Oversample {
+    fn memory_footprint(&self) -> usize {
+        self.stages.iter().map(|st| st.memory_footprint()).sum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -140,6 +145,50 @@ mod tests {
         let os = Oversample::new(OversampleFactor::TwoTimes, 4);
         assert_eq!(os.stages.len(), 4);
     }
+
+    #[test]
+    fn footprint_grows_with_block_size() {
+        let small = Oversample::new(OversampleFactor::TwoTimes, 16).memory_footprint();
+        let large = Oversample::new(OversampleFactor::TwoTimes, 32).memory_footprint();
+        // per input sample, a stage holds 4 floats of data and scratch plus 2 of convolution
+        // history, and every stage runs at twice the rate of the one before it
+        assert_eq!(
+            large - small,
+            16 * 4 * 4 * (1 + 2 + 4 + 8) + 16 * 4 * 2 * (1 + 2 + 4 + 8)
+        );
+    }
+
+    #[test]
+    fn reset_matches_fresh() {
+        let sig: Vec<f32> = (0..16).map(|v| (v as f32 * 0.4).sin()).collect();
+        let mut up = [0.0_f32; 16 * 16];
+        [
+            OversampleFactor::TwoTimes,
+            OversampleFactor::FourTimes,
+            OversampleFactor::EightTimes,
+            OversampleFactor::SixteenTimes,
+        ]
+        .iter()
+        .for_each(|factor| {
+            let up_len = 16 << *factor as usize;
+            let mut used = Oversample::new(OversampleFactor::SixteenTimes, 16);
+            let mut block = sig.clone();
+            used.process_up(&block, &mut up);
+            used.process_down(&up, &mut block);
+            used.set_oversample_factor(*factor);
+            used.reset();
+
+            let mut fresh = Oversample::new(*factor, 16);
+            let mut expected = sig.clone();
+            fresh.process_up(&expected, &mut up[..up_len]);
+            fresh.process_down(&up[..up_len], &mut expected);
+
+            let mut result = sig.clone();
+            used.process_up(&result, &mut up[..up_len]);
+            used.process_down(&up[..up_len], &mut result);
+            assert_eq!(result, expected);
+        });
+    }
     const ERR_TOL: f32 = 1e-5;
 
     fn check_results(result: &[f32], expected: &[f32]) {
@@ -1,4 +1,4 @@
-use window::{hann, kaiser, sinc};
+use window::{hann_into, kaiser_into, sinc_into};
 
 // pub const FILTER_TOTAL_TAPS_OS2X: usize = 95;
 // pub const FILTER_TOTAL_TAPS_OS4X: usize = 63;
@@ -25,23 +25,30 @@ pub const FILTER_EVEN_TAPS_OS16X: usize = 16;
 // pub const OS8X_DOWN_STAGE_DELAY_AMT: usize = (FILTER_EVEN_TAPS_OS8X / 2) + 1;
 // pub const OS16X_DOWN_STAGE_DELAY_AMT: usize = (FILTER_EVEN_TAPS_OS16X / 2) + 1;
 //
+/// Designed in f64 and rounded once at the end, `num_taps` must be odd
 pub fn build_filter_coefs(num_taps: usize) -> Vec<f32> {
-    let sinc = sinc(num_taps, 0.5);
-    let hann = hann(num_taps);
-    let kaiser = kaiser(num_taps, 10.0);
+    let mut sinc = vec![0.0_f64; num_taps];
+    let mut hann = vec![0.0_f64; num_taps];
+    let mut kaiser = vec![0.0_f64; num_taps];
+    sinc_into(&mut sinc, 0.5);
+    hann_into(&mut hann);
+    kaiser_into(&mut kaiser, 10.0);
     let res = sinc
         .iter()
         .zip(hann.iter())
         .zip(kaiser.iter())
         .map(|((v, h), k)| v * h * k)
-        .collect::<Vec<f32>>();
-    let sum: f32 = res.iter().sum();
-    res.into_iter().map(|v| v / sum).collect::<Vec<f32>>()
+        .collect::<Vec<f64>>();
+    let sum: f64 = res.iter().sum();
+    res.into_iter()
+        .map(|v| (v / sum) as f32)
+        .collect::<Vec<f32>>()
 }
 
 #[cfg(test)]
 mod tests {
     use nih_plug::util::window::hann;
+    use window::{kaiser, sinc};
 
     pub const TOTAL_FILTER_TAP: usize = 63;
     // pub const NUM_OS_FILTER_TAPS: usize = 32;
@@ -193,5 +200,14 @@ mod tests {
             .step_by(2)
             .zip(expected_result.iter())
             .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+
+        // every other tap falls on a zero of the sinc, the f32 design left ~1e-8 there
+        res.iter()
+            .enumerate()
+            .skip(1)
+            .step_by(2)
+            .filter(|(idx, _)| *idx != TOTAL_FILTER_TAP / 2)
+            .for_each(|(idx, v)| assert!(v.abs() < 1e-12, "{} at index: {}", v, idx));
+        assert!((res.iter().map(|v| *v as f64).sum::<f64>() - 1.0).abs() < 1e-6);
     }
 }
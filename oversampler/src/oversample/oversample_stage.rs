@@ -1,6 +1,8 @@
 use circular_buffer::circular_buffer::{CircularDelayBuffer, TiledConv};
+use sample::MemoryFootprint;
 
 use super::os_filter_constants::build_filter_coefs;
+use crate::polyphase::decompose;
 
 #[derive(Debug)]
 pub struct OversampleStage {
@@ -10,28 +12,48 @@ pub struct OversampleStage {
     down_conv_buff: TiledConv,
     up_delay_buf: CircularDelayBuffer,
     down_delay_buf: CircularDelayBuffer,
-    pub data: Vec<f32>,
+    data: Vec<f32>,
     scratch_buff_1: Vec<f32>,
     scratch_buff_2: Vec<f32>,
 }
 
 impl OversampleStage {
     pub fn new(input_len: usize, kernel_size: usize) -> Self {
-        let coefs = build_filter_coefs((kernel_size * 2) - 1);
+        let mut branches = decompose(&build_filter_coefs((kernel_size * 2) - 1), 2);
+        // a halfband's odd taps are all zero except the center one
+        let (delay, delay_coef) = branches[1]
+            .pure_delay()
+            .expect("halfband odd branch is a single tap");
+        let kernel = std::mem::take(&mut branches[0].taps);
 
         OversampleStage {
-            kernel: Vec::from_iter(coefs.clone().into_iter().step_by(2)),
-            delay_coef: coefs[coefs.len() / 2],
+            kernel,
+            delay_coef,
             up_conv_buff: TiledConv::new(kernel_size, input_len),
             down_conv_buff: TiledConv::new(kernel_size, input_len),
-            up_delay_buf: CircularDelayBuffer::new(kernel_size / 2),
-            down_delay_buf: CircularDelayBuffer::new((kernel_size / 2) + 1),
+            // the delay buffers hold one more sample than they delay by, going down the odd
+            // inputs are a sample further behind the even ones
+            up_delay_buf: CircularDelayBuffer::new(delay + 1),
+            down_delay_buf: CircularDelayBuffer::new(delay + 2),
             data: vec![0.0_f32; input_len * 2],
             scratch_buff_1: vec![0.0_f32; input_len],
             scratch_buff_2: vec![0.0_f32; input_len],
         }
     }
 
+    /// Output of the last `process_up` or `process_down`
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn reset(&mut self) {
+        self.up_conv_buff.reset();
+        self.down_conv_buff.reset();
+        self.up_delay_buf.reset();
+        self.down_delay_buf.reset();
+        self.data.iter_mut().for_each(|x| *x = 0.0);
+    }
+
     pub fn process_up(&mut self, input: &[f32]) {
         let input_len = input.len();
         self.scratch_buff_1.clone_from_slice(input);
@@ -78,6 +100,19 @@ impl OversampleStage {
     }
 }
 
+impl MemoryFootprint for OversampleStage {
+    fn memory_footprint(&self) -> usize {
+        self.kernel.memory_footprint()
+            + self.up_conv_buff.memory_footprint()
+            + self.down_conv_buff.memory_footprint()
+            + self.up_delay_buf.memory_footprint()
+            + self.down_delay_buf.memory_footprint()
+            + self.data.memory_footprint()
+            + self.scratch_buff_1.memory_footprint()
+            + self.scratch_buff_2.memory_footprint()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::oversample::{
@@ -0,0 +1,63 @@
+/// One branch of a polyphase split, tap `n` is tap `n * phases + phase` of the prototype
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyphaseBranch {
+    pub phase: usize,
+    pub taps: Vec<f32>,
+}
+
+impl PolyphaseBranch {
+    /// If only one tap is nonzero the branch is a plain delay, gives the delay in branch
+    /// samples and the gain. Taps below `f32::EPSILON` of the largest count as zero, a kernel
+    /// designed from a sinc never lands exactly on its zeros.
+    pub fn pure_delay(&self) -> Option<(usize, f32)> {
+        let (peak_idx, peak) = self
+            .taps
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))?;
+        let floor = peak.abs() * f32::EPSILON;
+        self.taps
+            .iter()
+            .enumerate()
+            .all(|(n, t)| n == peak_idx || t.abs() <= floor)
+            .then_some((peak_idx, *peak))
+    }
+}
+
+/// Splits `kernel` into `phases` branches, for 2x that's the even and the odd taps
+pub fn decompose(kernel: &[f32], phases: usize) -> Vec<PolyphaseBranch> {
+    assert!(phases > 0);
+    (0..phases)
+        .map(|phase| PolyphaseBranch {
+            phase,
+            taps: kernel.iter().skip(phase).step_by(phases).copied().collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oversample::os_filter_constants::build_filter_coefs;
+
+    #[test]
+    fn splits_by_phase() {
+        let kernel: Vec<f32> = (0..8).map(|n| n as f32).collect();
+        let branches = decompose(&kernel, 3);
+        assert_eq!(branches.len(), 3);
+        assert_eq!(branches[0].taps, vec![0., 3., 6.]);
+        assert_eq!(branches[1].taps, vec![1., 4., 7.]);
+        assert_eq!(branches[2].taps, vec![2., 5.]);
+        assert_eq!(branches[2].phase, 2);
+        assert_eq!(branches[1].pure_delay(), None);
+    }
+
+    #[test]
+    fn halfband_odd_branch_is_a_delay() {
+        let branches = decompose(&build_filter_coefs(127), 2);
+        assert_eq!(branches[0].taps.len(), 64);
+        let (delay, gain) = branches[1].pure_delay().unwrap();
+        assert_eq!(delay, 31);
+        assert!((gain - 0.5).abs() < 1e-6, "{}", gain);
+    }
+}
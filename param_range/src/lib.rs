@@ -0,0 +1 @@
+pub mod param_range;
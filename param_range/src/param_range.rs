@@ -0,0 +1,109 @@
+/// How a normalized 0.0..=1.0 position maps onto a range
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Taper {
+    Linear,
+    /// Equal steps give equal ratios, min and max must be positive
+    Logarithmic,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParamRange {
+    pub min: f32,
+    pub max: f32,
+    pub taper: Taper,
+}
+
+/// Drive into a nonlinearity, in dB
+pub const DRIVE_DB: ParamRange = ParamRange::new(-24.0, 48.0, Taper::Linear);
+/// Output level, in dB
+pub const OUTPUT_DB: ParamRange = ParamRange::new(-96.0, 12.0, Taper::Linear);
+/// Filter cutoff, in Hz
+pub const CUTOFF_HZ: ParamRange = ParamRange::new(20.0, 20000.0, Taper::Logarithmic);
+
+impl ParamRange {
+    pub const fn new(min: f32, max: f32, taper: Taper) -> Self {
+        ParamRange { min, max, taper }
+    }
+
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.clamp(self.min, self.max)
+    }
+
+    /// Inclusive of both ends, NaN is never contained
+    pub fn contains(&self, value: f32) -> bool {
+        value >= self.min && value <= self.max
+    }
+
+    pub fn from_normalized(&self, normalized: f32) -> f32 {
+        let n = normalized.clamp(0.0, 1.0);
+        match self.taper {
+            Taper::Linear => self.min + (self.max - self.min) * n,
+            Taper::Logarithmic => self.min * (self.max / self.min).powf(n),
+        }
+    }
+
+    pub fn to_normalized(&self, value: f32) -> f32 {
+        let v = self.clamp(value);
+        match self.taper {
+            Taper::Linear => (v - self.min) / (self.max - self.min),
+            Taper::Logarithmic => (v / self.min).ln() / (self.max / self.min).ln(),
+        }
+    }
+}
+
+#[inline]
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// Gains at or below `floor_db`, including 0.0, come out as the floor
+#[inline]
+pub fn gain_to_db(gain: f32, floor_db: f32) -> f32 {
+    if gain <= 0.0 {
+        floor_db
+    } else {
+        (20.0 * gain.log10()).max(floor_db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn db_conversions() {
+        assert_eq!(db_to_gain(0.0), 1.0);
+        assert!((db_to_gain(-6.0206) - 0.5).abs() < 1e-5);
+        assert!((gain_to_db(2.0, OUTPUT_DB.min) - 6.0206).abs() < 1e-4);
+        assert_eq!(gain_to_db(0.0, OUTPUT_DB.min), -96.0);
+        assert_eq!(gain_to_db(1e-9, OUTPUT_DB.min), -96.0);
+        [-24.0, -3.0, 0.0, 12.0, 48.0]
+            .iter()
+            .for_each(|db| assert!((gain_to_db(db_to_gain(*db), -120.0) - db).abs() < 1e-4));
+    }
+
+    #[test]
+    fn contains_is_inclusive() {
+        assert!(CUTOFF_HZ.contains(20.0) && CUTOFF_HZ.contains(20000.0));
+        assert!(!CUTOFF_HZ.contains(19.9) && !CUTOFF_HZ.contains(20001.0));
+        assert!(!OUTPUT_DB.contains(f32::NAN));
+    }
+
+    #[test]
+    fn normalized_round_trip() {
+        assert_eq!(DRIVE_DB.from_normalized(0.0), -24.0);
+        assert_eq!(DRIVE_DB.from_normalized(0.5), 12.0);
+        assert_eq!(OUTPUT_DB.to_normalized(12.0), 1.0);
+        assert_eq!(OUTPUT_DB.to_normalized(100.0), 1.0);
+        assert!((CUTOFF_HZ.from_normalized(0.5) - 632.4555).abs() < 1e-2);
+        // a decade is a third of the cutoff range
+        assert!((CUTOFF_HZ.to_normalized(200.0) - 1.0 / 3.0).abs() < 1e-5);
+
+        [DRIVE_DB, OUTPUT_DB, CUTOFF_HZ].iter().for_each(|range| {
+            [0.0, 0.1, 0.25, 0.8, 1.0].iter().for_each(|n| {
+                let back = range.to_normalized(range.from_normalized(*n));
+                assert!((back - n).abs() < 1e-5, "{:?} {}", range, n);
+            })
+        });
+    }
+}
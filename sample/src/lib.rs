@@ -0,0 +1,190 @@
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// Floating point type a processor can run at, implemented for `f32` and `f64`
+pub trait Sample:
+    Copy
+    + Debug
+    + Default
+    + PartialEq
+    + PartialOrd
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const PI: Self;
+    const EPSILON: Self;
+
+    fn from_f32(val: f32) -> Self;
+    fn from_f64(val: f64) -> Self;
+    fn to_f32(self) -> f32;
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn log10(self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+}
+
+macro_rules! impl_sample {
+    ($t:ident) => {
+        impl Sample for $t {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const PI: Self = std::$t::consts::PI;
+            const EPSILON: Self = $t::EPSILON;
+
+            #[inline]
+            fn from_f32(val: f32) -> Self {
+                val as $t
+            }
+
+            #[inline]
+            fn from_f64(val: f64) -> Self {
+                val as $t
+            }
+
+            #[inline]
+            fn to_f32(self) -> f32 {
+                self as f32
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn abs(self) -> Self {
+                $t::abs(self)
+            }
+
+            #[inline]
+            fn sqrt(self) -> Self {
+                $t::sqrt(self)
+            }
+
+            #[inline]
+            fn sin(self) -> Self {
+                $t::sin(self)
+            }
+
+            #[inline]
+            fn cos(self) -> Self {
+                $t::cos(self)
+            }
+
+            #[inline]
+            fn tan(self) -> Self {
+                $t::tan(self)
+            }
+
+            #[inline]
+            fn exp(self) -> Self {
+                $t::exp(self)
+            }
+
+            #[inline]
+            fn ln(self) -> Self {
+                $t::ln(self)
+            }
+
+            #[inline]
+            fn powf(self, n: Self) -> Self {
+                $t::powf(self, n)
+            }
+
+            #[inline]
+            fn powi(self, n: i32) -> Self {
+                $t::powi(self, n)
+            }
+
+            #[inline]
+            fn log10(self) -> Self {
+                $t::log10(self)
+            }
+
+            #[inline]
+            fn max(self, other: Self) -> Self {
+                $t::max(self, other)
+            }
+
+            #[inline]
+            fn min(self, other: Self) -> Self {
+                $t::min(self, other)
+            }
+
+            #[inline]
+            fn clamp(self, min: Self, max: Self) -> Self {
+                $t::clamp(self, min, max)
+            }
+        }
+    };
+}
+
+impl_sample!(f32);
+impl_sample!(f64);
+
+/// Heap memory a processor holds on to, for budgeting many instances. Processors that only
+/// keep their state inline can rely on the default.
+pub trait MemoryFootprint {
+    /// Bytes allocated on the heap, not counting the processor itself
+    fn memory_footprint(&self) -> usize {
+        0
+    }
+}
+
+impl<T: Sample> MemoryFootprint for Vec<T> {
+    fn memory_footprint(&self) -> usize {
+        self.capacity() * std::mem::size_of::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn second_harmonic<T: Sample>(x: T) -> T {
+        let two = T::from_f32(2.0);
+        (two * T::PI * x).sin() * x.exp().ln()
+    }
+
+    #[test]
+    fn f32_and_f64_agree() {
+        for x in [0.0, 0.1, 0.25, 0.7, 1.3] {
+            let single = second_harmonic(x as f32);
+            let double = second_harmonic(x);
+            assert!((single.to_f64() - double).abs() < 1e-6, "x: {}", x);
+            assert_eq!(f64::from_f32(single), single as f64);
+        }
+        assert_eq!(f32::PI, std::f32::consts::PI);
+        assert_eq!(f64::ONE - f64::ONE, f64::ZERO);
+    }
+
+    #[test]
+    fn vec_footprint_counts_capacity() {
+        let mut buff: Vec<f64> = Vec::with_capacity(16);
+        buff.push(1.0);
+        assert_eq!(buff.memory_footprint(), 128);
+        assert_eq!(Vec::<f32>::new().memory_footprint(), 0);
+    }
+}
@@ -0,0 +1 @@
+pub mod silence_detector;
@@ -0,0 +1,100 @@
+use param_range::param_range::db_to_gain;
+
+/// Watches the input for prolonged silence so the caller can flush delay, convolution and
+/// filter state to exact zeros (`CircularDelayBuffer::reset`, `TiledConv::reset`,
+/// `IIRBiquadFilter::reset`, `DCFilter::reset`). This avoids denormal tails and stale echoes
+/// when the transport restarts. Nothing is flushed unless a detector is created and checked.
+#[derive(Debug, Clone)]
+pub struct SilenceDetector {
+    threshold: f32,
+    hold_samples: usize,
+    silent_samples: usize,
+    flushed: bool,
+}
+
+impl SilenceDetector {
+    /// Silence is every sample below `threshold_db` for at least `hold_ms`
+    pub fn new(threshold_db: f32, hold_ms: f32, sample_rate: f32) -> Self {
+        SilenceDetector {
+            threshold: db_to_gain(threshold_db),
+            hold_samples: (hold_ms * 0.001 * sample_rate).round().max(1.0) as usize,
+            silent_samples: 0,
+            flushed: false,
+        }
+    }
+
+    /// Returns true once, for the block in which the silence has lasted long enough, that is
+    /// when state should be flushed. Any sample above the threshold starts the count over.
+    pub fn process(&mut self, block: &[f32]) -> bool {
+        match block.iter().rposition(|v| v.abs() >= self.threshold) {
+            Some(loud) => {
+                self.silent_samples = block.len() - loud - 1;
+                self.flushed = false;
+            }
+            None => self.silent_samples = self.silent_samples.saturating_add(block.len()),
+        }
+
+        if !self.flushed && self.silent_samples >= self.hold_samples {
+            self.flushed = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// True after a flush was signalled until the signal comes back
+    pub fn is_silent(&self) -> bool {
+        self.flushed
+    }
+
+    pub fn reset(&mut self) {
+        self.silent_samples = 0;
+        self.flushed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use circular_buffer::circular_buffer::CircularDelayBuffer;
+
+    #[test]
+    fn signals_once_after_hold() {
+        // 1 ms at 32 kHz
+        let mut detector = SilenceDetector::new(-90.0, 1.0, 32000.0);
+        let quiet = [1e-6_f32; 8];
+
+        let flushes: Vec<bool> = (0..8).map(|_| detector.process(&quiet)).collect();
+        assert_eq!(
+            flushes,
+            vec![false, false, false, true, false, false, false, false]
+        );
+        assert!(detector.is_silent());
+
+        // the count restarts after the last loud sample
+        let mut block = quiet;
+        block[5] = 0.5;
+        assert!(!detector.process(&block));
+        assert!(!detector.is_silent());
+        assert!(!detector.process(&quiet));
+        assert!(!detector.process(&quiet));
+        assert!(!detector.process(&quiet));
+        assert!(detector.process(&quiet));
+    }
+
+    #[test]
+    fn flush_drops_stale_echo() {
+        let mut detector = SilenceDetector::new(-80.0, 0.5, 48000.0);
+        let mut delay = CircularDelayBuffer::new(64);
+
+        let mut block = [0.25_f32; 32];
+        delay.delay(&mut block);
+        // transport stopped, the echo is still waiting in the delay line
+        assert!(detector.process(&[1e-20_f32; 32]));
+        delay.reset();
+
+        let mut block = [0.0_f32; 64];
+        delay.delay(&mut block);
+        assert!(block.iter().all(|v| *v == 0.0));
+    }
+}
@@ -1,50 +1,441 @@
+use sample::Sample;
+
+pub mod overlap_add;
+
+// The `_into` and `_iter` generators are generic so kernels can be designed in f64, the
+// allocating ones stay f32. `_iter` computes each tap as it's pulled, `_into` fills from it.
+
 pub fn sinc(size: usize, cutoff: f32) -> Vec<f32> {
-    (((size as i32 * -1) / 2)..=(size as i32 / 2))
-        .map(|i| {
-            if i == 0 {
-                1.0
-            } else {
-                let pi_i_cutoff = std::f32::consts::PI * (i as f32 * cutoff);
-                pi_i_cutoff.sin() / pi_i_cutoff
-            }
-        })
-        .collect::<Vec<f32>>()
+    let mut win = vec![0.0_f32; size];
+    sinc_into(&mut win, cutoff);
+    win
+}
+
+/// Centered on `(win.len() - 1) / 2`, an even length puts the center between two taps
+pub fn sinc_into<T: Sample>(win: &mut [T], cutoff: T) {
+    fill(win, sinc_iter(win.len(), cutoff))
+}
+
+pub fn sinc_iter<T: Sample>(size: usize, cutoff: T) -> impl ExactSizeIterator<Item = T> {
+    let center = (size as f64 - 1.) / 2.;
+    (0..size).map(move |n| {
+        let i = n as f64 - center;
+        if i == 0. {
+            T::ONE
+        } else {
+            let pi_i_cutoff = T::PI * (T::from_f64(i) * cutoff);
+            pi_i_cutoff.sin() / pi_i_cutoff
+        }
+    })
+}
+
+/// `sinc` times `cutoff`, the ideal lowpass impulse response with a DC gain of 1
+pub fn sinc_scaled(size: usize, cutoff: f32) -> Vec<f32> {
+    let mut win = vec![0.0_f32; size];
+    sinc_scaled_into(&mut win, cutoff);
+    win
+}
+
+pub fn sinc_scaled_into<T: Sample>(win: &mut [T], cutoff: T) {
+    fill(win, sinc_scaled_iter(win.len(), cutoff))
+}
+
+pub fn sinc_scaled_iter<T: Sample>(size: usize, cutoff: T) -> impl ExactSizeIterator<Item = T> {
+    sinc_iter(size, cutoff).map(move |w| w * cutoff)
+}
+
+/// Windowed FIR Hilbert transformer as long as `window`, which has to be odd, e.g.
+/// `hilbert(&blackman(127))`. Delays by `(len - 1) / 2` and shifts every frequency by -90
+/// degrees.
+pub fn hilbert(window: &[f32]) -> Vec<f32> {
+    let mut kernel = vec![0.0_f32; window.len()];
+    hilbert_into(&mut kernel, window);
+    kernel
+}
+
+pub fn hilbert_into<T: Sample>(kernel: &mut [T], window: &[T]) {
+    assert_eq!(kernel.len(), window.len());
+    fill(
+        kernel,
+        hilbert_iter::<T>(window.len())
+            .zip(window.iter())
+            .map(|(h, w)| h * *w),
+    )
+}
+
+/// Unwindowed taps, 2 / (pi n) at odd offsets `n` from the center and zero at even ones
+pub fn hilbert_iter<T: Sample>(size: usize) -> impl ExactSizeIterator<Item = T> {
+    assert!(
+        !size.is_multiple_of(2),
+        "a Hilbert transformer needs an odd length"
+    );
+    let center = (size / 2) as i64;
+    (0..size).map(move |n| {
+        let offset = n as i64 - center;
+        if offset % 2 == 0 {
+            T::ZERO
+        } else {
+            T::from_f64(2. / (std::f64::consts::PI * offset as f64))
+        }
+    })
 }
 
 pub fn hann(size: usize) -> Vec<f32> {
-    (0..size)
-        .map(|n| {
-            ((std::f32::consts::PI * n as f32) / (size - 1) as f32)
-                .sin()
-                .powf(2.)
-        })
-        .collect::<Vec<f32>>()
+    let mut win = vec![0.0_f32; size];
+    hann_into(&mut win);
+    win
+}
+
+pub fn hann_into<T: Sample>(win: &mut [T]) {
+    fill(win, hann_iter(win.len()))
+}
+
+pub fn hann_iter<T: Sample>(size: usize) -> impl ExactSizeIterator<Item = T> {
+    let n_recip = T::ONE / T::from_f64((size - 1) as f64);
+    (0..size).map(move |n| {
+        let s = (T::PI * T::from_f64(n as f64) * n_recip).sin();
+        s * s
+    })
 }
 
 pub fn kaiser(size: usize, beta: f32) -> Vec<f32> {
-    let one_over_denom = 1. / zeroth_order_bessel(beta);
-    let n_size: u32 = size as u32 - 1;
-    let n_recip: f32 = 1. / n_size as f32;
-
-    (0..size)
-        .map(|n| {
-            let k = (2. * (n as f32) * n_recip) - 1.;
-            let arg = (1. - (k * k)).sqrt();
-            zeroth_order_bessel(beta * arg) * one_over_denom
-        })
-        .collect::<Vec<f32>>()
-}
-
-fn zeroth_order_bessel(val: f32) -> f32 {
-    const EPS: f32 = 1e-6;
-    let mut bessel_value: f32 = 0.0;
-    let mut term: f32 = 1.;
-    let mut m: f32 = 0.;
-
-    while term > EPS * bessel_value {
+    let mut win = vec![0.0_f32; size];
+    kaiser_into(&mut win, beta);
+    win
+}
+
+pub fn kaiser_into<T: Sample>(win: &mut [T], beta: T) {
+    fill(win, kaiser_iter(win.len(), beta))
+}
+
+/// Evaluated in f64 whatever `T` is, the Bessel terms lose precision in f32 at high `beta`
+pub fn kaiser_iter<T: Sample>(size: usize, beta: T) -> impl ExactSizeIterator<Item = T> {
+    let beta = beta.to_f64();
+    let one_over_denom = 1. / bessel_i0(beta);
+    let n_recip = 1. / (size - 1) as f64;
+
+    (0..size).map(move |n| {
+        let k = (2. * n as f64 * n_recip) - 1.;
+        let arg = (1. - (k * k)).sqrt();
+        T::from_f64(bessel_i0(beta * arg) * one_over_denom)
+    })
+}
+
+/// Kaiser `beta` that reaches `attenuation_db` of stopband attenuation
+pub fn kaiser_beta(attenuation_db: f32) -> f32 {
+    let a = attenuation_db.abs();
+    if a > 50. {
+        0.1102 * (a - 8.7)
+    } else if a > 21. {
+        0.5842 * (a - 21.).powf(0.4) + 0.07886 * (a - 21.)
+    } else {
+        0.
+    }
+}
+
+/// Taps and `beta` for a Kaiser windowed design with `attenuation_db` of stopband attenuation
+/// and a transition band `transition_width` wide, as a fraction of nyquist like the `sinc`
+/// cutoff
+pub fn kaiser_order(attenuation_db: f32, transition_width: f32) -> (usize, f32) {
+    let a = attenuation_db.abs();
+    let taps = (a - 7.95) / (2.285 * std::f32::consts::PI * transition_width) + 1.;
+    (taps.ceil().max(1.) as usize, kaiser_beta(a))
+}
+
+const BLACKMAN: [f64; 3] = [0.42, 0.5, 0.08];
+const BLACKMAN_HARRIS: [f64; 4] = [0.35875, 0.48829, 0.14128, 0.01168];
+const NUTTALL: [f64; 4] = [0.355768, 0.487396, 0.144232, 0.012604];
+const FLAT_TOP: [f64; 5] = [
+    0.21557895,
+    0.41663158,
+    0.277263158,
+    0.083578947,
+    0.006947368,
+];
+
+pub fn blackman(size: usize) -> Vec<f32> {
+    cosine_sum(size, &BLACKMAN)
+}
+
+pub fn blackman_into<T: Sample>(win: &mut [T]) {
+    cosine_sum_into(win, &BLACKMAN)
+}
+
+pub fn blackman_iter<T: Sample>(size: usize) -> impl ExactSizeIterator<Item = T> {
+    cosine_sum_iter(size, &BLACKMAN)
+}
+
+pub fn blackman_harris(size: usize) -> Vec<f32> {
+    cosine_sum(size, &BLACKMAN_HARRIS)
+}
+
+pub fn blackman_harris_into<T: Sample>(win: &mut [T]) {
+    cosine_sum_into(win, &BLACKMAN_HARRIS)
+}
+
+pub fn blackman_harris_iter<T: Sample>(size: usize) -> impl ExactSizeIterator<Item = T> {
+    cosine_sum_iter(size, &BLACKMAN_HARRIS)
+}
+
+pub fn nuttall(size: usize) -> Vec<f32> {
+    cosine_sum(size, &NUTTALL)
+}
+
+pub fn nuttall_into<T: Sample>(win: &mut [T]) {
+    cosine_sum_into(win, &NUTTALL)
+}
+
+pub fn nuttall_iter<T: Sample>(size: usize) -> impl ExactSizeIterator<Item = T> {
+    cosine_sum_iter(size, &NUTTALL)
+}
+
+/// Very flat main lobe for reading amplitudes off a spectrum, dips slightly below zero
+pub fn flat_top(size: usize) -> Vec<f32> {
+    cosine_sum(size, &FLAT_TOP)
+}
+
+pub fn flat_top_into<T: Sample>(win: &mut [T]) {
+    cosine_sum_into(win, &FLAT_TOP)
+}
+
+pub fn flat_top_iter<T: Sample>(size: usize) -> impl ExactSizeIterator<Item = T> {
+    cosine_sum_iter(size, &FLAT_TOP)
+}
+
+/// Tapered cosine, `alpha` is the fraction of the window inside the tapers. 0.0 gives a
+/// rectangular window and 1.0 a Hann window.
+pub fn tukey(size: usize, alpha: f32) -> Vec<f32> {
+    let mut win = vec![0.0_f32; size];
+    tukey_into(&mut win, alpha);
+    win
+}
+
+pub fn tukey_into<T: Sample>(win: &mut [T], alpha: T) {
+    fill(win, tukey_iter(win.len(), alpha))
+}
+
+pub fn tukey_iter<T: Sample>(size: usize, alpha: T) -> impl ExactSizeIterator<Item = T> {
+    let alpha = if alpha < T::ZERO {
+        T::ZERO
+    } else if alpha > T::ONE {
+        T::ONE
+    } else {
+        alpha
+    };
+    let n_recip = T::ONE / T::from_f64((size - 1) as f64);
+    let half = T::from_f32(0.5);
+    let half_alpha = alpha * half;
+    let taper = move |x: T| half * (T::ONE + (T::from_f32(2.) * T::PI / alpha * x).cos());
+
+    (0..size).map(move |n| {
+        let x = T::from_f64(n as f64) * n_recip;
+        if x < half_alpha {
+            taper(x - half_alpha)
+        } else if x > T::ONE - half_alpha {
+            taper(x - T::ONE + half_alpha)
+        } else {
+            T::ONE
+        }
+    })
+}
+
+/// `sigma` is the standard deviation relative to half the window length, smaller values give
+/// a wider main lobe and lower sidelobes. Usually 0.5 or less.
+pub fn gaussian(size: usize, sigma: f32) -> Vec<f32> {
+    let mut win = vec![0.0_f32; size];
+    gaussian_into(&mut win, sigma);
+    win
+}
+
+pub fn gaussian_into<T: Sample>(win: &mut [T], sigma: T) {
+    fill(win, gaussian_iter(win.len(), sigma))
+}
+
+pub fn gaussian_iter<T: Sample>(size: usize, sigma: T) -> impl ExactSizeIterator<Item = T> {
+    let half = T::from_f64((size - 1) as f64 / 2.);
+    let denom_recip = T::ONE / (sigma * half);
+
+    (0..size).map(move |n| {
+        let k = (T::from_f64(n as f64) - half) * denom_recip;
+        (T::from_f32(-0.5) * k * k).exp()
+    })
+}
+
+/// Dolph-Chebyshev window, every sidelobe sits exactly `sidelobe_db` below the main lobe
+/// (given as a positive attenuation). Built from its frequency response, so it's O(size^2).
+pub fn chebyshev(size: usize, sidelobe_db: f32) -> Vec<f32> {
+    let mut win = vec![0.0_f32; size];
+    chebyshev_into(&mut win, sidelobe_db);
+    win
+}
+
+/// Always evaluated in f64, the Chebyshev polynomials get large
+pub fn chebyshev_into<T: Sample>(win: &mut [T], sidelobe_db: f32) {
+    let size = win.len();
+    let order = (size - 1) as f64;
+    let x0 = (10_f64.powf(sidelobe_db as f64 / 20.).acosh() / order).cosh();
+    let chebyshev_poly = |x: f64| {
+        if x.abs() <= 1. {
+            (order * x.acos()).cos()
+        } else {
+            x.signum().powi(order as i32) * (order * x.abs().acosh()).cosh()
+        }
+    };
+
+    // the response is evaluated again for every tap, there's nowhere to keep it
+    let freq_step = std::f64::consts::PI / size as f64;
+    win.iter_mut().enumerate().for_each(|(n, w)| {
+        let centered = n as f64 - order / 2.;
+        *w = T::from_f64(
+            (0..size)
+                .map(|k| {
+                    chebyshev_poly(x0 * (freq_step * k as f64).cos())
+                        * (2. * freq_step * k as f64 * centered).cos()
+                })
+                .sum::<f64>(),
+        )
+    });
+
+    let peak = win.iter().fold(T::ZERO, |m, v| if *v > m { *v } else { m });
+    let peak_recip = T::ONE / peak;
+    win.iter_mut().for_each(|w| *w *= peak_recip);
+}
+
+/// What `normalize` scales a window to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Mean of 1, a sinusoid reads its true amplitude in its bin
+    CoherentGain,
+    /// Mean square of 1, broadband noise keeps its power
+    Power,
+}
+
+/// Scales `win` in place and returns the factor it was multiplied by
+pub fn normalize<T: Sample>(win: &mut [T], mode: Normalization) -> T {
+    let len = T::from_f64(win.len() as f64);
+    let scale = match mode {
+        Normalization::CoherentGain => len / win.iter().fold(T::ZERO, |acc, w| acc + *w),
+        Normalization::Power => (len / win.iter().fold(T::ZERO, |acc, w| acc + *w * *w)).sqrt(),
+    };
+    win.iter_mut().for_each(|w| *w *= scale);
+    scale
+}
+
+/// Figures for calibrating measurements taken through a window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowInfo {
+    /// Equivalent noise bandwidth in bins
+    pub enbw: f32,
+    /// Mean of the window, a sinusoid on a bin center reads this much of its amplitude
+    pub coherent_gain: f32,
+    /// How much lower, in dB, a sinusoid halfway between two bins reads than one on a bin
+    pub scallop_loss_db: f32,
+}
+
+impl WindowInfo {
+    pub fn new<T: Sample>(win: &[T]) -> Self {
+        let len = win.len() as f64;
+        let sum: f64 = win.iter().map(|w| w.to_f64()).sum();
+        let sum_sq: f64 = win.iter().map(|w| w.to_f64() * w.to_f64()).sum();
+        let (re, im) = win.iter().enumerate().fold((0., 0.), |(re, im), (n, w)| {
+            let phase = std::f64::consts::PI * n as f64 / len;
+            (re + w.to_f64() * phase.cos(), im - w.to_f64() * phase.sin())
+        });
+
+        WindowInfo {
+            enbw: (len * sum_sq / (sum * sum)) as f32,
+            coherent_gain: (sum / len) as f32,
+            scallop_loss_db: (-20. * ((re * re + im * im).sqrt() / sum.abs()).log10()) as f32,
+        }
+    }
+}
+
+/// Whether copies of `win` every `hop` samples add up to a constant (constant overlap-add),
+/// to within `sqrt(T::EPSILON)` of the mean. The windows here are symmetric, so the hops that
+/// work divide `len - 1` rather than `len`.
+pub fn check_cola<T: Sample>(win: &[T], hop: usize) -> bool {
+    if hop == 0 || hop > win.len() {
+        return false;
+    }
+    let overlapped = (0..hop).map(|n| {
+        win.iter()
+            .skip(n)
+            .step_by(hop)
+            .fold(T::ZERO, |acc, w| acc + *w)
+    });
+    let mean = win.iter().fold(T::ZERO, |acc, w| acc + *w) / T::from_f64(hop as f64);
+    let tolerance = T::EPSILON.sqrt() * mean.abs();
+    overlapped
+        .into_iter()
+        .all(|sum| (sum - mean).abs() <= tolerance)
+}
+
+/// Every hop `win` is COLA at, largest first
+pub fn recommended_hops<T: Sample>(win: &[T]) -> Vec<usize> {
+    (1..=win.len())
+        .rev()
+        .filter(|hop| check_cola(win, *hop))
+        .collect()
+}
+
+fn cosine_sum(size: usize, coefs: &'static [f64]) -> Vec<f32> {
+    let mut win = vec![0.0_f32; size];
+    cosine_sum_into(&mut win, coefs);
+    win
+}
+
+fn cosine_sum_into<T: Sample>(win: &mut [T], coefs: &'static [f64]) {
+    fill(win, cosine_sum_iter(win.len(), coefs))
+}
+
+/// Generalized cosine window, sum of (-1)^k * a_k * cos(2 pi k n / (size - 1))
+fn cosine_sum_iter<T: Sample>(
+    size: usize,
+    coefs: &'static [f64],
+) -> impl ExactSizeIterator<Item = T> {
+    let n_recip = T::ONE / T::from_f64((size - 1) as f64);
+    (0..size).map(move |n| {
+        let phase = T::from_f32(2.) * T::PI * T::from_f64(n as f64) * n_recip;
+        coefs
+            .iter()
+            .enumerate()
+            .map(|(k, a)| {
+                let term = T::from_f64(*a) * (phase * T::from_f64(k as f64)).cos();
+                if k % 2 == 0 {
+                    term
+                } else {
+                    -term
+                }
+            })
+            .fold(T::ZERO, |acc, term| acc + term)
+    })
+}
+
+fn fill<T: Sample>(win: &mut [T], taps: impl Iterator<Item = T>) {
+    win.iter_mut().zip(taps).for_each(|(w, t)| *w = t);
+}
+
+/// Most terms `bessel_i0` will sum, the largest finite result (x = 713) takes 471
+const BESSEL_I0_MAX_TERMS: usize = 1000;
+
+/// Modified Bessel function of the first kind, order zero. The power series runs until a term
+/// no longer changes the sum. NaN comes back as NaN and +-inf as inf.
+pub fn bessel_i0(x: f64) -> f64 {
+    if !x.is_finite() {
+        return x.abs();
+    }
+    let quarter_x_sq = x * x / 4.;
+    let mut bessel_value = 0.;
+    let mut term = 1.;
+    let mut m = 0.;
+
+    for _ in 0..BESSEL_I0_MAX_TERMS {
+        if bessel_value + term == bessel_value {
+            break;
+        }
         bessel_value += term;
         m += 1.;
-        term *= (val * val) / (4. * m * m);
+        term *= quarter_x_sq / (m * m);
     }
 
     bessel_value
@@ -218,6 +609,328 @@ mod tests {
             .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
     }
 
+    #[test]
+    fn test_create_blackman() {
+        let res = blackman(8);
+        let expected_result = [
+            0., 0.09045342, 0.45918296, 0.9203636, 0.9203636, 0.45918296, 0.09045342, 0.,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn test_create_blackman_harris() {
+        let res = blackman_harris(8);
+        let expected_result = [
+            6.0e-05, 0.03339172, 0.3328335, 0.8893698, 0.8893698, 0.3328335, 0.03339172, 6.0e-05,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn test_create_nuttall() {
+        let res = nuttall(8);
+        let expected_result = [
+            0., 0.03114274, 0.32641681, 0.88762846, 0.88762846, 0.32641681, 0.03114274, 0.,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn test_create_flat_top() {
+        let res = flat_top(9);
+        let expected_result = [
+            -0.00042105,
+            -0.02687219,
+            -0.05473684,
+            0.44413536,
+            1.0,
+            0.44413536,
+            -0.05473684,
+            -0.02687219,
+            -0.00042105,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn test_create_tukey() {
+        let res = tukey(11, 0.5);
+        let expected_result = [
+            0., 0.3454915, 0.9045085, 1., 1., 1., 1., 1., 0.9045085, 0.3454915, 0.,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+
+        assert!(tukey(8, 0.0).iter().all(|v| *v == 1.));
+        tukey(10, 1.0)
+            .iter()
+            .zip(hann(10).iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn test_create_gaussian() {
+        let res = gaussian(9, 0.4);
+        let expected_result = [
+            0.04393693, 0.17242162, 0.45783336, 0.82257756, 1., 0.82257756, 0.45783336, 0.17242162,
+            0.04393693,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+    }
+
+    #[test]
+    fn test_create_chebyshev() {
+        let res = chebyshev(7, 50.);
+        let expected_result = [
+            0.1116911, 0.41963, 0.8137736, 1., 0.8137736, 0.41963, 0.1116911,
+        ];
+
+        res.iter()
+            .zip(expected_result.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
+
+        // past the main lobe the response never rises above the requested level
+        let res = chebyshev(32, 80.);
+        let magnitude = |freq: f64| {
+            let (re, im) = res.iter().enumerate().fold((0., 0.), |(re, im), (n, w)| {
+                let phase = freq * n as f64;
+                (re + *w as f64 * phase.cos(), im - *w as f64 * phase.sin())
+            });
+            (re * re + im * im).sqrt()
+        };
+        let dc = magnitude(0.);
+        let mut freqs = (1..=2048).map(|i| std::f64::consts::PI * i as f64 / 2048.);
+        let mut prev = dc;
+        let sidelobes: Vec<f64> = freqs
+            .by_ref()
+            .map(magnitude)
+            .skip_while(|m| {
+                let falling = *m < prev;
+                prev = *m;
+                falling
+            })
+            .collect();
+        let worst = 20. * (sidelobes.iter().fold(0_f64, |m, v| m.max(*v)) / dc).log10();
+        assert!((worst + 80.).abs() < 0.1, "{}", worst);
+    }
+
+    #[test]
+    fn into_matches_allocating() {
+        let mut win = [0.0_f32; 16];
+        let check = |win: &[f32], expected: Vec<f32>| {
+            assert_eq!(win.to_vec(), expected);
+        };
+
+        hann_into(&mut win);
+        check(&win, hann(16));
+        kaiser_into(&mut win, 4.);
+        check(&win, kaiser(16, 4.));
+        blackman_into(&mut win);
+        check(&win, blackman(16));
+        blackman_harris_into(&mut win);
+        check(&win, blackman_harris(16));
+        nuttall_into(&mut win);
+        check(&win, nuttall(16));
+        flat_top_into(&mut win);
+        check(&win, flat_top(16));
+        tukey_into(&mut win, 0.3);
+        check(&win, tukey(16, 0.3));
+        gaussian_into(&mut win, 0.4);
+        check(&win, gaussian(16, 0.4));
+        chebyshev_into(&mut win, 60.);
+        check(&win, chebyshev(16, 60.));
+
+        let mut win = [0.0_f32; 15];
+        sinc_into(&mut win, 0.25);
+        check(&win, sinc(15, 0.25));
+        sinc_scaled_into(&mut win, 0.25);
+        check(&win, sinc_scaled(15, 0.25));
+    }
+
+    #[test]
+    fn test_iter_matches_allocating() {
+        let check = |iter: &mut dyn ExactSizeIterator<Item = f32>, expected: Vec<f32>| {
+            assert_eq!(iter.len(), expected.len());
+            assert_eq!(iter.collect::<Vec<f32>>(), expected);
+        };
+        check(&mut hann_iter(16), hann(16));
+        check(&mut kaiser_iter(16, 8.), kaiser(16, 8.));
+        check(&mut blackman_iter(16), blackman(16));
+        check(&mut blackman_harris_iter(16), blackman_harris(16));
+        check(&mut nuttall_iter(16), nuttall(16));
+        check(&mut flat_top_iter(16), flat_top(16));
+        check(&mut tukey_iter(16, 0.3), tukey(16, 0.3));
+        check(&mut gaussian_iter(16, 0.4), gaussian(16, 0.4));
+        check(&mut sinc_iter(15, 0.25), sinc(15, 0.25));
+        check(&mut sinc_scaled_iter(15, 0.25), sinc_scaled(15, 0.25));
+
+        // a 2^24 point window without the 128 MiB f64 buffer
+        let sum = hann_iter::<f64>(1 << 24).sum::<f64>();
+        assert!((sum / ((1 << 24) - 1) as f64 - 0.5).abs() < 1e-9, "{}", sum);
+    }
+
+    #[test]
+    fn test_bessel_i0() {
+        assert_eq!(bessel_i0(0.), 1.);
+        [
+            (1., 1.2660658777520083),
+            (20., 43558282.559553533),
+            (50., 2.9325537838493363e20),
+        ]
+        .iter()
+        .for_each(|(x, expected)| {
+            let rel = (bessel_i0(*x) - expected).abs() / expected;
+            assert!(rel < 4. * f64::EPSILON, "x: {}, rel: {}", x, rel);
+        });
+
+        // non-finite inputs return straight away instead of spinning on NaN != NaN
+        assert!(bessel_i0(f64::NAN).is_nan());
+        assert_eq!(bessel_i0(f64::INFINITY), f64::INFINITY);
+        assert_eq!(bessel_i0(f64::NEG_INFINITY), f64::INFINITY);
+        assert_eq!(bessel_i0(1e6), f64::INFINITY);
+        assert!(kaiser(16, f32::NAN).iter().all(|v| v.is_nan()));
+        assert_eq!(kaiser_iter::<f64>(16, f64::INFINITY).count(), 16);
+
+        // a steep kaiser in f32 is the f64 one rounded once
+        let mut win = [0.0_f64; 127];
+        kaiser_into(&mut win, 30.);
+        kaiser(127, 30.)
+            .iter()
+            .zip(win.iter())
+            .for_each(|(a, b)| assert_eq!(*a, *b as f32));
+    }
+
+    #[test]
+    fn test_f64_windows() {
+        let mut win = [0.0_f64; 127];
+        kaiser_into(&mut win, 10.);
+        assert!((win[0] - 3.55149375e-04).abs() < 1e-12);
+        assert!((win[57] - 9.57803242e-01).abs() < 1e-9, "{}", win[57]);
+        assert_eq!(win[63], 1.);
+
+        hann_into(&mut win);
+        win.iter().enumerate().for_each(|(n, w)| {
+            let expected = 0.5 - 0.5 * (2. * std::f64::consts::PI * n as f64 / 126.).cos();
+            assert!((w - expected).abs() < 1e-15, "w: {}, e: {}", w, expected);
+        });
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut coherent = hann(8);
+        let scale = normalize(&mut coherent, Normalization::CoherentGain);
+        assert!((scale - 8. / 3.5).abs() < 1e-5, "{}", scale);
+        assert!((coherent.iter().sum::<f32>() / 8. - 1.).abs() < 1e-6);
+
+        let mut power = vec![0.0_f64; 1024];
+        blackman_harris_into(&mut power);
+        let original = power.clone();
+        let scale = normalize(&mut power, Normalization::Power);
+        assert!((power.iter().map(|w| w * w).sum::<f64>() / 1024. - 1.).abs() < 1e-12);
+        power
+            .iter()
+            .zip(original.iter())
+            .for_each(|(p, o)| assert_eq!(*p, o * scale));
+    }
+
+    #[test]
+    fn test_hilbert() {
+        let kernel = hilbert(&blackman(127));
+        let center = 63;
+        assert_eq!(kernel[center], 0.);
+        (1..=center).for_each(|k| {
+            assert!((kernel[center + k] + kernel[center - k]).abs() < 1e-7);
+            if k % 2 == 0 {
+                assert_eq!(kernel[center + k], 0.);
+            }
+        });
+
+        // the antisymmetric taps give a purely imaginary response, its magnitude is flat
+        // away from DC and nyquist
+        (15..=85).for_each(|pct| {
+            let w = std::f64::consts::PI * pct as f64 / 100.;
+            let mag: f64 = (1..=center)
+                .map(|k| 2. * kernel[center + k] as f64 * (w * k as f64).sin())
+                .sum();
+            assert!((mag - 1.).abs() < 1e-2, "{} at {}%", mag, pct);
+        });
+
+        let mut rect = [0.0_f64; 7];
+        hilbert_into(&mut rect, &[1.0; 7]);
+        let third = 2. / (3. * std::f64::consts::PI);
+        let first = 2. / std::f64::consts::PI;
+        assert_eq!(rect, [-third, 0., -first, 0., first, 0., third]);
+    }
+
+    #[test]
+    fn test_window_info() {
+        let rect = WindowInfo::new(&[1.0_f32; 1024]);
+        assert!((rect.enbw - 1.).abs() < 1e-6);
+        assert!((rect.coherent_gain - 1.).abs() < 1e-6);
+        assert!((rect.scallop_loss_db - 3.9224).abs() < 1e-3, "{:?}", rect);
+
+        let info = WindowInfo::new(&hann(4097));
+        assert!((info.enbw - 1.5).abs() < 1e-3, "{:?}", info);
+        assert!((info.coherent_gain - 0.5).abs() < 1e-3, "{:?}", info);
+        assert!((info.scallop_loss_db - 1.4236).abs() < 1e-2, "{:?}", info);
+
+        // flat top trades noise bandwidth for almost no scalloping
+        let flat = WindowInfo::new(&flat_top(4097));
+        assert!(flat.enbw > 3.7 && flat.enbw < 3.9, "{:?}", flat);
+        assert!(flat.scallop_loss_db < 0.02, "{:?}", flat);
+    }
+
+    #[test]
+    fn test_cola() {
+        let win = hann(9);
+        assert!(check_cola(&win, 4));
+        assert!(!check_cola(&win, 3));
+        assert!(!check_cola(&win, 0));
+        assert_eq!(recommended_hops(&win), vec![4, 2, 1]);
+
+        let mut win = vec![0.0_f64; 13];
+        blackman_into(&mut win);
+        assert_eq!(recommended_hops(&win), vec![4, 3, 2, 1]);
+        // a 4.5% ripple at hop 5
+        assert!(!check_cola(&win, 5));
+    }
+
+    #[test]
+    fn test_kaiser_order() {
+        // 65 dB with a 24 Hz transition at 1 kHz
+        let (taps, beta) = kaiser_order(65., 24. / 500.);
+        assert_eq!(taps, 167);
+        assert!((beta - 6.20426).abs() < 1e-4, "{}", beta);
+
+        assert!(
+            (kaiser_beta(40.) - 3.39532).abs() < 1e-4,
+            "{}",
+            kaiser_beta(40.)
+        );
+        assert_eq!(kaiser_beta(20.), 0.);
+        // tighter specs need more taps
+        assert!(kaiser_order(90., 0.05).0 > kaiser_order(60., 0.05).0);
+        assert!(kaiser_order(60., 0.02).0 > kaiser_order(60., 0.05).0);
+    }
+
     #[test]
     fn test_create_large_hann() {
         let res = hann(127);
@@ -381,6 +1094,26 @@ mod tests {
             .for_each(|(a, b)| assert!((a - b).abs() < 1e-6, "a: {}, b: {}", a, b));
     }
 
+    #[test]
+    fn test_sinc_length_and_scaling() {
+        (1..12).for_each(|size| assert_eq!(sinc(size, 0.5).len(), size));
+
+        // no center tap, the middle pair sits half a sample either side
+        let even = sinc(10, 0.5);
+        even.iter()
+            .zip(even.iter().rev())
+            .for_each(|(a, b)| assert_eq!(a, b));
+        let half = std::f32::consts::FRAC_PI_4;
+        assert!((even[4] - half.sin() / half).abs() < 1e-6);
+
+        let scaled = sinc_scaled(1001, 0.25);
+        sinc(1001, 0.25)
+            .iter()
+            .zip(scaled.iter())
+            .for_each(|(s, c)| assert_eq!(s * 0.25, *c));
+        assert!((scaled.iter().sum::<f32>() - 1.).abs() < 1e-2);
+    }
+
     #[test]
     fn test_create_large_sinc() {
         let res = sinc(127, 0.5);
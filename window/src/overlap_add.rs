@@ -0,0 +1,168 @@
+use sample::MemoryFootprint;
+
+use crate::check_cola;
+
+/// Streaming STFT framing. Every `hop` samples the last `window.len()` samples of input are
+/// windowed and handed to the frame callback, and whatever it leaves in the frame is added
+/// into the output. The overlapping windows are divided back out, so a callback that does
+/// nothing gives the input back delayed by `latency_samples`.
+#[derive(Debug, Clone)]
+pub struct OverlapAdd {
+    window: Vec<f32>,
+    hop: usize,
+    input: Vec<f32>,
+    frame: Vec<f32>,
+    accum: Vec<f32>,
+    output: Vec<f32>,
+    pos: usize,
+    scale: f32,
+}
+
+impl OverlapAdd {
+    /// The window has to be COLA at `hop`, see `recommended_hops`
+    pub fn new(window: Vec<f32>, hop: usize) -> Self {
+        assert!(
+            check_cola(&window, hop),
+            "window doesn't overlap-add to a constant at this hop"
+        );
+        let size = window.len();
+        let scale = hop as f32 / window.iter().sum::<f32>();
+        OverlapAdd {
+            window,
+            hop,
+            input: vec![0.0; size],
+            frame: vec![0.0; size],
+            accum: vec![0.0; size],
+            output: vec![0.0; hop],
+            pos: 0,
+            scale,
+        }
+    }
+
+    /// Runs `block` through in place, calling `frame_fn` on every frame that completes in it
+    pub fn process<F: FnMut(&mut [f32])>(&mut self, block: &mut [f32], mut frame_fn: F) {
+        let fill_start = self.window.len() - self.hop;
+        for sample in block.iter_mut() {
+            self.input[fill_start + self.pos] = *sample;
+            *sample = self.output[self.pos];
+            self.pos += 1;
+
+            if self.pos == self.hop {
+                self.pos = 0;
+                self.run_frame(&mut frame_fn);
+            }
+        }
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.window.len()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Always the frame size
+    pub fn latency_samples(&self) -> usize {
+        self.window.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.input.iter_mut().for_each(|x| *x = 0.0);
+        self.accum.iter_mut().for_each(|x| *x = 0.0);
+        self.output.iter_mut().for_each(|x| *x = 0.0);
+        self.pos = 0;
+    }
+
+    fn run_frame<F: FnMut(&mut [f32])>(&mut self, frame_fn: &mut F) {
+        let hop = self.hop;
+        let size = self.window.len();
+
+        self.frame
+            .iter_mut()
+            .zip(self.input.iter().zip(self.window.iter()))
+            .for_each(|(f, (x, w))| *f = x * w);
+        frame_fn(&mut self.frame);
+
+        let scale = self.scale;
+        self.accum
+            .iter_mut()
+            .zip(self.frame.iter())
+            .for_each(|(a, f)| *a += f * scale);
+        self.output.copy_from_slice(&self.accum[..hop]);
+        self.accum.copy_within(hop.., 0);
+        self.accum[size - hop..].iter_mut().for_each(|a| *a = 0.0);
+        self.input.copy_within(hop.., 0);
+    }
+}
+
+impl MemoryFootprint for OverlapAdd {
+    fn memory_footprint(&self) -> usize {
+        self.window.memory_footprint()
+            + self.input.memory_footprint()
+            + self.frame.memory_footprint()
+            + self.accum.memory_footprint()
+            + self.output.memory_footprint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hann;
+
+    fn signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| (n as f32 * 0.031).sin() + 0.3 * (n as f32 * 0.47).cos())
+            .collect()
+    }
+
+    #[test]
+    fn passthrough_reconstructs_delayed_input() {
+        let input = signal(2000);
+        [1, 7, 16, 300].iter().for_each(|block_len| {
+            let mut ola = OverlapAdd::new(hann(65), 16);
+            let mut frames = 0;
+            let mut out = input.clone();
+            out.chunks_mut(*block_len)
+                .for_each(|block| ola.process(block, |_| frames += 1));
+
+            assert_eq!(frames, 2000 / 16);
+            let latency = ola.latency_samples();
+            out.iter()
+                .take(latency)
+                .for_each(|y| assert!(y.abs() < 1e-6));
+            out.iter()
+                .skip(latency)
+                .zip(input.iter())
+                .for_each(|(y, x)| assert!((y - x).abs() < 1e-5, "{} {}", y, x));
+        });
+    }
+
+    #[test]
+    fn frame_edits_reach_output_and_reset_clears() {
+        let mut ola = OverlapAdd::new(hann(33), 8);
+        let mut block = signal(256);
+        ola.process(&mut block, |frame| {
+            frame.iter_mut().for_each(|f| *f *= -0.5)
+        });
+        let mut passthrough = OverlapAdd::new(hann(33), 8);
+        let mut expected = signal(256);
+        passthrough.process(&mut expected, |_| {});
+        block
+            .iter()
+            .zip(expected.iter())
+            .for_each(|(y, x)| assert!((y + 0.5 * x).abs() < 1e-5));
+
+        ola.reset();
+        let mut silence = vec![0.0_f32; 64];
+        ola.process(&mut silence, |_| {});
+        assert!(silence.iter().all(|y| *y == 0.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_non_cola_hop() {
+        OverlapAdd::new(hann(65), 24);
+    }
+}